@@ -1,6 +1,18 @@
-use crate::exchanges::Exchange;
+use crate::clock::ClockSync;
+use crate::exchanges::{symbols::stable_quote, BookIntegrity, Exchange, MarketType, SymbolId};
+use crate::latency::{FeedLatency, LatencyTracker};
+use crate::metrics;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+// Capacidad del canal de notificaciones. Si el detector se queda atrás recibe
+// `Lagged` y hace un barrido completo, así que no hace falta que sea enorme.
+const UPDATE_CHANNEL_CAPACITY: usize = 4096;
+// Un FX más viejo que esto ya no se considera vivo: se asume paridad 1:1
+const FX_MAX_AGE_MS: u64 = 60_000;
 
 // 1. Definimos la estructura del Libro (Bid y Ask)
 #[derive(Debug, Clone, Copy)]
@@ -9,48 +21,207 @@ pub struct MarketBook {
     pub ask: f64,      // El precio más bajo al que alguien quiere VENDER (tú compras aquí)
     pub bid_size: f64,
     pub ask_size: f64,
+    pub exchange_ts: Option<u64>, // Hora del evento según el venue (solo informativa)
+    pub received_at: Instant,     // Llegada local: base de toda medida de frescura
+    pub fx: Option<FxConversion>, // Conversión aplicada si el venue no cotiza en USDT
+    pub integrity: BookIntegrity,
+}
+
+impl MarketBook {
+    // Edad del libro con reloj monotónico: comparable entre venues
+    pub fn age_ms(&self) -> u64 {
+        self.received_at.elapsed().as_millis() as u64
+    }
+}
+
+// Libro que quedó fuera de la detección hasta re-sincronizar (para el dashboard)
+#[derive(Debug, Clone, Serialize)]
+pub struct ResyncingBook {
+    pub symbol: String,
+    pub exchange: Exchange,
+    pub market_type: MarketType,
+}
+
+// Cotización de una stablecoin en USDT
+#[derive(Debug, Clone, Copy)]
+pub struct FxRate {
+    pub rate: f64,       // USDT por unidad
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FxConversion {
+    pub from: &'static str,
+    pub rate: f64,
+    pub live: bool, // false = no había FX fresco y se asumió 1:1
+}
+
+// Lo mismo, visible en cada oportunidad
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FxAssumption {
+    pub exchange: Exchange,
+    pub from: String,
+    pub to: String,
+    pub rate: f64,
+    pub live: bool,
+}
+
+impl FxAssumption {
+    pub fn from_book(exchange: Exchange, book: &MarketBook) -> Option<Self> {
+        let fx = book.fx?;
+        Some(Self { exchange, from: fx.from.to_string(), to: "USDT".to_string(), rate: fx.rate, live: fx.live })
+    }
+}
+
+// Último funding conocido de un perp
+#[derive(Debug, Clone, Copy)]
+pub struct FundingInfo {
+    pub rate: f64,              // Por intervalo de liquidación (fracción)
+    pub interval_hours: f64,
+    pub next_funding_time: u64,
     pub timestamp: u64,
 }
 
+impl FundingInfo {
+    pub fn hourly_rate(&self) -> f64 {
+        self.rate / self.interval_hours
+    }
+}
+
 #[derive(Clone)]
 pub struct PriceAggregator {
     // 2. Ahora guardamos Libros enteros, no solo precios sueltos
-    // Map: Symbol -> (Exchange -> MarketBook)
-    books: Arc<DashMap<String, DashMap<Exchange, MarketBook>>>,
+    // Map: (Symbol, Spot/Perp) -> (Exchange -> MarketBook)
+    books: Arc<DashMap<(String, MarketType), DashMap<Exchange, MarketBook>>>,
+    // Map: Symbol -> (Exchange -> FundingInfo)
+    funding: Arc<DashMap<String, DashMap<Exchange, FundingInfo>>>,
+    // Map: Moneda ("USDC", "USD") -> USDT por unidad
+    fx: Arc<DashMap<&'static str, FxRate>>,
+    // Latencia de cada feed (recepción local vs hora del venue)
+    latency: LatencyTracker,
+    clock: ClockSync,
+    // Aviso de "este símbolo cambió" (y en qué mercado) para la detección por eventos
+    updates_tx: broadcast::Sender<(String, MarketType)>,
 }
 
 impl PriceAggregator {
-    pub fn new() -> Self {
+    pub fn new(clock: ClockSync) -> Self {
+        let (updates_tx, _) = broadcast::channel(UPDATE_CHANNEL_CAPACITY);
         Self {
             // Inicializamos el mapa de libros
             books: Arc::new(DashMap::new()),
+            funding: Arc::new(DashMap::new()),
+            fx: Arc::new(DashMap::new()),
+            latency: LatencyTracker::default(),
+            clock,
+            updates_tx,
         }
     }
 
-    // Actualizamos con Bid y Ask
-    pub fn update(&self, symbol: String, exchange: Exchange, book: MarketBook) {
+    // Actualizamos con Bid y Ask y avisamos a quien escuche qué símbolo cambió.
+    // Los precios se guardan siempre en USDT para que el detector compare lo mismo.
+    pub fn update(&self, symbol: String, exchange: Exchange, market_type: MarketType, mut book: MarketBook) {
+        let latency_ms = book
+            .exchange_ts
+            .map(|exchange_ts| self.latency.record(exchange, self.clock.to_local(exchange, exchange_ts), book.received_at));
+        metrics::book_update(exchange, market_type, latency_ms);
+        if let Some(ccy) = stable_quote(exchange) {
+            if SymbolId::parse(&symbol).is_some_and(|id| id.quote == "USDT") {
+                book = self.convert_quote(book, ccy);
+            }
+        }
         self.books
-            .entry(symbol)
-            .or_insert_with(DashMap::new)
+            .entry((symbol.clone(), market_type))
+            .or_default()
             .insert(exchange, book);
+        // Sin suscriptores `send` devuelve Err; no es un fallo
+        let _ = self.updates_tx.send((symbol, market_type));
     }
 
-    // Obtener todos los libros de un símbolo para compararlos
-    pub fn get_books(&self, symbol: &str) -> Option<Vec<(Exchange, MarketBook)>> {
-        self.books.get(symbol).map(|map| {
+    // Receptor de símbolos actualizados con su mercado (uno por consumidor)
+    pub fn subscribe(&self) -> broadcast::Receiver<(String, MarketType)> {
+        self.updates_tx.subscribe()
+    }
+
+    // Obtener todos los libros de un símbolo para compararlos.
+    // Un libro re-sincronizando no es confiable: ninguna estrategia lo ve.
+    pub fn get_books(&self, symbol: &str, market_type: MarketType) -> Option<Vec<(Exchange, MarketBook)>> {
+        self.books.get(&(symbol.to_string(), market_type)).map(|map| {
             map.iter()
+                .filter(|entry| entry.value().integrity == BookIntegrity::Synced)
                 .map(|entry| (*entry.key(), *entry.value()))
                 .collect()
         })
     }
 
-    pub fn get_all_symbols(&self) -> Vec<String> {
-        self.books.iter().map(|entry| entry.key().clone()).collect()
+    pub fn get_resyncing(&self) -> Vec<ResyncingBook> {
+        self.books
+            .iter()
+            .flat_map(|entry| {
+                let (symbol, market_type) = entry.key().clone();
+                entry.value()
+                    .iter()
+                    .filter(|book| book.value().integrity == BookIntegrity::Resyncing)
+                    .map(|book| ResyncingBook { symbol: symbol.clone(), exchange: *book.key(), market_type })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn convert_quote(&self, mut book: MarketBook, ccy: &'static str) -> MarketBook {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let (rate, live) = match self.get_fx(ccy) {
+            Some(fx) if now.saturating_sub(fx.timestamp) <= FX_MAX_AGE_MS => (fx.rate, true),
+            _ => (1.0, false),
+        };
+        book.bid *= rate;
+        book.ask *= rate;
+        book.fx = Some(FxConversion { from: ccy, rate, live });
+        book
+    }
+
+    pub fn feed_latency(&self) -> Vec<FeedLatency> {
+        self.latency.snapshot()
+    }
+
+    pub fn update_fx(&self, ccy: &'static str, rate: FxRate) {
+        self.fx.insert(ccy, rate);
+    }
+
+    pub fn get_fx(&self, ccy: &str) -> Option<FxRate> {
+        self.fx.get(ccy).map(|entry| *entry.value())
+    }
+
+    pub fn update_funding(&self, symbol: String, exchange: Exchange, info: FundingInfo) {
+        self.funding
+            .entry(symbol)
+            .or_default()
+            .insert(exchange, info);
+    }
+
+    pub fn get_funding(&self, symbol: &str) -> Option<Vec<(Exchange, FundingInfo)>> {
+        self.funding.get(symbol).map(|map| {
+            map.iter()
+                .map(|entry| (*entry.key(), *entry.value()))
+                .collect()
+        })
+    }
+
+    pub fn get_funding_symbols(&self) -> Vec<String> {
+        self.funding.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    pub fn get_all_symbols(&self, market_type: MarketType) -> Vec<String> {
+        self.books
+            .iter()
+            .filter(|entry| entry.key().1 == market_type)
+            .map(|entry| entry.key().0.clone())
+            .collect()
     }
     
-    pub fn get_exchange_count(&self, symbol: &str) -> usize {
+    pub fn get_exchange_count(&self, symbol: &str, market_type: MarketType) -> usize {
         self.books
-            .get(symbol)
+            .get(&(symbol.to_string(), market_type))
             .map(|map| map.len())
             .unwrap_or(0)
     }
@@ -0,0 +1,480 @@
+// src/api.rs
+//
+// API REST junto a /ws, para scripts y otros servicios que quieren consultar
+// el bot sin mantener un socket abierto. Todo GET y JSON, con rol viewer (ver auth.rs):
+//   /health                solo {"healthy"} (503 si algún feed está caído), sin auth para probes
+//   /api/health            detalle por feed, autenticado
+//   /api/config            configuración efectiva
+//   /api/opportunities     oportunidades actuales (?symbol=&min_net_pct=)
+//   /api/balances          balances del simulador por venue
+//   /api/positions         posiciones de convergencia y órdenes maker vivas
+//   /api/trades            historial paginado (?strategy=&symbol=&exchange=&since=&until=&page=&limit=)
+//   /api/feeds             estado por exchange: conexión, latencia, reloj, libros re-sincronizando
+//   /api/clients           clientes del WebSocket: conectados, lentos y cortados
+//   /metrics               métricas en formato Prometheus (ver metrics.rs)
+//   /api/control           pausas, límites vigentes y kill switch
+//
+// Lo que vive en el loop principal (oportunidades, balances, posiciones) se lee
+// de la última foto publicada para el dashboard.
+//
+// Control (POST, rol operator; sin usuarios configurados no se habilita):
+//   /api/control/pause, /api/control/resume      {"symbol"?, "exchange"?}; vacío = global
+//   /api/control/limits                          cambios parciales de los límites
+//   /api/control/instruments/refresh             recarga la metadata de los venues
+//   /api/control/kill-switch                     {"reason"}: cancela órdenes, cierra posiciones y frena todo
+//   /api/control/kill-switch/reset               rearma; queda en pausa global hasta un resume
+//
+// Escucha en `BIND_ADDR` (127.0.0.1:3030 por defecto), con TLS si hay
+// `TLS_CERT_PATH` y `TLS_KEY_PATH` (PEM).
+
+use crate::aggregator::{PriceAggregator, ResyncingBook};
+use crate::auth::{self, Auth, AuthError, Principal, Role};
+use crate::arbitrage::convergence::ConvergencePosition;
+use crate::arbitrage::maker_taker::RestingQuote;
+use crate::arbitrage::{
+    ArbitrageOpportunity, BasisOpportunity, ConvergenceConfig, ConvergenceSnapshot, FundingOpportunity, MakerTakerConfig,
+    MakerTakerSnapshot,
+};
+use crate::clock::{ClockStatus, ClockSync};
+use crate::control::{ControlAction, ControlError, ControlHandle, RuntimeLimits, Scope, TradingControl};
+use crate::fees::{FeeConfig, FeeStatus};
+use crate::health::{FeedHealth, FeedHealthStatus, HealthReport};
+use crate::journal::{Journal, TradeFilter};
+use crate::latency::FeedLatency;
+use crate::metrics;
+use crate::simulator::{SimStats, TradeLog};
+use crate::stream::ClientMetrics;
+use crate::supervisor::ShutdownSignal;
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::watch;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Rejection, Reply};
+
+#[derive(Serialize, Clone)]
+pub struct DashboardPayload {
+    pub opportunities: Vec<ArbitrageOpportunity>,
+    pub funding_opportunities: Vec<FundingOpportunity>,
+    pub basis_opportunities: Vec<BasisOpportunity>,
+    pub convergence: ConvergenceSnapshot,
+    pub maker_taker: MakerTakerSnapshot,
+    pub fees: Vec<FeeStatus>,
+    pub resyncing_books: Vec<ResyncingBook>,
+    pub feed_latency: Vec<FeedLatency>,
+    pub clock: Vec<ClockStatus>,
+    pub health: HealthReport,
+    pub stats: SimStats,
+    pub recent_trades: Vec<TradeLog>,
+    pub control: TradingControl,
+}
+
+// Parámetros con los que arrancó el motor; los límites vigentes van aparte
+#[derive(Serialize, Clone)]
+pub struct EngineConfig {
+    pub perp_symbols: Vec<String>,
+    pub spot_symbols: Vec<String>,
+    pub maker_taker: MakerTakerConfig,
+    pub convergence: ConvergenceConfig,
+    pub funding_holding_hours: f64,
+    pub funding_min_net_edge_pct: f64,
+    pub basis_min_net_pct: f64,
+    pub journal_path: String,
+    pub state_path: String,
+    pub tick_record_dir: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ConfigView<'a> {
+    #[serde(flatten)]
+    engine: &'a EngineConfig,
+    limits: Option<&'a RuntimeLimits>, // Cambian por la API de control
+    fees: Vec<FeeStatus>,              // Cambian con el volumen y el refresh de comisiones reales
+}
+
+#[derive(Serialize)]
+struct OpportunitiesView {
+    opportunities: Vec<ArbitrageOpportunity>,
+    funding: Vec<FundingOpportunity>,
+    basis: Vec<BasisOpportunity>,
+}
+
+#[derive(Serialize)]
+struct PositionsView {
+    convergence: Vec<ConvergencePosition>,
+    maker_quotes: Vec<RestingQuote>,
+}
+
+#[derive(Serialize)]
+struct FeedsView {
+    healthy: bool,
+    feeds: Vec<FeedHealthStatus>,
+    latency: Vec<FeedLatency>,
+    clock: Vec<ClockStatus>,
+    resyncing_books: Vec<ResyncingBook>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct KillSwitchRequest {
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct OpportunityQuery {
+    symbol: Option<String>,
+    min_net_pct: Option<f64>,
+}
+
+// Handle compartido por todas las rutas
+#[derive(Clone)]
+pub struct ApiState {
+    pub latest: watch::Receiver<Option<Arc<DashboardPayload>>>,
+    pub journal: Journal,
+    pub health: FeedHealth,
+    pub aggregator: PriceAggregator,
+    pub clock: ClockSync,
+    pub fees: FeeConfig,
+    pub config: Arc<EngineConfig>,
+    pub control: ControlHandle,
+    pub auth: Auth,
+    pub ws_clients: ClientMetrics,
+}
+
+const DEFAULT_BIND_ADDR: &str = "127.0.0.1:3030";
+
+pub struct TlsPaths {
+    pub cert: String,
+    pub key: String,
+}
+
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    pub tls: Option<TlsPaths>,
+}
+
+impl ServerConfig {
+    // Se valida al arrancar: un error acá es de configuración y no se arregla reintentando
+    pub fn from_env(auth: &Auth) -> Result<Self> {
+        let raw = std::env::var("BIND_ADDR").unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string());
+        let bind: SocketAddr = raw.parse().with_context(|| format!("BIND_ADDR inválido: {:?}", raw))?;
+        if auth.is_open() && !bind.ip().is_loopback() {
+            bail!("sin API_USERS solo se puede escuchar en loopback, no en {}", bind);
+        }
+        let tls = match (std::env::var("TLS_CERT_PATH").ok(), std::env::var("TLS_KEY_PATH").ok()) {
+            (Some(cert), Some(key)) => {
+                for path in [&cert, &key] {
+                    if !Path::new(path).is_file() {
+                        bail!("no existe el archivo TLS {}", path);
+                    }
+                }
+                Some(TlsPaths { cert, key })
+            }
+            (None, None) => None,
+            _ => bail!("TLS necesita TLS_CERT_PATH y TLS_KEY_PATH juntos"),
+        };
+        Ok(Self { bind, tls })
+    }
+}
+
+// Si no puede escuchar (puerto ocupado, certificado inválido) vuelve y el supervisor reintenta
+pub async fn serve<F>(routes: F, config: Arc<ServerConfig>, mut signal: ShutdownSignal)
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let shutdown = async move { signal.wait().await };
+    let server = warp::serve(routes);
+    match &config.tls {
+        Some(tls) => match server.tls().cert_path(&tls.cert).key_path(&tls.key).try_bind_with_graceful_shutdown(config.bind, shutdown) {
+            Ok((addr, running)) => {
+                tracing::info!("🔒 Servidor en https://{} (wss://{}/ws)", addr, addr);
+                running.await;
+            }
+            Err(e) => tracing::error!("❌ No se pudo levantar el servidor TLS en {}: {}", config.bind, e),
+        },
+        None => match server.try_bind_with_graceful_shutdown(config.bind, shutdown) {
+            Ok((addr, running)) => {
+                tracing::info!("🌐 Servidor en http://{} (ws://{}/ws)", addr, addr);
+                running.await;
+            }
+            Err(e) => tracing::error!("❌ No se pudo levantar el servidor en {}: {}", config.bind, e),
+        },
+    }
+}
+
+impl ApiState {
+    fn latest(&self) -> Option<Arc<DashboardPayload>> {
+        self.latest.borrow().clone()
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": message })), status).into_response()
+}
+
+// Hasta el primer publish del loop no hay foto que mostrar
+fn not_ready() -> warp::reply::Response {
+    error(StatusCode::SERVICE_UNAVAILABLE, "el motor todavía no publicó datos")
+}
+
+fn health(state: &ApiState) -> warp::reply::Response {
+    let report = state.health.report();
+    let status = if report.healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    warp::reply::with_status(warp::reply::json(&report), status).into_response()
+}
+
+// Sin credenciales: el estado por feed no se expone, solo el veredicto
+fn probe(state: &ApiState) -> warp::reply::Response {
+    let healthy = state.health.report().healthy;
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    warp::reply::with_status(warp::reply::json(&serde_json::json!({ "healthy": healthy })), status).into_response()
+}
+
+fn config(state: &ApiState) -> warp::reply::Response {
+    let latest = state.latest();
+    let limits = latest.as_ref().map(|p| &p.control.limits);
+    warp::reply::json(&ConfigView { engine: &state.config, limits, fees: state.fees.status() }).into_response()
+}
+
+fn opportunities(state: &ApiState, query: OpportunityQuery) -> warp::reply::Response {
+    let Some(latest) = state.latest() else { return not_ready() };
+    let symbol_ok = |s: &str| query.symbol.as_deref().is_none_or(|q| q == s);
+    let min_net = query.min_net_pct.unwrap_or(f64::NEG_INFINITY);
+    warp::reply::json(&OpportunitiesView {
+        opportunities: latest.opportunities.iter().filter(|o| symbol_ok(&o.symbol) && o.net_profit_pct >= min_net).cloned().collect(),
+        funding: latest.funding_opportunities.iter().filter(|o| symbol_ok(&o.symbol)).cloned().collect(),
+        basis: latest.basis_opportunities.iter().filter(|o| symbol_ok(&o.symbol)).cloned().collect(),
+    })
+    .into_response()
+}
+
+fn balances(state: &ApiState) -> warp::reply::Response {
+    let Some(latest) = state.latest() else { return not_ready() };
+    warp::reply::json(&latest.stats).into_response()
+}
+
+fn positions(state: &ApiState) -> warp::reply::Response {
+    let Some(latest) = state.latest() else { return not_ready() };
+    warp::reply::json(&PositionsView {
+        convergence: latest.convergence.open_positions.clone(),
+        maker_quotes: latest.maker_taker.resting.clone(),
+    })
+    .into_response()
+}
+
+fn trades(state: &ApiState, filter: TradeFilter) -> warp::reply::Response {
+    match state.journal.trade_page(&filter) {
+        Ok(page) => warp::reply::json(&page).into_response(),
+        Err(e) => {
+            tracing::warn!("⚠️ API: no se pudo leer el historial: {:?}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "no se pudo leer el historial")
+        }
+    }
+}
+
+fn feeds(state: &ApiState) -> warp::reply::Response {
+    let report = state.health.report();
+    warp::reply::json(&FeedsView {
+        healthy: report.healthy,
+        feeds: report.feeds,
+        latency: state.aggregator.feed_latency(),
+        clock: state.clock.status(),
+        resyncing_books: state.aggregator.get_resyncing(),
+    })
+    .into_response()
+}
+
+fn control_status(state: &ApiState) -> warp::reply::Response {
+    let Some(latest) = state.latest() else { return not_ready() };
+    warp::reply::json(&latest.control).into_response()
+}
+
+async fn control(
+    state: ApiState,
+    caller: Result<Principal, AuthError>,
+    addr: Option<SocketAddr>,
+    action: Result<ControlAction, String>,
+) -> warp::reply::Response {
+    if state.auth.is_open() {
+        return error(StatusCode::FORBIDDEN, "API de control deshabilitada: falta API_USERS");
+    }
+    let denied = match caller {
+        Ok(principal) if principal.role >= Role::Operator => Ok(principal.name),
+        Ok(principal) => Err((principal.name, AuthError::Forbidden(Role::Operator))),
+        Err(e) => Err((addr.map(|a| a.ip().to_string()).unwrap_or_else(|| "desconocido".to_string()), e)),
+    };
+    let actor = match denied {
+        Ok(actor) => actor,
+        Err((actor, e)) => {
+            // Los intentos fallidos también quedan en la auditoría
+            let (name, params) = action.as_ref().map(|a| (a.name(), a.params())).unwrap_or(("?", String::new()));
+            let outcome = if matches!(e, AuthError::Forbidden(_)) { "sin permiso" } else { "no autorizado" };
+            tracing::warn!("⚠️ Control {}: {} por {}", outcome, name, actor);
+            state.journal.record_control(&actor, name, &params, outcome);
+            return auth_error(&e);
+        }
+    };
+    let action = match action {
+        Ok(action) => action,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+    };
+
+    match state.control.send(action, actor).await {
+        Ok(status) => warp::reply::json(&status).into_response(),
+        Err(ControlError::Rejected(reason)) => error(StatusCode::BAD_REQUEST, &reason),
+        Err(ControlError::Unavailable) => error(StatusCode::SERVICE_UNAVAILABLE, "el motor se está apagando"),
+    }
+}
+
+fn auth_error(e: &AuthError) -> warp::reply::Response {
+    match e {
+        AuthError::Missing | AuthError::Invalid => {
+            let message = if matches!(e, AuthError::Missing) { "faltan credenciales" } else { "credenciales inválidas" };
+            let response = error(StatusCode::UNAUTHORIZED, message);
+            warp::reply::with_header(response, "www-authenticate", "Basic realm=\"flash-arb\"").into_response()
+        }
+        AuthError::Forbidden(role) => error(StatusCode::FORBIDDEN, &format!("hace falta rol {:?}", role).to_lowercase()),
+    }
+}
+
+// Rechazos de autenticación (REST y /ws) a JSON; el resto sigue el camino normal de warp
+pub async fn recover(rejection: Rejection) -> Result<warp::reply::Response, Rejection> {
+    match rejection.find::<AuthError>() {
+        Some(e) => Ok(auth_error(e)),
+        None => Err(rejection),
+    }
+}
+
+// Cuerpo JSON opcional: vacío vale el default (pausa global, kill switch sin motivo).
+// Un POST sin cuerpo no trae content-length; con cuerpo se exige y se limita.
+fn optional_json<T: DeserializeOwned + Default + Send>() -> impl Filter<Extract = (Result<T, String>,), Error = Rejection> + Clone {
+    let with_body = warp::body::content_length_limit(16 * 1024).and(warp::body::bytes());
+    let without_body = warp::header::optional::<u64>("content-length")
+        .and(warp::header::optional::<String>("transfer-encoding"))
+        .and_then(|length: Option<u64>, encoding: Option<String>| async move {
+            match (length, encoding) {
+                (None, None) => Ok(Bytes::new()),
+                _ => Err(warp::reject()),
+            }
+        });
+    with_body.or(without_body).unify().map(|body: Bytes| {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            Ok(T::default())
+        } else {
+            serde_json::from_slice(&body).map_err(|e| format!("cuerpo inválido: {}", e))
+        }
+    })
+}
+
+fn with_state(state: ApiState) -> impl Filter<Extract = (ApiState,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+pub fn routes(state: ApiState) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    // Lectura: cualquier usuario (viewer u operator); el principal no se usa
+    let get = warp::get()
+        .and(auth::require(state.auth.clone(), Role::Viewer).map(|_: Principal| ()).untuple_one())
+        .and(with_state(state.clone()));
+
+    // El probe del balanceador no tiene credenciales
+    let probe_route = warp::path!("health").and(warp::get()).and(with_state(state.clone())).map(|state: ApiState| probe(&state));
+    let api = warp::path("api");
+    let health_route = api.and(warp::path!("health")).and(get.clone()).map(|state: ApiState| health(&state));
+    let config_route = api.and(warp::path!("config")).and(get.clone()).map(|state: ApiState| config(&state));
+    let opportunities_route = api
+        .and(warp::path!("opportunities"))
+        .and(get.clone())
+        .and(warp::query::<OpportunityQuery>())
+        .map(|state: ApiState, query| opportunities(&state, query));
+    let balances_route = api.and(warp::path!("balances")).and(get.clone()).map(|state: ApiState| balances(&state));
+    let positions_route = api.and(warp::path!("positions")).and(get.clone()).map(|state: ApiState| positions(&state));
+    let trades_route = api
+        .and(warp::path!("trades"))
+        .and(get.clone())
+        .and(warp::query::<TradeFilter>())
+        .map(|state: ApiState, filter| trades(&state, filter));
+    let feeds_route = api.and(warp::path!("feeds")).and(get.clone()).map(|state: ApiState| feeds(&state));
+    let metrics_route = warp::path!("metrics").and(get.clone()).map(|_: ApiState| {
+        let (content_type, body) = metrics::render();
+        warp::reply::with_header(body, "content-type", content_type).into_response()
+    });
+    let clients_route = api
+        .and(warp::path!("clients"))
+        .and(get.clone())
+        .map(|state: ApiState| warp::reply::json(&state.ws_clients.stats()).into_response());
+    let control_status_route = api.and(warp::path!("control")).and(get).map(|state: ApiState| control_status(&state));
+
+    // Todas las acciones comparten autenticación, auditoría y respuesta
+    let post = warp::post()
+        .and(auth::identify(state.auth.clone()))
+        .and(with_state(state))
+        .and(warp::addr::remote())
+        .map(|caller, state, addr| (state, caller, addr))
+        .untuple_one();
+    let pause_route = warp::path!("api" / "control" / "pause")
+        .and(post.clone())
+        .and(optional_json::<Scope>().map(|scope: Result<Scope, String>| scope.map(ControlAction::Pause)))
+        .then(control);
+    let resume_route = warp::path!("api" / "control" / "resume")
+        .and(post.clone())
+        .and(optional_json::<Scope>().map(|scope: Result<Scope, String>| scope.map(ControlAction::Resume)))
+        .then(control);
+    let limits_route = warp::path!("api" / "control" / "limits")
+        .and(post.clone())
+        .and(optional_json::<serde_json::Value>().map(|patch: Result<serde_json::Value, String>| patch.map(ControlAction::SetLimits)))
+        .then(control);
+    let refresh_route = warp::path!("api" / "control" / "instruments" / "refresh")
+        .and(post.clone())
+        .and(warp::any().map(|| Ok::<_, String>(ControlAction::RefreshInstruments)))
+        .then(control);
+    let kill_route = warp::path!("api" / "control" / "kill-switch")
+        .and(post.clone())
+        .and(optional_json::<KillSwitchRequest>().map(|body: Result<KillSwitchRequest, String>| {
+            body.map(|b| ControlAction::TripKillSwitch { reason: if b.reason.trim().is_empty() { "manual".to_string() } else { b.reason } })
+        }))
+        .then(control);
+    let reset_route = warp::path!("api" / "control" / "kill-switch" / "reset")
+        .and(post)
+        .and(warp::any().map(|| Ok::<_, String>(ControlAction::ResetKillSwitch)))
+        .then(control);
+
+    probe_route
+        .or(health_route)
+        .unify()
+        .or(config_route)
+        .unify()
+        .or(opportunities_route)
+        .unify()
+        .or(balances_route)
+        .unify()
+        .or(positions_route)
+        .unify()
+        .or(trades_route)
+        .unify()
+        .or(feeds_route)
+        .unify()
+        .or(clients_route)
+        .unify()
+        .or(metrics_route)
+        .unify()
+        .or(control_status_route)
+        .unify()
+        .or(pause_route)
+        .unify()
+        .or(resume_route)
+        .unify()
+        .or(limits_route)
+        .unify()
+        .or(refresh_route)
+        .unify()
+        .or(kill_route)
+        .unify()
+        .or(reset_route)
+        .unify()
+}
@@ -0,0 +1,112 @@
+// src/arbitrage/basis.rs
+
+use crate::fees::FeeConfig;
+use crate::aggregator::PriceAggregator;
+use crate::exchanges::{Exchange, MarketType};
+use serde::{Deserialize, Serialize};
+
+const MAX_BOOK_AGE_MS: u64 = 5000;
+const MIN_TRADEABLE_USD: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BasisDirection {
+    CashAndCarry, // Perp caro: compramos spot y shorteamos el perp
+    ReverseCarry, // Perp barato: vendemos spot (inventario/préstamo) y longeamos el perp
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BasisOpportunity {
+    pub symbol: String,
+    pub direction: BasisDirection,
+    pub spot_exchange: Exchange,
+    pub perp_exchange: Exchange,
+    pub same_venue: bool,
+    pub spot_price: f64,
+    pub perp_price: f64,
+
+    pub basis_pct: f64,          // (perp - spot) / spot, con el lado ejecutable de cada libro
+    pub total_fees_pct: f64,     // Entrada y salida en ambas patas
+    pub net_basis_pct: f64,
+    pub max_tradeable_usd: f64,
+
+    pub data_age_ms: u64,
+    pub timestamp: u64,
+}
+
+pub struct BasisDetector {
+    aggregator: PriceAggregator,
+    fee_config: FeeConfig,
+    min_net_basis_pct: f64,
+}
+
+impl BasisDetector {
+    pub fn new(aggregator: PriceAggregator, fee_config: FeeConfig, min_net_basis_pct: f64) -> Self {
+        Self {
+            aggregator,
+            fee_config,
+            min_net_basis_pct,
+        }
+    }
+
+    // Spot contra perp de cualquier venue, incluido el mismo
+    pub fn detect_opportunities(&self) -> Vec<BasisOpportunity> {
+        let mut opportunities = Vec::new();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        for symbol in self.aggregator.get_all_symbols(MarketType::Spot) {
+            let (Some(spot_books), Some(perp_books)) = (
+                self.aggregator.get_books(&symbol, MarketType::Spot),
+                self.aggregator.get_books(&symbol, MarketType::Perp),
+            ) else { continue };
+
+            for (spot_ex, spot) in &spot_books {
+                for (perp_ex, perp) in &perp_books {
+                    let data_age_ms = spot.age_ms().max(perp.age_ms());
+                    if data_age_ms > MAX_BOOK_AGE_MS { continue; }
+
+                    let total_fees_pct = 2.0
+                        * (self.fee_config.get_market_taker_fee(*spot_ex, MarketType::Spot)
+                            + self.fee_config.get_market_taker_fee(*perp_ex, MarketType::Perp));
+
+                    // (dirección, precio spot, precio perp, cantidad disponible)
+                    let legs = [
+                        (BasisDirection::CashAndCarry, spot.ask, perp.bid, f64::min(spot.ask_size, perp.bid_size)),
+                        (BasisDirection::ReverseCarry, spot.bid, perp.ask, f64::min(spot.bid_size, perp.ask_size)),
+                    ];
+
+                    for (direction, spot_price, perp_price, qty) in legs {
+                        if spot_price <= 0.0 { continue; }
+                        let basis_pct = (perp_price - spot_price) / spot_price * 100.0;
+                        let captured_pct = match direction {
+                            BasisDirection::CashAndCarry => basis_pct,
+                            BasisDirection::ReverseCarry => -basis_pct,
+                        };
+                        let net_basis_pct = captured_pct - total_fees_pct;
+                        let max_tradeable_usd = qty * spot_price;
+
+                        if net_basis_pct > self.min_net_basis_pct && max_tradeable_usd >= MIN_TRADEABLE_USD {
+                            opportunities.push(BasisOpportunity {
+                                symbol: symbol.clone(),
+                                direction,
+                                spot_exchange: *spot_ex,
+                                perp_exchange: *perp_ex,
+                                same_venue: spot_ex == perp_ex,
+                                spot_price,
+                                perp_price,
+                                basis_pct,
+                                total_fees_pct,
+                                net_basis_pct,
+                                max_tradeable_usd,
+                                data_age_ms,
+                                timestamp: now,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        opportunities.sort_by(|a, b| b.net_basis_pct.total_cmp(&a.net_basis_pct));
+        opportunities
+    }
+}
@@ -0,0 +1,355 @@
+// src/arbitrage/convergence.rs
+
+use crate::fees::FeeConfig;
+use crate::aggregator::{MarketBook, PriceAggregator};
+use crate::exchanges::{Exchange, MarketType};
+use crate::execution::Side;
+use crate::journal::{self, Journal, TradeLeg, TradeRecord};
+use crate::metrics::{self, RiskLimit};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+const MAX_BOOK_AGE_MS: u64 = 5000;
+const MAX_CLOSED_HISTORY: usize = 20;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConvergenceConfig {
+    pub window: usize,       // Muestras en la media móvil (una por barrido)
+    pub min_samples: usize,  // No operamos hasta tener una estadística decente
+    pub entry_z: f64,        // Abrimos cuando |z| supera esto
+    pub exit_z: f64,         // Cerramos cuando |z| vuelve por debajo
+    pub notional_usd: f64,   // Tamaño por pata
+    pub max_hold_ms: u64,    // Cierre forzado si no converge
+}
+
+impl Default for ConvergenceConfig {
+    fn default() -> Self {
+        Self {
+            window: 300,
+            min_samples: 60,
+            entry_z: 3.0,
+            exit_z: 0.5,
+            notional_usd: 1000.0,
+            max_hold_ms: 60 * 60 * 1000,
+        }
+    }
+}
+
+// Spread de mids entre dos venues (en bps) y su estadística móvil
+struct RollingSpread {
+    samples: VecDeque<f64>,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl RollingSpread {
+    fn new() -> Self {
+        Self { samples: VecDeque::new(), sum: 0.0, sum_sq: 0.0 }
+    }
+
+    fn push(&mut self, value: f64, window: usize) {
+        self.samples.push_back(value);
+        self.sum += value;
+        self.sum_sq += value * value;
+        while self.samples.len() > window {
+            if let Some(old) = self.samples.pop_front() {
+                self.sum -= old;
+                self.sum_sq -= old * old;
+            }
+        }
+    }
+
+    fn mean_std(&self) -> (f64, f64) {
+        let n = self.samples.len() as f64;
+        if n < 2.0 {
+            return (0.0, 0.0);
+        }
+        let mean = self.sum / n;
+        let var = (self.sum_sq / n - mean * mean).max(0.0);
+        (mean, var.sqrt())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConvergencePosition {
+    pub symbol: String,
+    pub long_exchange: Exchange,
+    pub short_exchange: Exchange,
+    pub qty: f64,
+    pub long_entry: f64,
+    pub short_entry: f64,
+    pub entry_spread_bps: f64,
+    pub entry_z: f64,
+    pub current_z: f64,
+    pub unrealized_pnl_usd: f64,
+    // Último precio de salida visto por pata (bid del long, ask del short)
+    #[serde(default)]
+    pub long_mark: f64,
+    #[serde(default)]
+    pub short_mark: f64,
+    pub opened_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClosedConvergence {
+    pub symbol: String,
+    pub long_exchange: Exchange,
+    pub short_exchange: Exchange,
+    pub realized_pnl_usd: f64,
+    pub held_ms: u64,
+    pub reason: String,
+    pub closed_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConvergenceSnapshot {
+    pub open_positions: Vec<ConvergencePosition>,
+    pub recent_closed: Vec<ClosedConvergence>,
+    pub realized_pnl_usd: f64,
+    pub unrealized_pnl_usd: f64,
+    pub closed_count: u32,
+}
+
+// Abre una posición cubierta cuando el spread entre dos venues se aleja k
+// desviaciones de su media y la cierra cuando vuelve.
+pub struct ConvergenceStrategy {
+    aggregator: PriceAggregator,
+    fee_config: FeeConfig,
+    config: ConvergenceConfig,
+    journal: Journal,
+    // (symbol, venue A, venue B) con A < B alfabéticamente: spread = A - B
+    spreads: HashMap<(String, Exchange, Exchange), RollingSpread>,
+    open: Vec<ConvergencePosition>,
+    closed: Vec<ClosedConvergence>,
+    realized_pnl_usd: f64,
+    closed_count: u32,
+}
+
+impl ConvergenceStrategy {
+    pub fn new(aggregator: PriceAggregator, fee_config: FeeConfig, config: ConvergenceConfig, journal: Journal) -> Self {
+        Self {
+            aggregator,
+            fee_config,
+            config,
+            journal,
+            spreads: HashMap::new(),
+            open: Vec::new(),
+            closed: Vec::new(),
+            realized_pnl_usd: 0.0,
+            closed_count: 0,
+        }
+    }
+
+    fn mid(book: &MarketBook) -> f64 {
+        (book.bid + book.ask) / 2.0
+    }
+
+    // Cambios en caliente desde la API de control. Ventana y mínimo de muestras
+    // aplican a las estadísticas que ya se vienen acumulando.
+    pub fn set_config(&mut self, config: ConvergenceConfig) {
+        self.config = config;
+    }
+
+    // Una muestra por símbolo y par de venues; abre o cierra según el z-score.
+    // `can_open` decide si se permiten entradas nuevas (pausas, kill switch); los cierres siguen.
+    pub fn step(&mut self, can_open: impl Fn(&str, &[Exchange]) -> bool) {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        for symbol in self.aggregator.get_all_symbols(MarketType::Perp) {
+            let Some(books) = self.aggregator.get_books(&symbol, MarketType::Perp) else { continue };
+            let fresh: Vec<(Exchange, MarketBook)> = books
+                .into_iter()
+                .filter(|(_, b)| b.age_ms() <= MAX_BOOK_AGE_MS && b.bid > 0.0 && b.ask > 0.0)
+                .collect();
+
+            for (i, (ex_i, book_i)) in fresh.iter().enumerate() {
+                for (ex_j, book_j) in fresh.iter().skip(i + 1) {
+                    let (ex_a, book_a, ex_b, book_b) = if ex_i.as_str() < ex_j.as_str() {
+                        (*ex_i, book_i, *ex_j, book_j)
+                    } else {
+                        (*ex_j, book_j, *ex_i, book_i)
+                    };
+                    let allowed = can_open(&symbol, &[ex_a, ex_b]);
+                    self.evaluate_pair(&symbol, ex_a, book_a, ex_b, book_b, allowed, now);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn evaluate_pair(&mut self, symbol: &str, ex_a: Exchange, book_a: &MarketBook, ex_b: Exchange, book_b: &MarketBook, can_open: bool, now: u64) {
+        let spread_bps = (Self::mid(book_a) - Self::mid(book_b)) / Self::mid(book_b) * 10000.0;
+        let stats = self.spreads
+            .entry((symbol.to_string(), ex_a, ex_b))
+            .or_insert_with(RollingSpread::new);
+
+        // El z se calcula contra la historia previa, antes de sumar la muestra actual
+        let (mean, std) = stats.mean_std();
+        let enough = stats.samples.len() >= self.config.min_samples && std > 0.0;
+        stats.push(spread_bps, self.config.window);
+        if !enough {
+            return;
+        }
+        let z = (spread_bps - mean) / std;
+
+        let open_idx = self.open.iter().position(|p| {
+            p.symbol == symbol
+                && ((p.long_exchange == ex_a && p.short_exchange == ex_b)
+                    || (p.long_exchange == ex_b && p.short_exchange == ex_a))
+        });
+
+        match open_idx {
+            Some(idx) => {
+                let (long_book, short_book) = if self.open[idx].long_exchange == ex_a { (book_a, book_b) } else { (book_b, book_a) };
+                // Cerramos vendiendo el long al bid y recomprando el short al ask
+                let pnl = self.close_pnl(&self.open[idx], long_book.bid, short_book.ask);
+                let pos = &mut self.open[idx];
+                // z visto desde la posición: positivo = spread sigue abierto a favor
+                pos.current_z = if pos.short_exchange == ex_a { z } else { -z };
+                pos.unrealized_pnl_usd = pnl;
+                pos.long_mark = long_book.bid;
+                pos.short_mark = short_book.ask;
+
+                let held_ms = now.saturating_sub(pos.opened_at);
+                let reason = if pos.current_z <= self.config.exit_z {
+                    Some("Convergencia")
+                } else if held_ms > self.config.max_hold_ms {
+                    metrics::risk_limit(RiskLimit::MaxHold);
+                    Some("Timeout")
+                } else {
+                    None
+                };
+
+                if let Some(reason) = reason {
+                    self.close(idx, long_book.bid, short_book.ask, reason, now);
+                }
+            }
+            None if can_open && z.abs() >= self.config.entry_z => {
+                // Spread alto: A está caro -> short A / long B (y al revés)
+                let (long_ex, long_book, short_ex, short_book) = if z > 0.0 {
+                    (ex_b, book_b, ex_a, book_a)
+                } else {
+                    (ex_a, book_a, ex_b, book_b)
+                };
+                let long_entry = long_book.ask;
+                let short_entry = short_book.bid;
+                let qty = self.config.notional_usd / long_entry;
+                if qty > f64::min(long_book.ask_size, short_book.bid_size) {
+                    return; // No hay profundidad visible para el tamaño
+                }
+
+                tracing::info!("🔀 CONV OPEN {} long {:?} / short {:?} (z={:.2}, spread={:.2}bps)",
+                    symbol, long_ex, short_ex, z, spread_bps);
+                self.open.push(ConvergencePosition {
+                    symbol: symbol.to_string(),
+                    long_exchange: long_ex,
+                    short_exchange: short_ex,
+                    qty,
+                    long_entry,
+                    short_entry,
+                    entry_spread_bps: spread_bps,
+                    entry_z: z.abs(),
+                    current_z: z.abs(),
+                    unrealized_pnl_usd: 0.0,
+                    long_mark: long_book.bid,
+                    short_mark: short_book.ask,
+                    opened_at: now,
+                });
+            }
+            None => {}
+        }
+    }
+
+    // Cierra al precio de salida dado y deja el round trip (4 patas) en el diario
+    fn close(&mut self, idx: usize, long_exit: f64, short_exit: f64, reason: &str, now: u64) {
+        let pnl = self.close_pnl(&self.open[idx], long_exit, short_exit);
+        let pos = self.open.remove(idx);
+        tracing::info!("🔁 CONV CLOSE {} {:?}/{:?}: {:+.4} USD ({})",
+            pos.symbol, pos.long_exchange, pos.short_exchange, pnl, reason);
+
+        let (fee_long, fee_short) = self.taker_fees(&pos);
+        let leg = |exchange, side, price: f64, fee: f64| TradeLeg { exchange, side, price, qty: pos.qty, fee_usd: pos.qty * price * fee };
+        self.journal.record_trade(&TradeRecord {
+            strategy: journal::STRATEGY_CONVERGENCE,
+            executed_at: now,
+            symbol: &pos.symbol,
+            buy_exchange: pos.long_exchange,
+            sell_exchange: pos.short_exchange,
+            buy_price: pos.long_entry,
+            sell_price: pos.short_entry,
+            profit_usd: pnl,
+            notional_usd: pos.qty * pos.long_entry,
+            detected_edge_pct: None,
+            balance_after: None,
+            note: &format!("{} (z entrada {:.2}, spread {:.2}bps)", reason, pos.entry_z, pos.entry_spread_bps),
+            legs: vec![
+                leg(pos.long_exchange, Side::Buy, pos.long_entry, fee_long),
+                leg(pos.short_exchange, Side::Sell, pos.short_entry, fee_short),
+                leg(pos.long_exchange, Side::Sell, long_exit, fee_long),
+                leg(pos.short_exchange, Side::Buy, short_exit, fee_short),
+            ],
+        });
+
+        self.realized_pnl_usd += pnl;
+        self.closed_count += 1;
+        self.closed.insert(0, ClosedConvergence {
+            symbol: pos.symbol,
+            long_exchange: pos.long_exchange,
+            short_exchange: pos.short_exchange,
+            realized_pnl_usd: pnl,
+            held_ms: now.saturating_sub(pos.opened_at),
+            reason: reason.to_string(),
+            closed_at: now,
+        });
+        self.closed.truncate(MAX_CLOSED_HISTORY);
+    }
+
+    // Kill switch: cierra todo a mercado. Sin libro de alguna pata se toma el último precio visto.
+    pub fn close_all(&mut self, reason: &str) {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        while let Some(pos) = self.open.last() {
+            let books: HashMap<Exchange, MarketBook> =
+                self.aggregator.get_books(&pos.symbol, MarketType::Perp).unwrap_or_default().into_iter().collect();
+            let (long_exit, short_exit) = match (books.get(&pos.long_exchange), books.get(&pos.short_exchange)) {
+                (Some(long_book), Some(short_book)) => (long_book.bid, short_book.ask),
+                // Snapshots viejos no traen marcas: sin precio visto salimos a la entrada
+                _ if pos.long_mark > 0.0 && pos.short_mark > 0.0 => (pos.long_mark, pos.short_mark),
+                _ => (pos.long_entry, pos.short_entry),
+            };
+            self.close(self.open.len() - 1, long_exit, short_exit, reason, now);
+        }
+    }
+
+    fn taker_fees(&self, pos: &ConvergencePosition) -> (f64, f64) {
+        let fee = |ex| self.fee_config.get_fees(ex, MarketType::Perp, Some(&pos.symbol)).taker / 100.0;
+        (fee(pos.long_exchange), fee(pos.short_exchange))
+    }
+
+    // PnL neto si cerramos ahora: dos patas de entrada + dos de salida, todas taker
+    fn close_pnl(&self, pos: &ConvergencePosition, long_exit: f64, short_exit: f64) -> f64 {
+        let (fee_long, fee_short) = self.taker_fees(pos);
+
+        let gross = pos.qty * (long_exit - pos.long_entry) + pos.qty * (pos.short_entry - short_exit);
+        let fees = pos.qty * ((pos.long_entry + long_exit) * fee_long + (pos.short_entry + short_exit) * fee_short);
+        gross - fees
+    }
+
+    // Las medias móviles del spread no se guardan: se rearman con las muestras nuevas
+    pub fn restore(&mut self, snapshot: ConvergenceSnapshot) {
+        self.open = snapshot.open_positions;
+        self.closed = snapshot.recent_closed;
+        self.realized_pnl_usd = snapshot.realized_pnl_usd;
+        self.closed_count = snapshot.closed_count;
+    }
+
+    pub fn snapshot(&self) -> ConvergenceSnapshot {
+        ConvergenceSnapshot {
+            open_positions: self.open.clone(),
+            recent_closed: self.closed.clone(),
+            realized_pnl_usd: self.realized_pnl_usd,
+            unrealized_pnl_usd: self.open.iter().map(|p| p.unrealized_pnl_usd).sum(),
+            closed_count: self.closed_count,
+        }
+    }
+}
@@ -1,10 +1,30 @@
-use crate::aggregator::PriceAggregator;
-use crate::exchanges::Exchange;
+use crate::aggregator::{FxAssumption, PriceAggregator};
+use crate::exchanges::{Exchange, MarketType};
+use crate::execution::Side;
+use crate::fees::FeeConfig;
+use crate::instruments::InstrumentRegistry;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OpportunityKind {
+    #[default]
+    CrossVenue, // Compra en un exchange, venta en otro
+    Triangular, // Tres patas dentro del mismo exchange
+}
+
+// Una pata de una oportunidad multi-pata (triangular)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpportunityLeg {
+    pub symbol: String,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArbitrageOpportunity {
+    #[serde(default)]
+    pub kind: OpportunityKind,
     pub symbol: String,
     pub buy_exchange: Exchange,
     pub buy_price: f64,
@@ -26,120 +46,133 @@ pub struct ArbitrageOpportunity {
     pub timestamp: u64,
 
     pub created_at: u64, // Timestamp en milisegundos
-}
 
-#[derive(Clone, Copy)]
-pub struct ExchangeFees {
-    pub maker: f64,
-    pub taker: f64,
-}
+    #[serde(default)]
+    pub legs: Vec<OpportunityLeg>, // Solo triangulares
 
-pub struct FeeConfig {
-    fees: HashMap<Exchange, ExchangeFees>,
-}
-
-impl FeeConfig {
-    pub fn default() -> Self {
-        let mut fees = HashMap::new();
-        // Fees conservadores (Taker)
-        fees.insert(Exchange::Binance, ExchangeFees { maker: 0.02, taker: 0.05 }); 
-        fees.insert(Exchange::Hyperliquid, ExchangeFees { maker: 0.00, taker: 0.025 });
-        fees.insert(Exchange::Bybit, ExchangeFees { maker: 0.02, taker: 0.06 });
-        fees.insert(Exchange::Extended, ExchangeFees { maker: 0.05, taker: 0.05 }); 
-        Self { fees }
-    }
-    
-    pub fn get_taker_fee(&self, exchange: Exchange) -> f64 {
-        self.fees.get(&exchange).map(|f| f.taker).unwrap_or(0.06)
-    }
+    // Conversiones a USDT aplicadas a los precios (vacío si todo cotiza en USDT)
+    #[serde(default)]
+    pub fx: Vec<FxAssumption>,
 }
 
 pub struct ArbitrageDetector {
     aggregator: PriceAggregator,
     // Eliminamos el campo min_net_profit_bps de la struct ya que usamos logica interna
     fee_config: FeeConfig,
+    instruments: InstrumentRegistry,
 }
 
 impl ArbitrageDetector {
-    pub fn new(aggregator: PriceAggregator, _unused_threshold: f64) -> Self {
+    pub fn new(aggregator: PriceAggregator, fee_config: FeeConfig, instruments: InstrumentRegistry, _unused_threshold: f64) -> Self {
         Self {
             aggregator,
-            fee_config: FeeConfig::default(),
+            fee_config,
+            instruments,
         }
     }
 
+    // Barrido completo: todos los símbolos. Se usa periódicamente para que
+    // las oportunidades con datos viejos desaparezcan aunque no llegue nada nuevo.
     pub fn detect_opportunities(&self) -> Vec<ArbitrageOpportunity> {
+        let mut opportunities: Vec<ArbitrageOpportunity> = self
+            .aggregator
+            .get_all_symbols(MarketType::Perp)
+            .iter()
+            .flat_map(|symbol| self.detect_for_symbol(symbol))
+            .collect();
+        sort_by_profit(&mut opportunities);
+        opportunities
+    }
+
+    // Evalúa solo los pares de exchanges de un símbolo (disparado por un update)
+    pub fn detect_for_symbol(&self, symbol: &str) -> Vec<ArbitrageOpportunity> {
         let mut opportunities = Vec::new();
-        let symbols = self.aggregator.get_all_symbols();
+        if self.aggregator.get_exchange_count(symbol, MarketType::Perp) < 2 {
+            return opportunities;
+        }
         let now = chrono::Utc::now().timestamp_millis() as u64;
         
         let max_age_ms = 5000; // Permitimos hasta 2s de latencia
         let min_usd_profit = 0.001; // Bajamos un poco la vara para ver si funciona todo bien ($0.02)
 
-        for symbol in symbols {
-            if let Some(books) = self.aggregator.get_books(&symbol) {
-                for (exchange_buy, book_buy) in &books {
-                    for (exchange_sell, book_sell) in &books {
-                        if exchange_buy == exchange_sell { continue; }
-
-                        // 1. Latencia
-                        let age_buy = now.saturating_sub(book_buy.timestamp);
-                        let age_sell = now.saturating_sub(book_sell.timestamp);
-                        let max_age = std::cmp::max(age_buy, age_sell);
-                        if max_age > max_age_ms { continue; }
-
-                        // 2. Precios
-                        let buy_price = book_buy.ask;
-                        let sell_price = book_sell.bid;
-
-                        if sell_price > buy_price {
-                            // 3. Liquidez Real (Bottleneck)
-                            let max_qty_buy = book_buy.ask_size;
-                            let max_qty_sell = book_sell.bid_size;
-                            let tradeable_qty = f64::min(max_qty_buy, max_qty_sell);
-                            let tradeable_usd = tradeable_qty * buy_price;
-
-                            if tradeable_usd < 10.0 { continue; } 
-
-                            // 4. Fees y Ganancia USD
-                            let fee_buy_pct = self.fee_config.get_taker_fee(*exchange_buy);
-                            let fee_sell_pct = self.fee_config.get_taker_fee(*exchange_sell);
-                            
-                            let fee_buy = fee_buy_pct / 100.0;
-                            let fee_sell = fee_sell_pct / 100.0;
-                            
-                            let cost = tradeable_usd * (1.0 + fee_buy);
-                            let revenue = (tradeable_qty * sell_price) * (1.0 - fee_sell);
-                            
-                            let net_profit_usd = revenue - cost;
-                            let net_profit_pct = ((revenue - cost) / cost) * 100.0;
-                            let total_fees_pct = fee_buy_pct + fee_sell_pct;
-
-                            if net_profit_usd > min_usd_profit {
-                                opportunities.push(ArbitrageOpportunity {
-                                    symbol: symbol.clone(),
-                                    buy_exchange: *exchange_buy,
-                                    buy_price,
-                                    sell_exchange: *exchange_sell,
-                                    sell_price,
-                                    spread_pct: ((sell_price - buy_price)/buy_price)*100.0,
-                                    total_fees_pct,
-                                    net_profit_pct,
-                                    net_profit_usd,
-                                    max_tradeable_qty: tradeable_qty, // RELLENAMOS EL CAMPO
-                                    max_tradeable_usd: tradeable_usd,
-                                    liquidity_bottleneck: if max_qty_buy < max_qty_sell { *exchange_buy } else { *exchange_sell },
-                                    data_age_ms: max_age,
-                                    timestamp: now,
-                                    created_at: now,
-                                });
-                            }
+        if let Some(books) = self.aggregator.get_books(symbol, MarketType::Perp) {
+            for (exchange_buy, book_buy) in &books {
+                for (exchange_sell, book_sell) in &books {
+                    if exchange_buy == exchange_sell { continue; }
+
+                    // 1. Latencia
+                    let max_age = std::cmp::max(book_buy.age_ms(), book_sell.age_ms());
+                    if max_age > max_age_ms { continue; }
+
+                    // 2. Precios
+                    let buy_price = book_buy.ask;
+                    let sell_price = book_sell.bid;
+
+                    if sell_price > buy_price {
+                        // 3. Liquidez Real (Bottleneck)
+                        let max_qty_buy = book_buy.ask_size;
+                        let max_qty_sell = book_sell.bid_size;
+                        let tradeable_qty = f64::min(max_qty_buy, max_qty_sell);
+                        // Lote de ambos venues y mínimos de orden de cada uno
+                        let tradeable_qty = self.instruments.round_qty(*exchange_buy, MarketType::Perp, symbol, tradeable_qty);
+                        let tradeable_qty = self.instruments.round_qty(*exchange_sell, MarketType::Perp, symbol, tradeable_qty);
+                        if !self.instruments.meets_minimums(*exchange_buy, MarketType::Perp, symbol, tradeable_qty, buy_price)
+                            || !self.instruments.meets_minimums(*exchange_sell, MarketType::Perp, symbol, tradeable_qty, sell_price)
+                        {
+                            continue;
+                        }
+                        let tradeable_usd = tradeable_qty * buy_price;
+
+                        if tradeable_usd < 10.0 { continue; } 
+
+                        // 4. Fees y Ganancia USD
+                        let fee_buy_pct = self.fee_config.get_fees(*exchange_buy, MarketType::Perp, Some(symbol)).taker;
+                        let fee_sell_pct = self.fee_config.get_fees(*exchange_sell, MarketType::Perp, Some(symbol)).taker;
+                        
+                        let fee_buy = fee_buy_pct / 100.0;
+                        let fee_sell = fee_sell_pct / 100.0;
+                        
+                        let cost = tradeable_usd * (1.0 + fee_buy);
+                        let revenue = (tradeable_qty * sell_price) * (1.0 - fee_sell);
+                        
+                        let net_profit_usd = revenue - cost;
+                        let net_profit_pct = ((revenue - cost) / cost) * 100.0;
+                        let total_fees_pct = fee_buy_pct + fee_sell_pct;
+
+                        if net_profit_usd > min_usd_profit {
+                            opportunities.push(ArbitrageOpportunity {
+                                kind: OpportunityKind::CrossVenue,
+                                symbol: symbol.to_string(),
+                                buy_exchange: *exchange_buy,
+                                buy_price,
+                                sell_exchange: *exchange_sell,
+                                sell_price,
+                                spread_pct: ((sell_price - buy_price)/buy_price)*100.0,
+                                total_fees_pct,
+                                net_profit_pct,
+                                net_profit_usd,
+                                max_tradeable_qty: tradeable_qty, // RELLENAMOS EL CAMPO
+                                max_tradeable_usd: tradeable_usd,
+                                liquidity_bottleneck: if max_qty_buy < max_qty_sell { *exchange_buy } else { *exchange_sell },
+                                data_age_ms: max_age,
+                                timestamp: now,
+                                created_at: now,
+                                legs: Vec::new(),
+                                fx: [FxAssumption::from_book(*exchange_buy, book_buy), FxAssumption::from_book(*exchange_sell, book_sell)]
+                                    .into_iter()
+                                    .flatten()
+                                    .collect(),
+                            });
                         }
                     }
                 }
             }
         }
-        opportunities.sort_by(|a, b| b.net_profit_usd.partial_cmp(&a.net_profit_usd).unwrap());
+        sort_by_profit(&mut opportunities);
         opportunities
     }
-}
\ No newline at end of file
+}
+
+pub fn sort_by_profit(opportunities: &mut [ArbitrageOpportunity]) {
+    opportunities.sort_by(|a, b| b.net_profit_usd.total_cmp(&a.net_profit_usd));
+}
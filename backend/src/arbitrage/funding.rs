@@ -0,0 +1,98 @@
+// src/arbitrage/funding.rs
+
+use crate::fees::FeeConfig;
+use crate::aggregator::PriceAggregator;
+use crate::exchanges::Exchange;
+use serde::{Deserialize, Serialize};
+
+// Funding más viejo que esto no se usa (Extended se consulta cada 30s)
+const MAX_FUNDING_AGE_MS: u64 = 120_000;
+
+// Long en un venue, short en otro: cobramos la diferencia de funding mientras
+// la posición está abierta y el precio queda cubierto.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FundingOpportunity {
+    pub symbol: String,
+    pub long_exchange: Exchange,
+    pub short_exchange: Exchange,
+    pub long_rate_hourly_pct: f64,
+    pub short_rate_hourly_pct: f64,
+
+    pub holding_hours: f64,
+    pub funding_diff_pct: f64,    // Funding neto cobrado en el horizonte
+    pub entry_exit_cost_pct: f64, // Taker de entrada y salida en ambas patas
+    pub net_edge_pct: f64,
+    pub apr_pct: f64,             // Diferencial anualizado (sin costos)
+
+    pub next_funding_long: u64,
+    pub next_funding_short: u64,
+    pub timestamp: u64,
+}
+
+pub struct FundingDetector {
+    aggregator: PriceAggregator,
+    fee_config: FeeConfig,
+    holding_hours: f64,
+    min_net_edge_pct: f64,
+}
+
+impl FundingDetector {
+    pub fn new(aggregator: PriceAggregator, fee_config: FeeConfig, holding_hours: f64, min_net_edge_pct: f64) -> Self {
+        Self {
+            aggregator,
+            fee_config,
+            holding_hours,
+            min_net_edge_pct,
+        }
+    }
+
+    pub fn detect_opportunities(&self) -> Vec<FundingOpportunity> {
+        let mut opportunities = Vec::new();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        for symbol in self.aggregator.get_funding_symbols() {
+            let Some(rates) = self.aggregator.get_funding(&symbol) else { continue };
+            let rates: Vec<_> = rates
+                .into_iter()
+                .filter(|(_, info)| now.saturating_sub(info.timestamp) <= MAX_FUNDING_AGE_MS)
+                .collect();
+
+            for (long_ex, long_info) in &rates {
+                for (short_ex, short_info) in &rates {
+                    if long_ex == short_ex { continue; }
+
+                    // Funding positivo: el long paga al short
+                    let long_hourly = long_info.hourly_rate();
+                    let short_hourly = short_info.hourly_rate();
+                    let funding_diff_pct = (short_hourly - long_hourly) * self.holding_hours * 100.0;
+
+                    // Abrir y cerrar: dos takers por pata
+                    let entry_exit_cost_pct = 2.0
+                        * (self.fee_config.get_taker_fee(*long_ex) + self.fee_config.get_taker_fee(*short_ex));
+                    let net_edge_pct = funding_diff_pct - entry_exit_cost_pct;
+
+                    if net_edge_pct > self.min_net_edge_pct {
+                        opportunities.push(FundingOpportunity {
+                            symbol: symbol.clone(),
+                            long_exchange: *long_ex,
+                            short_exchange: *short_ex,
+                            long_rate_hourly_pct: long_hourly * 100.0,
+                            short_rate_hourly_pct: short_hourly * 100.0,
+                            holding_hours: self.holding_hours,
+                            funding_diff_pct,
+                            entry_exit_cost_pct,
+                            net_edge_pct,
+                            apr_pct: (short_hourly - long_hourly) * 24.0 * 365.0 * 100.0,
+                            next_funding_long: long_info.next_funding_time,
+                            next_funding_short: short_info.next_funding_time,
+                            timestamp: now,
+                        });
+                    }
+                }
+            }
+        }
+
+        opportunities.sort_by(|a, b| b.net_edge_pct.total_cmp(&a.net_edge_pct));
+        opportunities
+    }
+}
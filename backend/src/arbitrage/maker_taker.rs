@@ -0,0 +1,461 @@
+// src/arbitrage/maker_taker.rs
+
+use crate::fees::FeeConfig;
+use crate::aggregator::{MarketBook, PriceAggregator};
+use crate::exchanges::{Exchange, MarketType};
+use crate::execution::{Executor, Side};
+use crate::journal::{self, Journal, OrderRecord, OrderStatus, TradeLeg, TradeRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const MAX_BOOK_AGE_MS: u64 = 2000;
+const MAX_RECENT_FILLS: usize = 20;
+// Espera entre reintentos de una cobertura fallida (el libro se actualiza cada pocos ms)
+const HEDGE_RETRY_MS: u64 = 1000;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MakerTakerConfig {
+    pub notional_usd: f64,
+    pub min_edge_pct: f64,           // Edge neto (maker + taker) exigido al cotizar
+    pub reprice_tolerance_pct: f64,  // Movimiento mínimo del precio objetivo para re-cotizar
+}
+
+impl Default for MakerTakerConfig {
+    fn default() -> Self {
+        Self {
+            notional_usd: 1000.0,
+            min_edge_pct: 0.02,
+            reprice_tolerance_pct: 0.005,
+        }
+    }
+}
+
+// Orden pasiva en el venue maker, cubierta con un taker en el otro venue al llenarse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestingQuote {
+    pub symbol: String,
+    pub side: Side,
+    pub maker_exchange: Exchange,
+    pub hedge_exchange: Exchange,
+    pub price: f64,
+    pub qty: f64,
+    pub hedge_reference: f64,     // Precio del otro libro usado para cotizar
+    pub expected_edge_pct: f64,   // Con fee maker en la pata pasiva y taker en la cobertura
+    pub order_id: String,
+    pub reprices: u32,
+    pub placed_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MakerTakerFill {
+    pub symbol: String,
+    pub side: Side,
+    pub maker_exchange: Exchange,
+    pub hedge_exchange: Exchange,
+    pub maker_price: f64,
+    pub hedge_price: f64,
+    pub qty: f64,
+    pub expected_edge_pct: f64,
+    pub realized_pnl_usd: f64,
+    pub timestamp: u64,
+}
+
+// Fill maker cuya cobertura taker falló: exposición abierta hasta que se cubra
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnhedgedFill {
+    pub quote: RestingQuote,
+    pub filled_at: u64,
+    pub attempts: u32,
+    pub last_attempt: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MakerTakerSnapshot {
+    pub resting: Vec<RestingQuote>,
+    #[serde(default)]
+    pub unhedged: Vec<UnhedgedFill>,
+    pub recent_fills: Vec<MakerTakerFill>,
+    pub realized_pnl_usd: f64,
+    pub fill_count: u32,
+    pub reprice_count: u32,
+}
+
+pub struct MakerTakerStrategy {
+    aggregator: PriceAggregator,
+    fee_config: FeeConfig,
+    config: MakerTakerConfig,
+    executors: HashMap<Exchange, Arc<dyn Executor + Send + Sync>>,
+    journal: Journal,
+    // Una orden por (símbolo, lado)
+    quotes: HashMap<(String, Side), RestingQuote>,
+    unhedged: Vec<UnhedgedFill>,
+    fills: Vec<MakerTakerFill>,
+    realized_pnl_usd: f64,
+    fill_count: u32,
+    reprice_count: u32,
+}
+
+impl MakerTakerStrategy {
+    pub fn new(
+        aggregator: PriceAggregator,
+        fee_config: FeeConfig,
+        config: MakerTakerConfig,
+        executors: HashMap<Exchange, Arc<dyn Executor + Send + Sync>>,
+        journal: Journal,
+    ) -> Self {
+        Self {
+            aggregator,
+            fee_config,
+            config,
+            executors,
+            journal,
+            quotes: HashMap::new(),
+            unhedged: Vec::new(),
+            fills: Vec::new(),
+            realized_pnl_usd: 0.0,
+            fill_count: 0,
+            reprice_count: 0,
+        }
+    }
+
+    // Cambios en caliente desde la API de control: rigen desde la próxima re-cotización
+    pub fn set_config(&mut self, config: MakerTakerConfig) {
+        self.config = config;
+    }
+
+    // Llamado por cada update del símbolo: primero fills, después re-cotización.
+    // Los venues en `paused` no reciben órdenes nuevas (las vivas se cancelan al re-cotizar),
+    // pero un fill ya ocurrido se cubre igual.
+    pub async fn on_symbol(&mut self, symbol: &str, paused: &HashSet<Exchange>) {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let books: HashMap<Exchange, MarketBook> = self
+            .aggregator
+            .get_books(symbol, MarketType::Perp)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, b)| b.age_ms() <= MAX_BOOK_AGE_MS)
+            .collect();
+
+        // Fills que quedaron sin cubrir en vueltas anteriores
+        let (retry, waiting): (Vec<UnhedgedFill>, Vec<UnhedgedFill>) = std::mem::take(&mut self.unhedged)
+            .into_iter()
+            .partition(|u| u.quote.symbol == symbol && now.saturating_sub(u.last_attempt) >= HEDGE_RETRY_MS);
+        self.unhedged = waiting;
+        for unhedged in retry {
+            self.hedge(unhedged, &books, now).await;
+        }
+
+        for side in [Side::Buy, Side::Sell] {
+            let key = (symbol.to_string(), side);
+            if let Some(quote) = self.quotes.get(&key).cloned() {
+                if Self::is_filled(&quote, &books) {
+                    self.quotes.remove(&key);
+                    self.record_order(&quote, OrderStatus::Filled);
+                    self.hedge(UnhedgedFill { quote, filled_at: now, attempts: 0, last_attempt: 0 }, &books, now).await;
+                }
+            }
+        }
+        let books: HashMap<Exchange, MarketBook> = books.into_iter().filter(|(ex, _)| !paused.contains(ex)).collect();
+        for side in [Side::Buy, Side::Sell] {
+            self.requote(symbol, side, &books, now).await;
+        }
+    }
+
+    // Simulación: la orden pasiva se llena cuando el libro del venue maker la atraviesa
+    fn is_filled(quote: &RestingQuote, books: &HashMap<Exchange, MarketBook>) -> bool {
+        let Some(book) = books.get(&quote.maker_exchange) else { return false };
+        match quote.side {
+            Side::Buy => book.ask <= quote.price,
+            Side::Sell => book.bid >= quote.price,
+        }
+    }
+
+    // Si la orden taker falla el fill queda en `unhedged` y se reintenta: sin cobertura
+    // no hay trade cerrado, así que no se registra PnL, trade ni volumen de la cobertura
+    async fn hedge(&mut self, mut unhedged: UnhedgedFill, books: &HashMap<Exchange, MarketBook>, now: u64) {
+        let quote = unhedged.quote.clone();
+        let hedge_side = match quote.side { Side::Buy => Side::Sell, Side::Sell => Side::Buy };
+        // Sin libro fresco cubrimos igual, al precio con el que cotizamos
+        let hedge_price = books
+            .get(&quote.hedge_exchange)
+            .map(|b| match hedge_side { Side::Sell => b.bid, Side::Buy => b.ask })
+            .unwrap_or(quote.hedge_reference);
+
+        if let Some(executor) = self.executors.get(&quote.hedge_exchange) {
+            let result = executor.place_order(&quote.symbol, hedge_side, quote.qty, None).await;
+            let (order_id, status) = match &result {
+                Ok(order_id) => (order_id.as_str(), OrderStatus::Filled),
+                Err(_) => ("", OrderStatus::Rejected),
+            };
+            self.journal.record_order(&OrderRecord {
+                strategy: journal::STRATEGY_MAKER_TAKER,
+                exchange: quote.hedge_exchange,
+                symbol: &quote.symbol,
+                side: hedge_side,
+                price: None,
+                qty: quote.qty,
+                order_id,
+                status,
+            });
+            if let Err(e) = result {
+                unhedged.attempts += 1;
+                unhedged.last_attempt = now;
+                tracing::error!("❌ Hedge taker falló en {:?} {} (intento {}): {:?}. Fill maker sin cubrir",
+                    quote.hedge_exchange, quote.symbol, unhedged.attempts, e);
+                self.unhedged.push(unhedged);
+                return;
+            }
+        }
+
+        let maker_fee = self.fee_config.get_fees(quote.maker_exchange, MarketType::Perp, Some(&quote.symbol)).maker / 100.0;
+        let taker_fee = self.fee_config.get_fees(quote.hedge_exchange, MarketType::Perp, Some(&quote.symbol)).taker / 100.0;
+        let (buy_cost, sell_revenue) = match quote.side {
+            Side::Buy => (quote.price * (1.0 + maker_fee), hedge_price * (1.0 - taker_fee)),
+            Side::Sell => (hedge_price * (1.0 + taker_fee), quote.price * (1.0 - maker_fee)),
+        };
+        let pnl = quote.qty * (sell_revenue - buy_cost);
+
+        tracing::info!("🎯 MAKER FILL {} {:?} @{} en {:?} -> hedge {:?} @{}: {:+.4} USD",
+            quote.symbol, quote.side, quote.price, quote.maker_exchange, quote.hedge_exchange, hedge_price, pnl);
+
+        // Ambas patas cuentan para el volumen de 30 días (tier de fees)
+        self.fee_config.record_volume(quote.maker_exchange, quote.qty * quote.price);
+        self.fee_config.record_volume(quote.hedge_exchange, quote.qty * hedge_price);
+
+        let (buy_exchange, buy_price, buy_fee, sell_exchange, sell_price, sell_fee) = match quote.side {
+            Side::Buy => (quote.maker_exchange, quote.price, maker_fee, quote.hedge_exchange, hedge_price, taker_fee),
+            Side::Sell => (quote.hedge_exchange, hedge_price, taker_fee, quote.maker_exchange, quote.price, maker_fee),
+        };
+        self.journal.record_trade(&TradeRecord {
+            strategy: journal::STRATEGY_MAKER_TAKER,
+            executed_at: now,
+            symbol: &quote.symbol,
+            buy_exchange,
+            sell_exchange,
+            buy_price,
+            sell_price,
+            profit_usd: pnl,
+            notional_usd: quote.qty * buy_price,
+            detected_edge_pct: Some(quote.expected_edge_pct),
+            balance_after: None,
+            note: &format!("Maker {:?} en {:?}", quote.side, quote.maker_exchange),
+            legs: vec![
+                TradeLeg { exchange: buy_exchange, side: Side::Buy, price: buy_price, qty: quote.qty, fee_usd: quote.qty * buy_price * buy_fee },
+                TradeLeg { exchange: sell_exchange, side: Side::Sell, price: sell_price, qty: quote.qty, fee_usd: quote.qty * sell_price * sell_fee },
+            ],
+        });
+
+        self.realized_pnl_usd += pnl;
+        self.fill_count += 1;
+        self.fills.insert(0, MakerTakerFill {
+            symbol: quote.symbol,
+            side: quote.side,
+            maker_exchange: quote.maker_exchange,
+            hedge_exchange: quote.hedge_exchange,
+            maker_price: quote.price,
+            hedge_price,
+            qty: quote.qty,
+            expected_edge_pct: quote.expected_edge_pct,
+            realized_pnl_usd: pnl,
+            timestamp: now,
+        });
+        self.fills.truncate(MAX_RECENT_FILLS);
+        if unhedged.attempts > 0 {
+            tracing::info!("✅ Fill maker de {} cubierto tras {} intentos fallidos", unhedged.quote.symbol, unhedged.attempts);
+        }
+    }
+
+    // Precio pasivo que deja `min_edge_pct` neto contra el mejor libro de cobertura
+    fn target(&self, symbol: &str, side: Side, books: &HashMap<Exchange, MarketBook>) -> Option<RestingQuote> {
+        // Mismo esquema de fees (tier y override del símbolo) que al cubrir el fill
+        let fees = |exchange: Exchange| self.fee_config.get_fees(exchange, MarketType::Perp, Some(symbol));
+        // Venue maker: el de fee maker más barato entre los que tienen libro fresco. Ante
+        // un empate se queda el de la orden viva y si no manda el orden de `Exchange::ALL`:
+        // el orden del HashMap haría saltar la orden de venue en cada vuelta.
+        let current = self.quotes.get(&(symbol.to_string(), side)).map(|q| q.maker_exchange);
+        let rank = |exchange: Exchange| Exchange::ALL.iter().position(|e| *e == exchange);
+        let maker_exchange = *books.keys().min_by(|a, b| {
+            fees(**a)
+                .maker
+                .total_cmp(&fees(**b).maker)
+                .then_with(|| (current == Some(**b)).cmp(&(current == Some(**a))))
+                .then_with(|| rank(**a).cmp(&rank(**b)))
+        })?;
+        let maker_book = books.get(&maker_exchange)?;
+        let maker_fee = fees(maker_exchange).maker / 100.0;
+        let min_edge = self.config.min_edge_pct / 100.0;
+
+        // Mejor cobertura neta de taker entre los demás venues
+        let (hedge_exchange, hedge_reference, price) = books
+            .iter()
+            .filter(|(ex, _)| **ex != maker_exchange)
+            .map(|(ex, b)| {
+                let taker_fee = fees(*ex).taker / 100.0;
+                match side {
+                    // Compramos pasivo y vendemos taker al bid del otro
+                    Side::Buy => (*ex, b.bid, b.bid * (1.0 - taker_fee) / (1.0 + maker_fee + min_edge)),
+                    // Vendemos pasivo y recompramos taker al ask del otro
+                    Side::Sell => (*ex, b.ask, b.ask * (1.0 + taker_fee) / (1.0 - maker_fee - min_edge)),
+                }
+            })
+            // Un NaN de fee o precio ordenaría como el mejor: fuera antes de elegir
+            .filter(|(_, _, price)| price.is_finite())
+            .max_by(|a, b| match side {
+                Side::Buy => a.2.total_cmp(&b.2),
+                Side::Sell => b.2.total_cmp(&a.2),
+            })?;
+
+        // Post-only: si cruzaría el libro maker ya es un arbitraje taker, no lo cotizamos
+        let crosses = match side {
+            Side::Buy => price >= maker_book.ask,
+            Side::Sell => price <= maker_book.bid,
+        };
+        if crosses || price <= 0.0 {
+            return None;
+        }
+
+        let taker_fee = fees(hedge_exchange).taker / 100.0;
+        let expected_edge_pct = match side {
+            Side::Buy => (hedge_reference * (1.0 - taker_fee) - price * (1.0 + maker_fee)) / price * 100.0,
+            Side::Sell => (price * (1.0 - maker_fee) - hedge_reference * (1.0 + taker_fee)) / price * 100.0,
+        };
+
+        Some(RestingQuote {
+            symbol: String::new(),
+            side,
+            maker_exchange,
+            hedge_exchange,
+            price,
+            qty: self.config.notional_usd / price,
+            hedge_reference,
+            expected_edge_pct,
+            order_id: String::new(),
+            reprices: 0,
+            placed_at: 0,
+        })
+    }
+
+    async fn requote(&mut self, symbol: &str, side: Side, books: &HashMap<Exchange, MarketBook>, now: u64) {
+        let key = (symbol.to_string(), side);
+        let target = self.target(symbol, side, books);
+        let current = self.quotes.get(&key).cloned();
+
+        match (current, target) {
+            (Some(current), Some(target))
+                if current.maker_exchange == target.maker_exchange
+                    && ((target.price - current.price) / current.price).abs() * 100.0 < self.config.reprice_tolerance_pct =>
+            {
+                // Dentro de la tolerancia: solo refrescamos la referencia
+                if let Some(q) = self.quotes.get_mut(&key) {
+                    q.hedge_exchange = target.hedge_exchange;
+                    q.hedge_reference = target.hedge_reference;
+                }
+            }
+            (current, target) => {
+                let reprices = current.as_ref().map(|c| c.reprices + 1).unwrap_or(0);
+                if let Some(current) = current {
+                    self.cancel(&current).await;
+                    self.quotes.remove(&key);
+                }
+                if let Some(mut target) = target {
+                    let Some(executor) = self.executors.get(&target.maker_exchange) else { return };
+                    match executor.place_order(symbol, side, target.qty, Some(target.price)).await {
+                        Ok(order_id) => {
+                            if reprices > 0 {
+                                self.reprice_count += 1;
+                            }
+                            target.symbol = symbol.to_string();
+                            target.order_id = order_id;
+                            target.reprices = reprices;
+                            target.placed_at = now;
+                            self.record_order(&target, OrderStatus::Placed);
+                            self.quotes.insert(key, target);
+                        }
+                        Err(e) => {
+                            tracing::error!("❌ Orden maker rechazada en {:?} {}: {:?}", target.maker_exchange, symbol, e);
+                            target.symbol = symbol.to_string();
+                            self.record_order(&target, OrderStatus::Rejected);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn cancel(&self, quote: &RestingQuote) {
+        if let Some(executor) = self.executors.get(&quote.maker_exchange) {
+            if let Err(e) = executor.cancel_order(&quote.symbol, &quote.order_id).await {
+                tracing::warn!("⚠️ Cancel falló en {:?} {}: {:?}", quote.maker_exchange, quote.symbol, e);
+                return;
+            }
+            self.record_order(quote, OrderStatus::Canceled);
+        }
+    }
+
+    fn record_order(&self, quote: &RestingQuote, status: OrderStatus) {
+        self.journal.record_order(&OrderRecord {
+            strategy: journal::STRATEGY_MAKER_TAKER,
+            exchange: quote.maker_exchange,
+            symbol: &quote.symbol,
+            side: quote.side,
+            price: Some(quote.price),
+            qty: quote.qty,
+            order_id: &quote.order_id,
+            status,
+        });
+    }
+
+    // Apagado y kill switch: ninguna orden pasiva queda viva sin nadie que cubra el fill
+    pub async fn cancel_all(&mut self) {
+        self.cancel_where(|_| true).await;
+    }
+
+    // Pausas: baja en el momento lo que quedó en un símbolo o venue pausado,
+    // sin esperar al próximo update del libro
+    pub async fn cancel_where(&mut self, matches: impl Fn(&RestingQuote) -> bool) {
+        let keys: Vec<(String, Side)> = self.quotes.iter().filter(|(_, q)| matches(q)).map(|(k, _)| k.clone()).collect();
+        for key in &keys {
+            if let Some(quote) = self.quotes.remove(key) {
+                self.cancel(&quote).await;
+            }
+        }
+        if !keys.is_empty() {
+            tracing::info!("🧹 {} órdenes maker canceladas", keys.len());
+        }
+    }
+
+    // Las órdenes que quedaron vivas (si el proceso cayó sin cancelarlas) se siguen gestionando
+    pub fn restore(&mut self, snapshot: MakerTakerSnapshot) {
+        self.quotes = snapshot
+            .resting
+            .into_iter()
+            .map(|q| ((q.symbol.clone(), q.side), q))
+            .collect();
+        self.unhedged = snapshot.unhedged;
+        self.fills = snapshot.recent_fills;
+        self.realized_pnl_usd = snapshot.realized_pnl_usd;
+        self.fill_count = snapshot.fill_count;
+        self.reprice_count = snapshot.reprice_count;
+    }
+
+    pub fn fill_count(&self) -> u32 {
+        self.fill_count
+    }
+
+    pub fn unhedged_count(&self) -> usize {
+        self.unhedged.len()
+    }
+
+    pub fn snapshot(&self) -> MakerTakerSnapshot {
+        MakerTakerSnapshot {
+            resting: self.quotes.values().cloned().collect(),
+            unhedged: self.unhedged.clone(),
+            recent_fills: self.fills.clone(),
+            realized_pnl_usd: self.realized_pnl_usd,
+            fill_count: self.fill_count,
+            reprice_count: self.reprice_count,
+        }
+    }
+}
@@ -1,6 +1,16 @@
 // src/arbitrage/mod.rs
 
+pub mod basis;
+pub mod convergence;
 pub mod detector;
+pub mod funding;
+pub mod maker_taker;
+pub mod triangular;
 
 // Re-exportamos para facilitar el uso en main.rs
-pub use detector::{ArbitrageDetector, ArbitrageOpportunity};
\ No newline at end of file
+pub use detector::{ArbitrageDetector, ArbitrageOpportunity};
+pub use basis::{BasisDetector, BasisOpportunity};
+pub use convergence::{ConvergenceConfig, ConvergenceSnapshot, ConvergenceStrategy};
+pub use funding::{FundingDetector, FundingOpportunity};
+pub use maker_taker::{MakerTakerConfig, MakerTakerSnapshot, MakerTakerStrategy};
+pub use triangular::TriangularDetector;
\ No newline at end of file
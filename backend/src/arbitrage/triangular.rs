@@ -0,0 +1,150 @@
+// src/arbitrage/triangular.rs
+
+use super::detector::{sort_by_profit, ArbitrageOpportunity, OpportunityKind, OpportunityLeg};
+use crate::fees::FeeConfig;
+use crate::aggregator::{FxAssumption, MarketBook, PriceAggregator};
+use crate::exchanges::{Exchange, MarketType, SymbolId};
+use crate::execution::Side;
+use std::collections::HashMap;
+
+const MAX_BOOK_AGE_MS: u64 = 5000;
+const MIN_TRADEABLE_USD: f64 = 10.0;
+// Todos los ciclos empiezan y terminan en esta moneda (el capital está en USDT)
+const ANCHOR: &str = "USDT";
+
+// Conversión de `from` a `to` en un par del libro
+struct Edge {
+    symbol: String,
+    side: Side,
+    price: f64,
+    rate: f64,     // Unidades de `to` por unidad de `from`
+    capacity: f64, // Máximo de `from` que absorbe el top of book
+    age_ms: u64,
+    fx: Option<FxAssumption>,
+}
+
+impl Edge {
+    // Par BASE-QUOTE: vender BASE al bid, o comprar BASE con QUOTE al ask
+    fn from_book(symbol: &str, exchange: Exchange, book: &MarketBook) -> Option<[(String, String, Edge); 2]> {
+        let SymbolId { base, quote } = SymbolId::parse(symbol)?;
+        if book.bid <= 0.0 || book.ask <= 0.0 {
+            return None;
+        }
+        Some([
+            (base.clone(), quote.clone(), Edge {
+                symbol: symbol.to_string(), side: Side::Sell, price: book.bid,
+                rate: book.bid, capacity: book.bid_size, age_ms: book.age_ms(),
+                fx: FxAssumption::from_book(exchange, book),
+            }),
+            (quote, base, Edge {
+                symbol: symbol.to_string(), side: Side::Buy, price: book.ask,
+                rate: 1.0 / book.ask, capacity: book.ask_size * book.ask, age_ms: book.age_ms(),
+                fx: FxAssumption::from_book(exchange, book),
+            }),
+        ])
+    }
+}
+
+// Ciclos de 3 patas dentro de un mismo exchange, p. ej. USDT -> ETH -> BTC -> USDT
+pub struct TriangularDetector {
+    aggregator: PriceAggregator,
+    fee_config: FeeConfig,
+}
+
+impl TriangularDetector {
+    pub fn new(aggregator: PriceAggregator, fee_config: FeeConfig) -> Self {
+        Self {
+            aggregator,
+            fee_config,
+        }
+    }
+
+    pub fn detect_opportunities(&self) -> Vec<ArbitrageOpportunity> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        // Grafo por exchange: from -> (to -> edge)
+        let mut graphs: HashMap<Exchange, HashMap<String, HashMap<String, Edge>>> = HashMap::new();
+        for symbol in self.aggregator.get_all_symbols(MarketType::Spot) {
+            let Some(books) = self.aggregator.get_books(&symbol, MarketType::Spot) else { continue };
+            for (exchange, book) in books {
+                if book.age_ms() > MAX_BOOK_AGE_MS { continue; }
+                let Some(edges) = Edge::from_book(&symbol, exchange, &book) else { continue };
+                let graph = graphs.entry(exchange).or_default();
+                for (from, to, edge) in edges {
+                    graph.entry(from).or_default().insert(to, edge);
+                }
+            }
+        }
+
+        let mut opportunities = Vec::new();
+        for (exchange, graph) in &graphs {
+            let fee = self.fee_config.get_market_taker_fee(*exchange, MarketType::Spot) / 100.0;
+            let Some(first_hops) = graph.get(ANCHOR) else { continue };
+
+            for (b, leg1) in first_hops {
+                let Some(second_hops) = graph.get(b) else { continue };
+                for (c, leg2) in second_hops {
+                    if c == ANCHOR { continue; }
+                    let Some(leg3) = graph.get(c).and_then(|m| m.get(ANCHOR)) else { continue };
+                    if let Some(op) = Self::evaluate_cycle(*exchange, [b, c], [leg1, leg2, leg3], fee, now) {
+                        opportunities.push(op);
+                    }
+                }
+            }
+        }
+
+        sort_by_profit(&mut opportunities);
+        opportunities
+    }
+
+    fn evaluate_cycle(exchange: Exchange, path: [&String; 2], legs: [&Edge; 3], fee: f64, now: u64) -> Option<ArbitrageOpportunity> {
+        let gross = legs[0].rate * legs[1].rate * legs[2].rate;
+        let net = gross * (1.0 - fee).powi(3);
+        if net <= 1.0 {
+            return None;
+        }
+
+        // Profundidad: la pata más chica, expresada en USDT de entrada
+        let mut start_per_unit = 1.0; // USDT necesarios por unidad de la moneda de cada pata
+        let mut max_start = f64::MAX;
+        for leg in legs {
+            max_start = max_start.min(leg.capacity * start_per_unit);
+            start_per_unit /= leg.rate;
+        }
+        if max_start < MIN_TRADEABLE_USD {
+            return None;
+        }
+
+        // Cantidad que se mueve en cada pata (en moneda base del par)
+        let mut amount = max_start;
+        let mut op_legs = Vec::with_capacity(3);
+        for leg in legs {
+            let out = amount * leg.rate;
+            let size = match leg.side { Side::Sell => amount, Side::Buy => out };
+            op_legs.push(OpportunityLeg { symbol: leg.symbol.clone(), side: leg.side, price: leg.price, size });
+            amount = out * (1.0 - fee);
+        }
+
+        Some(ArbitrageOpportunity {
+            kind: OpportunityKind::Triangular,
+            symbol: format!("{}/{}/{}", path[0], path[1], ANCHOR),
+            buy_exchange: exchange,
+            buy_price: legs[0].price,
+            sell_exchange: exchange,
+            sell_price: legs[2].price,
+            spread_pct: (gross - 1.0) * 100.0,
+            total_fees_pct: fee * 3.0 * 100.0,
+            net_profit_pct: (net - 1.0) * 100.0,
+            net_profit_usd: max_start * (net - 1.0),
+            max_tradeable_qty: max_start,
+            max_tradeable_usd: max_start,
+            liquidity_bottleneck: exchange,
+            data_age_ms: legs.iter().map(|l| l.age_ms).max().unwrap_or(0),
+            timestamp: now,
+            created_at: now,
+            legs: op_legs,
+            // Todas las patas del mismo venue comparten la conversión
+            fx: legs.iter().find_map(|l| l.fx.clone()).into_iter().collect(),
+        })
+    }
+}
@@ -0,0 +1,172 @@
+// src/auth.rs
+//
+// Usuarios del servidor (dashboard, API REST y control) con su rol. Se definen
+// en `API_USERS` como `nombre:rol:secreto` separados por coma; el rol es
+// `viewer` (solo lectura) u `operator` (además puede usar /api/control).
+// El secreto viaja como `Authorization: Bearer <secreto>`, como Basic
+// (`nombre:secreto`) o, en el WebSocket del navegador que no puede mandar
+// headers, como `/ws?token=<secreto>`.
+//
+// Sin usuarios el servidor queda abierto en solo lectura, como antes: no hay
+// control y no se permite escuchar fuera de loopback.
+
+use anyhow::{bail, Result};
+use base64::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::Filter;
+
+const ANONYMOUS: &str = "anónimo";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator, // Incluye todo lo de Viewer
+}
+
+impl Role {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "viewer" => Some(Role::Viewer),
+            "operator" => Some(Role::Operator),
+            _ => None,
+        }
+    }
+}
+
+struct User {
+    name: String,
+    role: Role,
+    secret: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Missing,
+    Invalid,
+    Forbidden(Role), // Autenticado, pero el rol no alcanza
+}
+
+impl warp::reject::Reject for AuthError {}
+
+#[derive(Clone, Default)]
+pub struct Auth {
+    users: Arc<Vec<User>>,
+}
+
+impl Auth {
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("API_USERS") {
+            Ok(spec) if !spec.trim().is_empty() => Self::parse(&spec),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn parse(spec: &str) -> Result<Self> {
+        let mut users: Vec<User> = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            // El secreto puede tener ':'; nombre y rol no
+            let mut parts = entry.splitn(3, ':');
+            let (Some(name), Some(role), Some(secret)) = (parts.next(), parts.next(), parts.next()) else {
+                bail!("API_USERS: se esperaba nombre:rol:secreto en {:?}", entry.split(':').next().unwrap_or_default());
+            };
+            let Some(role) = Role::parse(role) else {
+                bail!("API_USERS: rol desconocido {:?} para {} (viewer u operator)", role, name);
+            };
+            if name.is_empty() || secret.is_empty() {
+                bail!("API_USERS: nombre y secreto no pueden estar vacíos");
+            }
+            // Con Bearer solo llega el secreto: tiene que identificar a un único usuario
+            if users.iter().any(|u| u.name == name || u.secret == secret) {
+                bail!("API_USERS: nombre o secreto repetido ({})", name);
+            }
+            users.push(User { name: name.to_string(), role, secret: secret.to_string() });
+        }
+        Ok(Self { users: Arc::new(users) })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_open() {
+            return "abierto, solo lectura".to_string();
+        }
+        let operators = self.users.iter().filter(|u| u.role == Role::Operator).count();
+        format!("{} usuarios ({} operadores)", self.users.len(), operators)
+    }
+
+    // `authorization` es el header tal cual; `token` el de la query del WebSocket
+    pub fn identify(&self, authorization: Option<&str>, token: Option<&str>) -> Result<Principal, AuthError> {
+        if self.is_open() {
+            return Ok(Principal { name: ANONYMOUS.to_string(), role: Role::Viewer });
+        }
+        let user = match (authorization, token) {
+            (Some(header), _) => self.user_for_header(header),
+            (None, Some(token)) => self.users.iter().find(|u| secret_matches(token, &u.secret)),
+            (None, None) => return Err(AuthError::Missing),
+        };
+        user.map(|u| Principal { name: u.name.clone(), role: u.role }).ok_or(AuthError::Invalid)
+    }
+
+    fn user_for_header(&self, header: &str) -> Option<&User> {
+        let (scheme, credentials) = header.trim().split_once(' ')?;
+        let credentials = credentials.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return self.users.iter().find(|u| secret_matches(credentials, &u.secret));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(credentials).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (name, secret) = decoded.split_once(':')?;
+            return self.users.iter().find(|u| u.name == name && secret_matches(secret, &u.secret));
+        }
+        None
+    }
+
+    pub fn authorize(&self, authorization: Option<&str>, token: Option<&str>, role: Role) -> Result<Principal, AuthError> {
+        let principal = self.identify(authorization, token)?;
+        if principal.role < role {
+            return Err(AuthError::Forbidden(role));
+        }
+        Ok(principal)
+    }
+}
+
+// Comparación sin cortocircuito: no filtra por tiempo cuántos bytes del secreto coinciden
+fn secret_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// Quién llama, sin rechazar: la decide cada ruta (el control audita los intentos fallidos)
+pub fn identify(auth: Auth) -> impl Filter<Extract = (Result<Principal, AuthError>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .map(move |header: Option<String>| auth.identify(header.as_deref(), None))
+}
+
+// Rechaza con `AuthError` si no hay credenciales válidas con al menos `role`
+pub fn require(auth: Auth, role: Role) -> impl Filter<Extract = (Principal,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization").and_then(move |header: Option<String>| {
+        let result = auth.authorize(header.as_deref(), None, role);
+        async move { result.map_err(warp::reject::custom) }
+    })
+}
+
+// WebSocket: además del header acepta `?token=` (el navegador no puede mandar headers)
+pub fn require_ws(auth: Auth) -> impl Filter<Extract = (Principal,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |header: Option<String>, query: HashMap<String, String>| {
+            let result = auth.authorize(header.as_deref(), query.get("token").map(String::as_str), Role::Viewer);
+            async move { result.map_err(warp::reject::custom) }
+        })
+}
@@ -0,0 +1,165 @@
+// src/clock.rs
+//
+// Desfase de nuestro reloj contra el de cada exchange. Sirve para leer bien
+// las horas de evento de los feeds y para firmar requests dentro del
+// recvWindow. Estimación tipo NTP: offset = hora del server - punto medio
+// local del request, quedándonos con la muestra de menor RTT.
+
+use crate::exchanges::Exchange;
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const BINANCE_TIME_URL: &str = "https://fapi.binance.com/fapi/v1/time";
+const BYBIT_TIME_URL: &str = "https://api.bybit.com/v5/market/time";
+const CLOCK_SYNC_INTERVAL_SECS: u64 = 60;
+// Requests por ronda: el de menor RTT es el de menor error
+const SAMPLES_PER_SYNC: usize = 5;
+// Por encima de esto los requests firmados empiezan a rozar el recvWindow
+const DEFAULT_SKEW_ALERT_MS: i64 = 1000;
+// Un request colgado frena la ronda entera (y la de los demás venues)
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Rondas seguidas sin medir a partir de las cuales el offset guardado ya no es confiable
+const STALE_AFTER_MISSED_ROUNDS: u32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClockOffset {
+    pub offset_ms: i64, // Hora del exchange - hora local
+    pub rtt_ms: u64,
+    pub measured_at: u64,
+    pub missed_rounds: u32, // Rondas fallidas desde la última medición
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockStatus {
+    pub exchange: Exchange,
+    pub offset_ms: i64,
+    pub rtt_ms: u64,
+    pub measured_at: u64,
+    pub alert: bool,
+    pub stale: bool, // Offset viejo: varias rondas seguidas sin poder medir
+}
+
+#[derive(Clone)]
+pub struct ClockSync {
+    offsets: Arc<DashMap<Exchange, ClockOffset>>,
+    alert_ms: i64,
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        let alert_ms = std::env::var("CLOCK_SKEW_ALERT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SKEW_ALERT_MS);
+        Self { offsets: Arc::new(DashMap::new()), alert_ms }
+    }
+
+    // 0 mientras no haya medición (o si el venue no publica su hora)
+    pub fn offset_ms(&self, exchange: Exchange) -> i64 {
+        self.offsets.get(&exchange).map(|o| o.offset_ms).unwrap_or(0)
+    }
+
+    // Hora actual según el exchange: la que hay que poner en los requests firmados
+    pub fn server_now(&self, exchange: Exchange) -> u64 {
+        (chrono::Utc::now().timestamp_millis() + self.offset_ms(exchange)) as u64
+    }
+
+    // Hora de un evento del exchange llevada a nuestro reloj
+    pub fn to_local(&self, exchange: Exchange, exchange_ts: u64) -> u64 {
+        (exchange_ts as i64 - self.offset_ms(exchange)) as u64
+    }
+
+    pub fn status(&self) -> Vec<ClockStatus> {
+        let mut status: Vec<ClockStatus> = self
+            .offsets
+            .iter()
+            .map(|entry| {
+                let o = entry.value();
+                ClockStatus {
+                    exchange: *entry.key(),
+                    offset_ms: o.offset_ms,
+                    rtt_ms: o.rtt_ms,
+                    measured_at: o.measured_at,
+                    alert: o.offset_ms.abs() > self.alert_ms,
+                    stale: o.missed_rounds >= STALE_AFTER_MISSED_ROUNDS,
+                }
+            })
+            .collect();
+        status.sort_by_key(|s| s.exchange.as_str());
+        status
+    }
+
+    fn record(&self, exchange: Exchange, offset: ClockOffset) {
+        if offset.offset_ms.abs() > self.alert_ms {
+            tracing::warn!("🕰️ Reloj desfasado {} ms contra {:?} (umbral {} ms, rtt {} ms)",
+                offset.offset_ms, exchange, self.alert_ms, offset.rtt_ms);
+        } else {
+            tracing::debug!("🕰️ Offset {:?}: {} ms (rtt {} ms)", exchange, offset.offset_ms, offset.rtt_ms);
+        }
+        self.offsets.insert(exchange, offset);
+    }
+
+    // Se sigue usando el último offset, pero el status lo marca como viejo
+    fn record_miss(&self, exchange: Exchange) {
+        let Some(mut offset) = self.offsets.get_mut(&exchange) else { return };
+        offset.missed_rounds += 1;
+        if offset.missed_rounds == STALE_AFTER_MISSED_ROUNDS {
+            tracing::warn!("🕰️ Offset de {:?} sin actualizar hace {} rondas: marcado como viejo", exchange, offset.missed_rounds);
+        }
+    }
+}
+
+// Binance: {"serverTime":1499827319559}
+// Bybit:   {"retCode":0,"result":{"timeSecond":"1688639403","timeNano":"1688639403423213947"},"time":1688639403423}
+// Hyperliquid y Extended no publican un endpoint de hora: quedan con offset 0.
+async fn fetch_server_time(client: &reqwest::Client, exchange: Exchange) -> Result<u64> {
+    let (url, field) = match exchange {
+        Exchange::Binance => (BINANCE_TIME_URL, "serverTime"),
+        Exchange::Bybit => (BYBIT_TIME_URL, "time"),
+        Exchange::Hyperliquid | Exchange::Extended => return Err(anyhow!("{:?} no publica su hora", exchange)),
+    };
+    let json: Value = client.get(url).send().await?.json().await?;
+    json[field].as_u64().ok_or_else(|| anyhow!("respuesta inesperada: {}", json))
+}
+
+async fn measure(client: &reqwest::Client, exchange: Exchange) -> Result<ClockOffset> {
+    let mut best: Option<ClockOffset> = None;
+    for _ in 0..SAMPLES_PER_SYNC {
+        let sent_wall = chrono::Utc::now().timestamp_millis();
+        let sent = Instant::now();
+        let server = fetch_server_time(client, exchange).await?;
+        let rtt_ms = sent.elapsed().as_millis() as u64;
+        let midpoint = sent_wall + (rtt_ms / 2) as i64;
+        let sample = ClockOffset {
+            offset_ms: server as i64 - midpoint,
+            rtt_ms,
+            measured_at: chrono::Utc::now().timestamp_millis() as u64,
+            missed_rounds: 0,
+        };
+        if best.is_none_or(|b| sample.rtt_ms < b.rtt_ms) {
+            best = Some(sample);
+        }
+    }
+    best.ok_or_else(|| anyhow!("sin muestras"))
+}
+
+pub async fn run(clock: ClockSync) {
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default();
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(CLOCK_SYNC_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        for exchange in [Exchange::Binance, Exchange::Bybit] {
+            match measure(&client, exchange).await {
+                Ok(offset) => clock.record(exchange, offset),
+                Err(e) => {
+                    tracing::warn!("⚠️ No se pudo medir el reloj de {:?}: {:?}", exchange, e);
+                    clock.record_miss(exchange);
+                }
+            }
+        }
+    }
+}
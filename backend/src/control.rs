@@ -0,0 +1,283 @@
+// src/control.rs
+//
+// Control del motor en caliente: pausas (global, por símbolo o por venue),
+// topes y umbrales de las estrategias, recarga de instrumentos y kill switch.
+// La API manda cada acción por un canal y el loop principal la aplica entre
+// dos vueltas, así nada cambia a mitad de un trade. Todo queda en el diario.
+
+use crate::arbitrage::{ConvergenceConfig, MakerTakerConfig};
+use crate::exchanges::Exchange;
+use crate::journal::Journal;
+use crate::simulator::DEFAULT_MAX_TRADE_USD;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use tokio::sync::{mpsc, oneshot};
+
+// Acciones en cola mientras el loop está ocupado; más que esto es un operador apretando de más
+const CONTROL_QUEUE: usize = 32;
+
+// Qué se pausa: sin símbolo ni venue es todo; con ambos, ese símbolo en ese venue
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange: Option<Exchange>,
+}
+
+impl Scope {
+    fn is_global(&self) -> bool {
+        self.symbol.is_none() && self.exchange.is_none()
+    }
+
+    fn covers(&self, symbol: &str, exchange: Exchange) -> bool {
+        self.symbol.as_deref().is_none_or(|s| s == symbol) && self.exchange.is_none_or(|ex| ex == exchange)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.symbol, self.exchange) {
+            (None, None) => write!(f, "global"),
+            (Some(symbol), None) => write!(f, "{}", symbol),
+            (None, Some(exchange)) => write!(f, "{:?}", exchange),
+            (Some(symbol), Some(exchange)) => write!(f, "{} en {:?}", symbol, exchange),
+        }
+    }
+}
+
+// Topes y umbrales que se pueden cambiar sin reiniciar
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeLimits {
+    pub sim_max_trade_usd: f64,
+    pub maker_taker: MakerTakerConfig,
+    pub convergence: ConvergenceConfig,
+}
+
+impl Default for RuntimeLimits {
+    fn default() -> Self {
+        Self {
+            sim_max_trade_usd: DEFAULT_MAX_TRADE_USD,
+            maker_taker: MakerTakerConfig::default(),
+            convergence: ConvergenceConfig::default(),
+        }
+    }
+}
+
+impl RuntimeLimits {
+    fn validate(&self) -> Result<(), String> {
+        let mt = &self.maker_taker;
+        let conv = &self.convergence;
+        let checks = [
+            (self.sim_max_trade_usd > 0.0, "sim_max_trade_usd debe ser positivo"),
+            (mt.notional_usd > 0.0, "maker_taker.notional_usd debe ser positivo"),
+            (mt.min_edge_pct >= 0.0, "maker_taker.min_edge_pct no puede ser negativo"),
+            (mt.reprice_tolerance_pct >= 0.0, "maker_taker.reprice_tolerance_pct no puede ser negativo"),
+            (conv.notional_usd > 0.0, "convergence.notional_usd debe ser positivo"),
+            (conv.window >= 2, "convergence.window debe ser al menos 2"),
+            (conv.min_samples >= 2 && conv.min_samples <= conv.window, "convergence.min_samples debe estar entre 2 y window"),
+            (conv.entry_z > 0.0, "convergence.entry_z debe ser positivo"),
+            (conv.exit_z < conv.entry_z, "convergence.exit_z debe ser menor que entry_z"),
+            (conv.max_hold_ms > 0, "convergence.max_hold_ms debe ser positivo"),
+        ];
+        match checks.iter().find(|(ok, _)| !ok) {
+            Some((_, message)) => Err(message.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    // Merge parcial estilo JSON merge patch: solo se tocan los campos presentes
+    fn patched(&self, patch: &Value) -> Result<Self, String> {
+        if !patch.is_object() {
+            return Err("se esperaba un objeto JSON con los campos a cambiar".to_string());
+        }
+        let mut current = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        merge(&mut current, patch);
+        let limits: Self = serde_json::from_value(current).map_err(|e| e.to_string())?;
+        limits.validate()?;
+        Ok(limits)
+    }
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KillSwitch {
+    pub reason: String,
+    pub actor: String,
+    pub tripped_at: u64,
+}
+
+// Estado de control vigente: va en el payload del dashboard y en el estado guardado
+// (un kill switch activo sobrevive a un reinicio)
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TradingControl {
+    pub paused: Vec<Scope>,
+    pub kill_switch: Option<KillSwitch>,
+    #[serde(default)]
+    pub limits: RuntimeLimits,
+}
+
+impl TradingControl {
+    // ¿Se puede abrir algo en `symbol` que toque todos estos venues?
+    pub fn allows(&self, symbol: &str, exchanges: &[Exchange]) -> bool {
+        self.kill_switch.is_none()
+            && exchanges.iter().all(|ex| !self.paused.iter().any(|scope| scope.covers(symbol, *ex)))
+    }
+
+    // Venues donde no se puede operar `symbol` ahora mismo
+    pub fn paused_exchanges(&self, symbol: &str) -> HashSet<Exchange> {
+        Exchange::ALL.into_iter().filter(|ex| !self.allows(symbol, &[*ex])).collect()
+    }
+
+    // Solo el cambio de estado; los efectos sobre las estrategias los hace quien llama
+    pub fn apply(&mut self, action: &ControlAction, actor: &str) -> Result<(), String> {
+        match action {
+            ControlAction::Pause(scope) => {
+                if scope.symbol.as_deref().is_some_and(|s| s.trim().is_empty()) {
+                    return Err("símbolo vacío".to_string());
+                }
+                if !self.paused.contains(scope) {
+                    self.paused.push(scope.clone());
+                }
+            }
+            ControlAction::Resume(scope) => {
+                if self.kill_switch.is_some() {
+                    return Err("kill switch activo: hay que rearmarlo antes de reanudar".to_string());
+                }
+                if scope.is_global() {
+                    // Reanudar global levanta todas las pausas
+                    self.paused.clear();
+                } else if let Some(idx) = self.paused.iter().position(|p| p == scope) {
+                    self.paused.remove(idx);
+                } else {
+                    return Err(format!("no hay una pausa para {}", scope));
+                }
+            }
+            ControlAction::SetLimits(patch) => {
+                self.limits = self.limits.patched(patch)?;
+            }
+            ControlAction::RefreshInstruments => {}
+            ControlAction::TripKillSwitch { reason } => {
+                if self.kill_switch.is_none() {
+                    self.kill_switch = Some(KillSwitch {
+                        reason: reason.clone(),
+                        actor: actor.to_string(),
+                        tripped_at: chrono::Utc::now().timestamp_millis() as u64,
+                    });
+                }
+            }
+            ControlAction::ResetKillSwitch => {
+                if self.kill_switch.take().is_none() {
+                    return Err("el kill switch no está activo".to_string());
+                }
+                // Rearmar no reanuda: se vuelve a operar con un resume explícito
+                if !self.paused.contains(&Scope::default()) {
+                    self.paused.push(Scope::default());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub enum ControlAction {
+    Pause(Scope),
+    Resume(Scope),
+    SetLimits(Value),
+    RefreshInstruments,
+    TripKillSwitch { reason: String },
+    ResetKillSwitch,
+}
+
+impl ControlAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlAction::Pause(_) => "pause",
+            ControlAction::Resume(_) => "resume",
+            ControlAction::SetLimits(_) => "set_limits",
+            ControlAction::RefreshInstruments => "refresh_instruments",
+            ControlAction::TripKillSwitch { .. } => "kill_switch",
+            ControlAction::ResetKillSwitch => "reset_kill_switch",
+        }
+    }
+
+    // Parámetros tal como se pidieron, para la auditoría
+    pub fn params(&self) -> String {
+        let params = match self {
+            ControlAction::Pause(scope) | ControlAction::Resume(scope) => serde_json::to_value(scope).unwrap_or_default(),
+            ControlAction::SetLimits(patch) => patch.clone(),
+            ControlAction::TripKillSwitch { reason } => serde_json::json!({ "reason": reason }),
+            ControlAction::RefreshInstruments | ControlAction::ResetKillSwitch => serde_json::json!({}),
+        };
+        params.to_string()
+    }
+}
+
+pub enum ControlError {
+    Rejected(String),
+    Unavailable, // El loop principal ya no atiende (apagando)
+}
+
+pub struct ControlRequest {
+    pub action: ControlAction,
+    pub actor: String,
+    reply: oneshot::Sender<Result<TradingControl, String>>,
+}
+
+impl ControlRequest {
+    // Auditoría y respuesta a la API
+    pub fn finish(self, journal: &Journal, result: Result<TradingControl, String>) {
+        self.finish_with(journal, result, "ok");
+    }
+
+    // Como `finish`, con el detalle del resultado exitoso para la auditoría
+    pub fn finish_with(self, journal: &Journal, result: Result<TradingControl, String>, ok_outcome: &str) {
+        let params = self.action.params();
+        match &result {
+            Ok(_) => tracing::info!("🛂 Control: {} {} por {}: {}", self.action.name(), params, self.actor, ok_outcome),
+            Err(e) => tracing::warn!("⚠️ Control rechazado: {} {} por {}: {}", self.action.name(), params, self.actor, e),
+        }
+        let outcome = result.as_ref().err().map(String::as_str).unwrap_or(ok_outcome);
+        journal.record_control(&self.actor, self.action.name(), &params, outcome);
+        let _ = self.reply.send(result);
+    }
+}
+
+#[derive(Clone)]
+pub struct ControlHandle {
+    tx: mpsc::Sender<ControlRequest>,
+}
+
+pub fn channel() -> (ControlHandle, mpsc::Receiver<ControlRequest>) {
+    let (tx, rx) = mpsc::channel(CONTROL_QUEUE);
+    (ControlHandle { tx }, rx)
+}
+
+impl ControlHandle {
+    // Espera a que el loop aplique la acción y devuelve el estado resultante
+    pub async fn send(&self, action: ControlAction, actor: String) -> Result<TradingControl, ControlError> {
+        let (reply, response) = oneshot::channel();
+        self.tx
+            .send(ControlRequest { action, actor, reply })
+            .await
+            .map_err(|_| ControlError::Unavailable)?;
+        match response.await {
+            Ok(result) => result.map_err(ControlError::Rejected),
+            Err(_) => Err(ControlError::Unavailable),
+        }
+    }
+}
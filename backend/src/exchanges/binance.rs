@@ -1,20 +1,79 @@
-use super::{BookUpdate, Exchange, ExchangeConnector};
-use anyhow::Result;
+use super::orderbook::{snapshot_client, binance_futures_rule, binance_spot_rule, parse_levels, BookSnapshot, DeltaOutcome, DepthDelta, SyncedBook};
+use super::{BookUpdate, Exchange, ExchangeConnector, FeedEvent, FundingUpdate, MarketType, SymbolMap};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
+const FUTURES_WS_URL: &str = "wss://fstream.binance.com/ws";
+const SPOT_WS_URL: &str = "wss://stream.binance.com:9443/ws";
+const FUTURES_DEPTH_URL: &str = "https://fapi.binance.com/fapi/v1/depth";
+const SPOT_DEPTH_URL: &str = "https://api.binance.com/api/v3/depth";
+// Profundidad del snapshot REST: tiene que cubrir los niveles que los deltas pueden destapar
+const SNAPSHOT_LIMIT: &str = "1000";
+
 pub struct BinanceConnector {
+    market_type: MarketType,
     tx: Option<mpsc::Sender<BookUpdate>>,
     rx: Option<mpsc::Receiver<BookUpdate>>,
+    funding_tx: Option<mpsc::Sender<FundingUpdate>>,
+    funding_rx: Option<mpsc::Receiver<FundingUpdate>>,
+    event_tx: Option<mpsc::Sender<FeedEvent>>,
+    event_rx: Option<mpsc::Receiver<FeedEvent>>,
 }
 
 impl BinanceConnector {
     pub fn new() -> Self {
+        Self::with_market(MarketType::Perp)
+    }
+
+    pub fn spot() -> Self {
+        Self::with_market(MarketType::Spot)
+    }
+
+    fn with_market(market_type: MarketType) -> Self {
         let (tx, rx) = mpsc::channel(1000);
-        Self { tx: Some(tx), rx: Some(rx) }
+        let (funding_tx, funding_rx) = mpsc::channel(1000);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        Self {
+            market_type,
+            tx: Some(tx),
+            rx: Some(rx),
+            funding_tx: Some(funding_tx),
+            funding_rx: Some(funding_rx),
+            event_tx: Some(event_tx),
+            event_rx: Some(event_rx),
+        }
+    }
+
+    // {"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}
+    async fn fetch_snapshot(client: &reqwest::Client, market_type: MarketType, native: &str) -> Result<BookSnapshot> {
+        let url = match market_type {
+            MarketType::Perp => FUTURES_DEPTH_URL,
+            MarketType::Spot => SPOT_DEPTH_URL,
+        };
+        let json: Value = client.get(url).query(&[("symbol", native), ("limit", SNAPSHOT_LIMIT)]).send().await?.json().await?;
+        Ok(BookSnapshot {
+            seq: json["lastUpdateId"].as_u64().ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+            bids: parse_levels(&json["bids"]),
+            asks: parse_levels(&json["asks"]),
+        })
+    }
+
+    // depthUpdate: {"e":"depthUpdate","s":"BTCUSDT","U":157,"u":160,"pu":149,"b":[["0.0024","10"]],"a":[...]}
+    fn parse_delta(json: &Value) -> Option<DepthDelta> {
+        Some(DepthDelta {
+            first_seq: json["U"].as_u64()?,
+            last_seq: json["u"].as_u64()?,
+            prev_seq: json["pu"].as_u64(),
+            bids: parse_levels(&json["b"]),
+            asks: parse_levels(&json["a"]),
+        })
     }
 }
 
@@ -24,24 +83,44 @@ impl ExchangeConnector for BinanceConnector {
         Exchange::Binance
     }
 
-    async fn connect(&mut self, symbols: Vec<String>) -> Result<()> {
+    async fn connect(&mut self, symbols: SymbolMap) -> Result<JoinSet<()>> {
+        let mut tasks = JoinSet::new();
         let tx = self.tx.clone().unwrap();
-        
+        let funding_tx = self.funding_tx.clone().unwrap();
+        let event_tx = self.event_tx.clone().unwrap();
+        let market_type = self.market_type;
+
         // Usamos la URL base limpia. La suscripción se hace via JSON después.
-        let url = "wss://fstream.binance.com/ws";
+        let url = match market_type {
+            MarketType::Perp => FUTURES_WS_URL,
+            MarketType::Spot => SPOT_WS_URL,
+        };
+        let rule = match market_type {
+            MarketType::Perp => binance_futures_rule,
+            MarketType::Spot => binance_spot_rule,
+        };
 
-        tokio::spawn(async move {
+        tasks.spawn(async move {
+            let client = snapshot_client();
             loop {
-                tracing::info!("🔌 Connecting to Binance Futures...");
-                
+                tracing::info!("🔌 Connecting to Binance {:?}...", market_type);
+
                 match connect_async(url).await {
                     Ok((ws_stream, _)) => {
                         tracing::info!("✅ Connected to Binance WS");
+                        let _ = event_tx.send(FeedEvent::Connected).await;
                         let (mut write, mut read) = ws_stream.split();
 
-                        // 1. Convertir símbolos (BTC-USDT -> btcusdt)
-                        let params: Vec<String> = symbols.iter()
-                            .map(|s| format!("{}@bookTicker", s.replace("-", "").to_lowercase()))
+                        // 1. Streams por nombre nativo en minúscula (btcusdt). markPrice trae el funding (solo perps).
+                        let params: Vec<String> = symbols.natives()
+                            .flat_map(|s| {
+                                let stream = s.to_lowercase();
+                                let mut streams = vec![format!("{}@depth@100ms", stream)];
+                                if market_type == MarketType::Perp {
+                                    streams.push(format!("{}@markPrice@1s", stream));
+                                }
+                                streams
+                            })
                             .collect();
 
                         // 2. Enviar Suscripción Inmediata
@@ -56,74 +135,153 @@ impl ExchangeConnector for BinanceConnector {
                             tracing::error!("❌ Failed to subscribe Binance: {:?}", e);
                             // Si falla enviar suscripción, forzamos reconexión
                         } else {
-                            tracing::info!("📡 Subscribed to {} symbols on Binance", params.len());
-                            
+                            tracing::info!("📡 Subscribed to {} streams on Binance {:?}", params.len(), market_type);
+
+                            // Libros locales de esta conexión: una reconexión arranca todo de cero
+                            let mut books: HashMap<String, SyncedBook> = HashMap::new();
+                            let (snapshot_tx, mut snapshot_rx) = mpsc::channel::<(String, Result<BookSnapshot>)>(100);
+                            let request_snapshot = |native: String, delay: Duration| {
+                                let client = client.clone();
+                                let snapshot_tx = snapshot_tx.clone();
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(delay).await;
+                                    let result = Self::fetch_snapshot(&client, market_type, &native).await;
+                                    let _ = snapshot_tx.send((native, result)).await;
+                                });
+                            };
+
                             // 3. Loop de lectura
-                            while let Some(msg) = read.next().await {
-                                match msg {
-                                    Ok(Message::Text(text)) => {
-                                        if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                                            // Ignorar respuestas de control (id, null result)
-                                            if json.get("id").is_some() { continue; }
-
-                                            // Parsear BookTicker
-                                            if let (Some(s), Some(bid), Some(ask), Some(bid_qty), Some(ask_qty)) = (
-                                                json.get("s").and_then(|v| v.as_str()),
-                                                json.get("b").and_then(|v| v.as_str()),
-                                                json.get("a").and_then(|v| v.as_str()),
-                                                json.get("B").and_then(|v| v.as_str()),
-                                                json.get("A").and_then(|v| v.as_str())
-                                            ) {
-                                                // Convertir btcusdt -> BTC-USDT
-                                                let symbol_std = format!("{}-{}", &s[0..s.len()-4], "USDT");
-                                                
-                                                if let (Ok(bid_p), Ok(ask_p), Ok(bid_sz), Ok(ask_sz)) = (
-                                                    bid.parse::<f64>(), 
-                                                    ask.parse::<f64>(),
-                                                    bid_qty.parse::<f64>(),
-                                                    ask_qty.parse::<f64>()
-                                                ) {
-                                                    let _ = tx.send(BookUpdate {
-                                                        symbol: symbol_std,
-                                                        exchange: Exchange::Binance,
-                                                        bid: bid_p,
-                                                        ask: ask_p,
-                                                        bid_size: bid_sz,
-                                                        ask_size: ask_sz,
-                                                        timestamp: chrono::Utc::now().timestamp_millis() as u64,
-                                                    }).await;
+                            loop {
+                                tokio::select! {
+                                    Some((native, result)) = snapshot_rx.recv() => {
+                                        let received_at = Instant::now();
+                                        let (Some(book), Some(symbol)) = (books.get_mut(&native), symbols.symbol(&native)) else { continue };
+                                        match result {
+                                            Ok(snapshot) => {
+                                                if book.on_snapshot(snapshot) {
+                                                    tracing::info!("📗 Libro Binance {:?} {} sincronizado", market_type, symbol);
+                                                    if let Some(update) = book.book_update(symbol, Exchange::Binance, market_type, None, received_at) {
+                                                        let _ = tx.send(update).await;
+                                                    }
+                                                } else {
+                                                    tracing::warn!("⚠️ Snapshot de {} no empalma con los deltas, pidiendo otro", native);
+                                                    request_snapshot(native, book.snapshot_delay());
                                                 }
                                             }
+                                            Err(e) => {
+                                                tracing::warn!("⚠️ Snapshot de {} falló: {:?}", native, e);
+                                                book.snapshot_failed();
+                                            }
                                         }
                                     }
-                                    Ok(Message::Ping(payload)) => {
-                                        // Responder Pongs es vital para no ser desconectado
-                                        let _ = write.send(Message::Pong(payload)).await;
-                                    }
-                                    Ok(Message::Close(_)) => {
-                                        tracing::warn!("⚠️ Binance connection closed by server");
-                                        break;
+                                    msg = read.next() => {
+                                        let Some(msg) = msg else { break };
+                                        match msg {
+                                            Ok(Message::Text(text)) => {
+                                                let received_at = Instant::now();
+                                                let Ok(json) = serde_json::from_str::<Value>(&text) else {
+                                                    let _ = event_tx.send(FeedEvent::ParseError).await;
+                                                    continue;
+                                                };
+                                                // Ignorar respuestas de control (id, null result)
+                                                if json.get("id").is_some() { continue; }
+
+                                                match json.get("e").and_then(|v| v.as_str()) {
+                                                    // markPriceUpdate: {"s":"BTCUSDT","r":"0.0001","T":1562306400000,...}
+                                                    Some("markPriceUpdate") => {
+                                                        if let (Some(symbol), Some(rate)) = (
+                                                            json.get("s").and_then(|v| v.as_str()).and_then(|s| symbols.symbol(s)),
+                                                            json.get("r").and_then(|v| v.as_str()).and_then(|r| r.parse::<f64>().ok())
+                                                        ) {
+                                                            let _ = funding_tx.send(FundingUpdate {
+                                                                symbol: symbol.to_string(),
+                                                                exchange: Exchange::Binance,
+                                                                rate,
+                                                                next_funding_time: json.get("T").and_then(|v| v.as_u64()).unwrap_or(0),
+                                                                timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                                                            }).await;
+                                                        }
+                                                    }
+                                                    Some("depthUpdate") => {
+                                                        let Some(native) = json["s"].as_str() else { continue };
+                                                        let Some(symbol) = symbols.symbol(native) else { continue };
+                                                        let Some(delta) = Self::parse_delta(&json) else {
+                                                            let _ = event_tx.send(FeedEvent::ParseError).await;
+                                                            continue;
+                                                        };
+                                                        let exchange_ts = json["E"].as_u64();
+                                                        let book = books.entry(native.to_string()).or_insert_with(|| SyncedBook::new(rule));
+                                                        let was_synced = book.is_synced();
+
+                                                        match book.on_delta(delta) {
+                                                            DeltaOutcome::Applied => {
+                                                                if let Some(update) = book.book_update(symbol, Exchange::Binance, market_type, exchange_ts, received_at) {
+                                                                    let _ = tx.send(update).await;
+                                                                }
+                                                            }
+                                                            DeltaOutcome::Buffered => {}
+                                                            DeltaOutcome::NeedSnapshot => {
+                                                                if was_synced {
+                                                                    tracing::warn!("⚠️ Hueco de secuencia en Binance {:?} {}: re-sincronizando", market_type, symbol);
+                                                                    // Avisamos que el libro quedó inválido
+                                                                    if let Some(update) = book.book_update(symbol, Exchange::Binance, market_type, None, received_at) {
+                                                                        let _ = tx.send(update).await;
+                                                                    }
+                                                                }
+                                                                request_snapshot(native.to_string(), book.snapshot_delay());
+                                                            }
+                                                        }
+                                                    }
+                                                    _ => {}
+                                                }
+                                            }
+                                            Ok(Message::Ping(payload)) => {
+                                                // Responder Pongs es vital para no ser desconectado
+                                                let _ = write.send(Message::Pong(payload)).await;
+                                            }
+                                            Ok(Message::Close(_)) => {
+                                                tracing::warn!("⚠️ Binance connection closed by server");
+                                                break;
+                                            }
+                                            Err(_) => break, // Error de socket
+                                            _ => {}
+                                        }
                                     }
-                                    Err(_) => break, // Error de socket
-                                    _ => {}
+                                }
+                            }
+
+                            // Los libros de esta conexión ya no se actualizan
+                            for (native, book) in books.iter_mut() {
+                                book.invalidate();
+                                if let Some(update) = symbols.symbol(native).and_then(|s| book.book_update(s, Exchange::Binance, market_type, None, Instant::now())) {
+                                    let _ = tx.send(update).await;
                                 }
                             }
                         }
+                        let _ = event_tx.send(FeedEvent::Disconnected).await;
                     }
                     Err(e) => {
                         tracing::error!("❌ Binance Connection Failed: {:?}", e);
                     }
                 }
-                
+
                 tracing::warn!("🔄 Reconnecting to Binance in 2s...");
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
             }
         });
 
-        Ok(())
+        Ok(tasks)
     }
 
     fn get_receiver(&mut self) -> mpsc::Receiver<BookUpdate> {
         self.rx.take().expect("Receiver already taken")
     }
-}
\ No newline at end of file
+
+    fn get_funding_receiver(&mut self) -> mpsc::Receiver<FundingUpdate> {
+        self.funding_rx.take().expect("Funding receiver already taken")
+    }
+
+    fn get_event_receiver(&mut self) -> mpsc::Receiver<FeedEvent> {
+        self.event_rx.take().expect("Event receiver already taken")
+    }
+}
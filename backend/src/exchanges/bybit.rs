@@ -1,34 +1,78 @@
-use super::{BookUpdate, Exchange, ExchangeConnector};
-use anyhow::Result;
+use super::orderbook::{snapshot_client, bybit_rule, parse_levels, BookSnapshot, DeltaOutcome, DepthDelta, SyncedBook};
+use super::{BookUpdate, Exchange, ExchangeConnector, FeedEvent, FundingUpdate, MarketType, SymbolMap};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
+const ORDERBOOK_REST_URL: &str = "https://api.bybit.com/v5/market/orderbook";
+
 pub struct BybitConnector {
+    market_type: MarketType,
     tx: Option<mpsc::Sender<BookUpdate>>,
     rx: Option<mpsc::Receiver<BookUpdate>>,
+    funding_tx: Option<mpsc::Sender<FundingUpdate>>,
+    funding_rx: Option<mpsc::Receiver<FundingUpdate>>,
+    event_tx: Option<mpsc::Sender<FeedEvent>>,
+    event_rx: Option<mpsc::Receiver<FeedEvent>>,
 }
 
 impl BybitConnector {
     pub fn new() -> Self {
-        let (tx, rx) = mpsc::channel(1000);
-        Self { tx: Some(tx), rx: Some(rx) }
+        Self::with_market(MarketType::Perp)
     }
 
-    fn normalize_symbol(symbol: &str) -> String {
-        symbol.replace("-", "")
+    pub fn spot() -> Self {
+        Self::with_market(MarketType::Spot)
+    }
+
+    fn with_market(market_type: MarketType) -> Self {
+        let (tx, rx) = mpsc::channel(1000);
+        let (funding_tx, funding_rx) = mpsc::channel(1000);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        Self {
+            market_type,
+            tx: Some(tx),
+            rx: Some(rx),
+            funding_tx: Some(funding_tx),
+            funding_rx: Some(funding_rx),
+            event_tx: Some(event_tx),
+            event_rx: Some(event_rx),
+        }
     }
 
-    fn denormalize_symbol(bybit_symbol: &str) -> String {
-        if bybit_symbol.ends_with("USDT") {
-            let base = &bybit_symbol[0..bybit_symbol.len() - 4];
-            format!("{}-USDT", base)
-        } else {
-            bybit_symbol.to_string()
+    // El `u` del REST solo coincide con el del WS si ambos son de la misma profundidad
+    fn depth(market_type: MarketType) -> u32 {
+        match market_type {
+            MarketType::Perp => 500,
+            MarketType::Spot => 200,
         }
     }
+
+    // {"retCode":0,"result":{"s":"BTCUSDT","b":[["65485.47","47.081829"]],"a":[...],"u":18521288,...}}
+    async fn fetch_snapshot(client: &reqwest::Client, market_type: MarketType, native: &str) -> Result<BookSnapshot> {
+        let category = match market_type {
+            MarketType::Perp => "linear",
+            MarketType::Spot => "spot",
+        };
+        let limit = Self::depth(market_type).to_string();
+        let json: Value = client
+            .get(ORDERBOOK_REST_URL)
+            .query(&[("category", category), ("symbol", native), ("limit", limit.as_str())])
+            .send().await?
+            .json().await?;
+        let result = &json["result"];
+        Ok(BookSnapshot {
+            seq: result["u"].as_u64().ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+            bids: parse_levels(&result["b"]),
+            asks: parse_levels(&result["a"]),
+        })
+    }
 }
 
 #[async_trait]
@@ -37,34 +81,88 @@ impl ExchangeConnector for BybitConnector {
         Exchange::Bybit
     }
 
-    async fn connect(&mut self, symbols: Vec<String>) -> Result<()> {
-        let url = "wss://stream.bybit.com/v5/public/linear";
-        tracing::info!("🔌 Connecting to Bybit V5 (Linear)...");
+    async fn connect(&mut self, symbols: SymbolMap) -> Result<JoinSet<()>> {
+        let mut tasks = JoinSet::new();
+        let market_type = self.market_type;
+        let url = match market_type {
+            MarketType::Perp => "wss://stream.bybit.com/v5/public/linear",
+            MarketType::Spot => "wss://stream.bybit.com/v5/public/spot",
+        };
+        tracing::info!("🔌 Connecting to Bybit V5 ({:?})...", market_type);
 
         let (ws_stream, _) = connect_async(url).await?;
         let (mut write, mut read) = ws_stream.split();
 
+        // tickers.* trae fundingRate / nextFundingTime (solo linear)
         let args: Vec<String> = symbols
-            .iter()
-            .map(|s| format!("orderbook.1.{}", Self::normalize_symbol(s)))
+            .natives()
+            .flat_map(|sym| {
+                let mut topics = vec![format!("orderbook.{}.{}", Self::depth(market_type), sym)];
+                if market_type == MarketType::Perp {
+                    topics.push(format!("tickers.{}", sym));
+                }
+                topics
+            })
             .collect();
 
-        let subscribe_msg = json!({
-            "op": "subscribe",
-            "args": args
-        });
-
-        write.send(Message::Text(subscribe_msg.to_string())).await?;
-        tracing::info!("📡 Subscribed to Bybit Orderbooks");
+        // Spot acepta como máximo 10 args por mensaje de suscripción
+        for chunk in args.chunks(10) {
+            let subscribe_msg = json!({
+                "op": "subscribe",
+                "args": chunk
+            });
+            write.send(Message::Text(subscribe_msg.to_string())).await?;
+        }
+        tracing::info!("📡 Subscribed to Bybit {:?} Orderbooks", market_type);
 
         let tx = self.tx.clone().unwrap();
+        let funding_tx = self.funding_tx.clone().unwrap();
+        let event_tx = self.event_tx.clone().unwrap();
 
-        tokio::spawn(async move {
+        tasks.spawn(async move {
+            let _ = event_tx.send(FeedEvent::Connected).await;
             // Ping cada 20 segundos para mantener la conexión viva
             let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(20));
 
+            // El WS manda su propio snapshot al suscribir; el REST solo se usa ante un hueco
+            let client = snapshot_client();
+            let mut books: HashMap<String, SyncedBook> = HashMap::new();
+            // Último funding conocido por símbolo: los deltas de tickers solo traen lo que cambió
+            let mut tickers: HashMap<String, (Option<f64>, u64)> = HashMap::new();
+            let (snapshot_tx, mut snapshot_rx) = mpsc::channel::<(String, Result<BookSnapshot>)>(100);
+            let request_snapshot = |native: String, delay: Duration| {
+                let client = client.clone();
+                let snapshot_tx = snapshot_tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let result = Self::fetch_snapshot(&client, market_type, &native).await;
+                    let _ = snapshot_tx.send((native, result)).await;
+                });
+            };
+
             loop {
                 tokio::select! {
+                    Some((native, result)) = snapshot_rx.recv() => {
+                        let received_at = Instant::now();
+                        let (Some(book), Some(symbol)) = (books.get_mut(&native), symbols.symbol(&native)) else { continue };
+                        match result {
+                            Ok(snapshot) => {
+                                if book.on_snapshot(snapshot) {
+                                    tracing::info!("📗 Libro Bybit {:?} {} sincronizado", market_type, symbol);
+                                    if let Some(update) = book.book_update(symbol, Exchange::Bybit, market_type, None, received_at) {
+                                        let _ = tx.send(update).await;
+                                    }
+                                } else {
+                                    tracing::warn!("⚠️ Snapshot de {} no empalma con los deltas, pidiendo otro", native);
+                                    request_snapshot(native, book.snapshot_delay());
+                                }
+                            }
+                            Err(e) => {
+                                tracing::warn!("⚠️ Snapshot de {} falló: {:?}", native, e);
+                                book.snapshot_failed();
+                            }
+                        }
+                    }
                     _ = ping_interval.tick() => {
                         let ping_msg = json!({"op": "ping"});
                         if let Err(e) = write.send(Message::Text(ping_msg.to_string())).await {
@@ -75,33 +173,81 @@ impl ExchangeConnector for BybitConnector {
                     msg = read.next() => {
                         match msg {
                             Some(Ok(Message::Text(text))) => {
+                                let received_at = Instant::now();
                                 if let Ok(json) = serde_json::from_str::<Value>(&text) {
                                     // Ignorar pong y confirmaciones
                                     if json.get("op").map(|s| s == "pong").unwrap_or(false) { continue; }
                                     if json.get("op").is_some() || json.get("success").is_some() { continue; }
 
-                                    let ts = json.get("ts").and_then(|t| t.as_u64()).unwrap_or_else(|| chrono::Utc::now().timestamp_millis() as u64);
-
-                                    if let Some(data) = json.get("data") {
-                                        if let Some(s) = data["s"].as_str() {
-                                            let bids = &data["b"];
-                                            let asks = &data["a"];
-                                            let get_data = |list: &Value| -> Option<(f64, f64)> {
-                                                let item = list.get(0)?; 
-                                                let p = item.get(0)?.as_str()?.parse().ok()?;
-                                                let sz = item.get(1)?.as_str()?.parse().ok()?;
-                                                Some((p, sz))
-                                            };
-                                            if let (Some((bid, bid_sz)), Some((ask, ask_sz))) = (get_data(bids), get_data(asks)) {
-                                                let update = BookUpdate {
-                                                    symbol: Self::denormalize_symbol(s),
-                                                    exchange: Exchange::Bybit,
-                                                    bid, ask, bid_size: bid_sz, ask_size: ask_sz, timestamp: ts,
-                                                };
+                                    let exchange_ts = json.get("ts").and_then(|t| t.as_u64());
+
+                                    // Los deltas de tickers solo traen campos que cambiaron
+                                    let is_ticker = json.get("topic").and_then(|t| t.as_str()).map(|t| t.starts_with("tickers.")).unwrap_or(false);
+                                    if is_ticker {
+                                        let data = &json["data"];
+                                        let Some(native) = data["symbol"].as_str() else { continue };
+                                        let Some(symbol) = symbols.symbol(native) else { continue };
+                                        let (rate, next_funding_time) = tickers.entry(native.to_string()).or_insert((None, 0));
+                                        if let Some(new_rate) = data["fundingRate"].as_str().and_then(|r| r.parse::<f64>().ok()) {
+                                            *rate = Some(new_rate);
+                                        }
+                                        if let Some(next) = data["nextFundingTime"].as_str().and_then(|t| t.parse().ok()) {
+                                            *next_funding_time = next;
+                                        }
+                                        // Cualquier delta confirma que el último valor sigue vigente
+                                        if let Some(rate) = *rate {
+                                            let _ = funding_tx.send(FundingUpdate {
+                                                symbol: symbol.to_string(),
+                                                exchange: Exchange::Bybit,
+                                                rate,
+                                                next_funding_time: *next_funding_time,
+                                                timestamp: exchange_ts.unwrap_or_else(|| chrono::Utc::now().timestamp_millis() as u64),
+                                            }).await;
+                                        }
+                                        continue;
+                                    }
+
+                                    // orderbook: {"type":"snapshot"|"delta","data":{"s":"BTCUSDT","b":[...],"a":[...],"u":18521288}}
+                                    let data = &json["data"];
+                                    let Some(native) = data["s"].as_str() else { continue };
+                                    let (Some(symbol), Some(seq)) = (symbols.symbol(native), data["u"].as_u64()) else { continue };
+                                    let bids = parse_levels(&data["b"]);
+                                    let asks = parse_levels(&data["a"]);
+                                    let book = books.entry(native.to_string()).or_insert_with(|| SyncedBook::new(bybit_rule));
+
+                                    if json["type"].as_str() == Some("snapshot") {
+                                        // También llega sin pedirlo si el servicio de Bybit se reinicia (u = 1)
+                                        if !book.on_snapshot(BookSnapshot { seq, bids, asks }) {
+                                            tracing::warn!("⚠️ Snapshot WS de Bybit {:?} {} cruzado, pidiendo por REST", market_type, symbol);
+                                            request_snapshot(native.to_string(), book.snapshot_delay());
+                                            continue;
+                                        }
+                                        if let Some(update) = book.book_update(symbol, Exchange::Bybit, market_type, exchange_ts, received_at) {
+                                            let _ = tx.send(update).await;
+                                        }
+                                        continue;
+                                    }
+
+                                    let was_synced = book.is_synced();
+                                    match book.on_delta(DepthDelta { first_seq: seq, last_seq: seq, prev_seq: None, bids, asks }) {
+                                        DeltaOutcome::Applied => {
+                                            if let Some(update) = book.book_update(symbol, Exchange::Bybit, market_type, exchange_ts, received_at) {
                                                 let _ = tx.send(update).await;
                                             }
                                         }
+                                        DeltaOutcome::Buffered => {}
+                                        DeltaOutcome::NeedSnapshot => {
+                                            if was_synced {
+                                                tracing::warn!("⚠️ Hueco de secuencia en Bybit {:?} {}: re-sincronizando", market_type, symbol);
+                                                if let Some(update) = book.book_update(symbol, Exchange::Bybit, market_type, exchange_ts, received_at) {
+                                                    let _ = tx.send(update).await;
+                                                }
+                                            }
+                                            request_snapshot(native.to_string(), book.snapshot_delay());
+                                        }
                                     }
+                                } else {
+                                    let _ = event_tx.send(FeedEvent::ParseError).await;
                                 }
                             }
                             Some(Err(e)) => {
@@ -117,12 +263,29 @@ impl ExchangeConnector for BybitConnector {
                     }
                 }
             }
+
+            let _ = event_tx.send(FeedEvent::Disconnected).await;
+            // Los libros ya no se actualizan: que nadie opere contra el último top
+            for (native, book) in books.iter_mut() {
+                book.invalidate();
+                if let Some(update) = symbols.symbol(native).and_then(|s| book.book_update(s, Exchange::Bybit, market_type, None, Instant::now())) {
+                    let _ = tx.send(update).await;
+                }
+            }
         });
 
-        Ok(())
+        Ok(tasks)
     }
 
     fn get_receiver(&mut self) -> mpsc::Receiver<BookUpdate> {
         self.rx.take().expect("Receiver already taken")
     }
+
+    fn get_funding_receiver(&mut self) -> mpsc::Receiver<FundingUpdate> {
+        self.funding_rx.take().expect("Funding receiver already taken")
+    }
+
+    fn get_event_receiver(&mut self) -> mpsc::Receiver<FeedEvent> {
+        self.event_rx.take().expect("Event receiver already taken")
+    }
 }
\ No newline at end of file
@@ -1,32 +1,64 @@
-use super::{BookUpdate, Exchange, ExchangeConnector};
+use super::orderbook::{Level, LocalBook, DEPTH_LEVELS};
+use super::{BookIntegrity, BookUpdate, Exchange, ExchangeConnector, FeedEvent, FundingUpdate, MarketType, SymbolMap};
 use anyhow::Result;
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use serde_json::Value;
 // CORRECCIÓN: Importaciones separadas para mpsc y broadcast
 use tokio::sync::{mpsc, broadcast}; 
+use tokio::task::JoinSet;
 use tokio_tungstenite::{connect_async, tungstenite::client::IntoClientRequest, tungstenite::Message};
 use http::HeaderValue;
 use tracing::{info, warn, error};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+// El funding de Extended no viene en el stream de libros: lo leemos de las stats del mercado
+const MARKET_STATS_URL: &str = "https://api.starknet.extended.exchange/api/v1/info/markets";
+const FUNDING_POLL_SECS: u64 = 30;
+const FUNDING_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct ExtendedConnector {
     tx: Option<mpsc::Sender<BookUpdate>>,
     rx: Option<mpsc::Receiver<BookUpdate>>,
+    funding_tx: Option<mpsc::Sender<FundingUpdate>>,
+    funding_rx: Option<mpsc::Receiver<FundingUpdate>>,
+    event_tx: Option<mpsc::Sender<FeedEvent>>,
+    event_rx: Option<mpsc::Receiver<FeedEvent>>,
 }
 
 impl ExtendedConnector {
     pub fn new() -> Self {
         let (tx, rx) = mpsc::channel(1000);
-        Self { tx: Some(tx), rx: Some(rx) }
+        let (funding_tx, funding_rx) = mpsc::channel(1000);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        Self {
+            tx: Some(tx),
+            rx: Some(rx),
+            funding_tx: Some(funding_tx),
+            funding_rx: Some(funding_rx),
+            event_tx: Some(event_tx),
+            event_rx: Some(event_rx),
+        }
     }
 
-    fn normalize_symbol(symbol: &str) -> String {
-        if symbol.ends_with("USDT") {
-            symbol.replace("USDT", "USD")
-        } else {
-            symbol.to_string()
-        }
+    // {"status":"OK","data":{"fundingRate":"0.0001","nextFundingRate":1701563440000,...}}
+    async fn fetch_funding(client: &reqwest::Client, market: &str) -> Option<(f64, u64)> {
+        let url = format!("{}/{}/stats", MARKET_STATS_URL, market);
+        let json: Value = client.get(url).send().await.ok()?.json().await.ok()?;
+        let data = json.get("data")?;
+        let rate = data.get("fundingRate")?.as_str()?.parse::<f64>().ok()?;
+        let next = data.get("nextFundingRate").and_then(|t| t.as_u64()).unwrap_or(0);
+        Some((rate, next))
+    }
+
+    // [{"p":"25670","q":"0.1"}, ...]
+    fn parse_levels(value: &Value) -> Vec<Level> {
+        value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|l| Some((l.get("p")?.as_str()?.parse().ok()?, l.get("q")?.as_str()?.parse().ok()?)))
+            .collect()
     }
 }
 
@@ -36,21 +68,48 @@ impl ExchangeConnector for ExtendedConnector {
         Exchange::Extended
     }
 
-    async fn connect(&mut self, symbols: Vec<String>) -> Result<()> {
+    async fn connect(&mut self, symbols: SymbolMap) -> Result<JoinSet<()>> {
+        let mut tasks = JoinSet::new();
         let tx_base = self.tx.clone().unwrap();
+        let event_base = self.event_tx.clone().unwrap();
+        let funding_tx = self.funding_tx.clone().unwrap();
 
-        for symbol in symbols {
-            let market = Self::normalize_symbol(&symbol);
+        // Polling de funding para todos los mercados en una sola tarea
+        // (símbolo del sistema, mercado nativo "BTC-USD")
+        let markets: Vec<(String, String)> = symbols.iter()
+            .map(|(id, market)| (id.to_string(), market.clone()))
+            .collect();
+        tasks.spawn(async move {
+            let client = reqwest::Client::builder().timeout(FUNDING_TIMEOUT).build().unwrap_or_default();
+            let mut interval = tokio::time::interval(Duration::from_secs(FUNDING_POLL_SECS));
+            loop {
+                interval.tick().await;
+                for (symbol, market) in &markets {
+                    if let Some((rate, next_funding_time)) = Self::fetch_funding(&client, market).await {
+                        let _ = funding_tx.send(FundingUpdate {
+                            symbol: symbol.clone(),
+                            exchange: Exchange::Extended,
+                            rate,
+                            next_funding_time,
+                            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                        }).await;
+                    }
+                }
+            }
+        });
+
+        for (id, market) in symbols.iter() {
             let tx = tx_base.clone();
-            let safe_symbol = symbol.clone();
+            let event_tx = event_base.clone();
+            let safe_symbol = id.to_string();
 
             let url_str = format!(
-                "wss://api.starknet.extended.exchange/stream.extended.exchange/v1/orderbooks/{}?depth=1",
+                "wss://api.starknet.extended.exchange/stream.extended.exchange/v1/orderbooks/{}",
                 market
             );
 
             // Bucle de reconexión dentro del spawn
-            tokio::spawn(async move {
+            tasks.spawn(async move {
                 loop {
                     let mut request = match url_str.clone().into_client_request() {
                         Ok(req) => req,
@@ -62,50 +121,100 @@ impl ExchangeConnector for ExtendedConnector {
                     info!("🔌 Connecting to Extended: {}", safe_symbol);
 
                     if let Ok((ws_stream, _)) = connect_async(request).await {
+                        let _ = event_tx.send(FeedEvent::Connected).await;
                         let (_, mut read) = ws_stream.split();
+                        // Libro completo: SNAPSHOT al conectar y después DELTAs con `seq` consecutivo.
+                        // El REST de Extended no trae secuencia, así que ante un hueco se reconecta
+                        // y el nuevo SNAPSHOT del WS hace de resync.
+                        let mut book = LocalBook::default();
+                        let mut last_seq: Option<u64> = None;
+                        let mut last_top = None;
                         while let Some(msg) = read.next().await {
                             if let Ok(Message::Text(text)) = msg {
+                                let received_at = Instant::now();
                                 if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                                    if let Some(data) = json.get("data") {
-                                        let ts = json.get("ts").and_then(|t| t.as_u64()).unwrap_or(0);
-                                        let bids = data.get("b");
-                                        let asks = data.get("a");
-                                    
-                                        // Función interna para extraer el mejor precio
-                                        let get_top = |list: Option<&Value>| -> Option<(f64, f64)> {
-                                            let items = list?.as_array()?;
-                                            let top = items.get(0)?;
-                                            let p = top.get("p")?.as_str()?.parse::<f64>().ok()?;
-                                            let q = top.get("q")?.as_str()?.parse::<f64>().ok()?;
-                                            Some((p, q))
-                                        };
-                                    
-                                        if let (Some((bid, bid_sz)), Some((ask, ask_sz))) = (get_top(bids), get_top(asks)) {
-                                            // ESTA ES LA LÍNEA QUE FALTA O ESTÁ FALLANDO:
-                                            let _ = tx.send(BookUpdate {
-                                                symbol: safe_symbol.clone(),
-                                                exchange: Exchange::Extended,
-                                                bid,
-                                                ask,
-                                                bid_size: bid_sz,
-                                                ask_size: ask_sz,
-                                                timestamp: ts,
-                                            }).await; 
+                                    let Some(data) = json.get("data") else { continue };
+                                    let exchange_ts = json.get("ts").and_then(|t| t.as_u64());
+                                    let seq = json.get("seq").and_then(|s| s.as_u64());
+                                    let bids = Self::parse_levels(&data["b"]);
+                                    let asks = Self::parse_levels(&data["a"]);
+
+                                    if json.get("type").and_then(|t| t.as_str()) == Some("SNAPSHOT") {
+                                        book.reset(&bids, &asks);
+                                    } else {
+                                        let contiguous = matches!((last_seq, seq), (Some(last), Some(seq)) if seq == last + 1);
+                                        if !contiguous {
+                                            warn!("⚠️ Hueco de secuencia en Extended {} ({:?} -> {:?}): reconectando", safe_symbol, last_seq, seq);
+                                            break;
                                         }
+                                        // En los DELTA `q` es el cambio del nivel, no la cantidad final
+                                        book.apply_relative(&bids, &asks);
+                                        if book.is_crossed() {
+                                            warn!("⚠️ Libro de Extended {} cruzado: reconectando", safe_symbol);
+                                            break;
+                                        }
+                                    }
+                                    last_seq = seq;
+
+                                    if let Some((bid, bid_sz, ask, ask_sz)) = book.top() {
+                                        last_top = Some((bid, bid_sz, ask, ask_sz));
+                                        let (bids, asks) = book.depth(DEPTH_LEVELS);
+                                        let _ = tx.send(BookUpdate {
+                                            symbol: safe_symbol.clone(),
+                                            exchange: Exchange::Extended,
+                                            market_type: MarketType::Perp,
+                                            bid,
+                                            ask,
+                                            bid_size: bid_sz,
+                                            ask_size: ask_sz,
+                                            exchange_ts,
+                                            received_at,
+                                            integrity: BookIntegrity::Synced,
+                                            bids,
+                                            asks,
+                                        }).await;
                                     }
+                                } else {
+                                    let _ = event_tx.send(FeedEvent::ParseError).await;
                                 }
                             }
                         }
+                        let _ = event_tx.send(FeedEvent::Disconnected).await;
+                        // Hasta el SNAPSHOT de la próxima conexión el último top no es confiable
+                        if let Some((bid, bid_sz, ask, ask_sz)) = last_top {
+                            let _ = tx.send(BookUpdate {
+                                symbol: safe_symbol.clone(),
+                                exchange: Exchange::Extended,
+                                market_type: MarketType::Perp,
+                                bid,
+                                ask,
+                                bid_size: bid_sz,
+                                ask_size: ask_sz,
+                                exchange_ts: None,
+                                received_at: Instant::now(),
+                                integrity: BookIntegrity::Resyncing,
+                                bids: Vec::new(),
+                                asks: Vec::new(),
+                            }).await;
+                        }
                         warn!("⚠️ Connection lost for {}. Retrying...", safe_symbol);
                     }
                     tokio::time::sleep(Duration::from_secs(5)).await;
                 }
             });
         }
-        Ok(())
+        Ok(tasks)
     }
 
     fn get_receiver(&mut self) -> mpsc::Receiver<BookUpdate> {
         self.rx.take().expect("Receiver already taken")
     }
+
+    fn get_funding_receiver(&mut self) -> mpsc::Receiver<FundingUpdate> {
+        self.funding_rx.take().expect("Funding receiver already taken")
+    }
+
+    fn get_event_receiver(&mut self) -> mpsc::Receiver<FeedEvent> {
+        self.event_rx.take().expect("Event receiver already taken")
+    }
 }
\ No newline at end of file
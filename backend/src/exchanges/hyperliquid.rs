@@ -1,20 +1,47 @@
-use super::{BookUpdate, Exchange, ExchangeConnector};
+use super::orderbook::DEPTH_LEVELS;
+use super::{next_hour_ms, BookIntegrity, BookUpdate, Exchange, ExchangeConnector, FeedEvent, FundingUpdate, Level, MarketType, SymbolMap};
 use anyhow::Result;
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Instant;
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 pub struct HyperliquidConnector {
+    market_type: MarketType,
     tx: Option<mpsc::Sender<BookUpdate>>,
     rx: Option<mpsc::Receiver<BookUpdate>>,
+    funding_tx: Option<mpsc::Sender<FundingUpdate>>,
+    funding_rx: Option<mpsc::Receiver<FundingUpdate>>,
+    event_tx: Option<mpsc::Sender<FeedEvent>>,
+    event_rx: Option<mpsc::Receiver<FeedEvent>>,
 }
 
 impl HyperliquidConnector {
     pub fn new() -> Self {
+        Self::with_market(MarketType::Perp)
+    }
+
+    pub fn spot() -> Self {
+        Self::with_market(MarketType::Spot)
+    }
+
+    fn with_market(market_type: MarketType) -> Self {
         let (tx, rx) = mpsc::channel(1000);
-        Self { tx: Some(tx), rx: Some(rx) }
+        let (funding_tx, funding_rx) = mpsc::channel(1000);
+        let (event_tx, event_rx) = mpsc::channel(100);
+        Self {
+            market_type,
+            tx: Some(tx),
+            rx: Some(rx),
+            funding_tx: Some(funding_tx),
+            funding_rx: Some(funding_rx),
+            event_tx: Some(event_tx),
+            event_rx: Some(event_rx),
+        }
     }
 }
 
@@ -24,66 +51,139 @@ impl ExchangeConnector for HyperliquidConnector {
         Exchange::Hyperliquid
     }
 
-    async fn connect(&mut self, symbols: Vec<String>) -> Result<()> {
+    async fn connect(&mut self, symbols: SymbolMap) -> Result<JoinSet<()>> {
+        let mut tasks = JoinSet::new();
         let tx = self.tx.clone().unwrap();
-        
+        let funding_tx = self.funding_tx.clone().unwrap();
+        let event_tx = self.event_tx.clone().unwrap();
+        let market_type = self.market_type;
+
+        // coin de HL -> símbolo del sistema ("BTC" -> "BTC-USDT", "kPEPE" -> "PEPE-USDT", "@107" -> "HYPE-USDT")
+        let coin_to_symbol: HashMap<String, String> = symbols
+            .iter()
+            .map(|(id, coin)| (coin.clone(), id.to_string()))
+            .collect();
+
         // Hyperliquid usa UNA sola conexión para todo (Multiplexing)
-        tokio::spawn(async move {
+        tasks.spawn(async move {
             let url = "wss://api.hyperliquid.xyz/ws";
-            
+
             match connect_async(url).await {
                 Ok((ws_stream, _)) => {
-                    tracing::info!("✅ Connected to Hyperliquid Mainnet");
+                    tracing::info!("✅ Connected to Hyperliquid Mainnet ({:?})", market_type);
+                    let _ = event_tx.send(FeedEvent::Connected).await;
                     let (mut write, mut read) = ws_stream.split();
 
                     // 1. Suscribirse a cada símbolo
-                    for symbol in symbols {
-                        // FIX: Hyperliquid espera "BTC", no "BTC-USDT"
-                        let coin = symbol.split('-').next().unwrap_or(&symbol);
-                        
+                    for coin in coin_to_symbol.keys() {
                         let sub_msg = json!({
                             "type": "subscribe",
                             "subscription": {
                                 "type": "l2Book",
-                                "coin": coin 
+                                "coin": coin
                             }
                         });
-                        
+
                         if let Err(e) = write.send(Message::Text(sub_msg.to_string())).await {
                             tracing::error!("❌ Error enviando suscripción HL: {:?}", e);
                         }
+
+                        if market_type == MarketType::Spot { continue; }
+
+                        // Contexto del activo (funding, mark, OI)
+                        let ctx_msg = json!({
+                            "type": "subscribe",
+                            "subscription": {
+                                "type": "activeAssetCtx",
+                                "coin": coin
+                            }
+                        });
+
+                        if let Err(e) = write.send(Message::Text(ctx_msg.to_string())).await {
+                            tracing::error!("❌ Error enviando suscripción HL: {:?}", e);
+                        }
                     }
 
                     // 2. Loop de lectura
                     while let Some(msg) = read.next().await {
                         if let Ok(Message::Text(text)) = msg {
+                            let received_at = Instant::now();
                             if let Ok(json) = serde_json::from_str::<Value>(&text) {
-                                // "channel": "l2Book", "data": { "levels": [[p, s], ...], "coin": "BTC" }
-                                if let Some(data) = json.get("data") {
-                                    if let Some(coin) = data.get("coin").and_then(|c| c.as_str()) {
-                                        let levels = data.get("levels");
-                                        // Lógica simplificada de parsing...
-                                        // Debes mapear 'coin' de vuelta a 'BTC-USDT' para tu sistema
-                                        let symbol_map = format!("{}-USDT", coin); 
-                                        
-                                        if let Some(l) = levels {
-                                            // Extraer bid/ask del snapshot
-                                            // Enviar por tx.send(...)
+                                match json.get("channel").and_then(|c| c.as_str()) {
+                                    // "data": { "coin": "BTC", "ctx": { "funding": "0.0000125", ... } }
+                                    Some("activeAssetCtx") => {
+                                        let data = &json["data"];
+                                        if let (Some(symbol), Some(rate)) = (
+                                            data["coin"].as_str().and_then(|c| coin_to_symbol.get(c)),
+                                            data["ctx"]["funding"].as_str().and_then(|f| f.parse::<f64>().ok())
+                                        ) {
+                                            let now = chrono::Utc::now().timestamp_millis() as u64;
+                                            let _ = funding_tx.send(FundingUpdate {
+                                                symbol: symbol.clone(),
+                                                exchange: Exchange::Hyperliquid,
+                                                rate,
+                                                next_funding_time: next_hour_ms(now),
+                                                timestamp: now,
+                                            }).await;
+                                        }
+                                    }
+                                    // "data": { "coin": "BTC", "time": 1700000000000, "levels": [[{px, sz, n}, ...], [...]] }
+                                    Some("l2Book") => {
+                                        let data = &json["data"];
+                                        let Some(symbol) = data["coin"].as_str().and_then(|c| coin_to_symbol.get(c)) else { continue };
+                                        let get_levels = |side: &Value| -> Vec<Level> {
+                                            side.as_array()
+                                                .into_iter()
+                                                .flatten()
+                                                .take(DEPTH_LEVELS)
+                                                .filter_map(|l| Some((l["px"].as_str()?.parse::<f64>().ok()?, l["sz"].as_str()?.parse::<f64>().ok()?)))
+                                                .collect()
+                                        };
+                                        let (bids, asks) = (get_levels(&data["levels"][0]), get_levels(&data["levels"][1]));
+                                        if let (Some(&(bid, bid_size)), Some(&(ask, ask_size))) = (bids.first(), asks.first()) {
+                                            let _ = tx.send(BookUpdate {
+                                                symbol: symbol.clone(),
+                                                exchange: Exchange::Hyperliquid,
+                                                market_type,
+                                                bid,
+                                                ask,
+                                                bid_size,
+                                                ask_size,
+                                                exchange_ts: data["time"].as_u64(),
+                                                received_at,
+                                                // l2Book manda el libro completo en cada mensaje: no hay deltas que perder
+                                                integrity: BookIntegrity::Synced,
+                                                bids,
+                                                asks,
+                                            }).await;
                                         }
                                     }
+                                    _ => {}
                                 }
+                            } else {
+                                let _ = event_tx.send(FeedEvent::ParseError).await;
                             }
                         }
                     }
+                    tracing::warn!("⚠️ Hyperliquid connection closed ({:?})", market_type);
+                    let _ = event_tx.send(FeedEvent::Disconnected).await;
                 }
                 Err(e) => tracing::error!("❌ Hyperliquid Connect Error: {:?}", e),
             }
         });
 
-        Ok(())
+        Ok(tasks)
     }
 
     fn get_receiver(&mut self) -> mpsc::Receiver<BookUpdate> {
         self.rx.take().expect("Receiver already taken")
     }
-}
\ No newline at end of file
+
+    fn get_funding_receiver(&mut self) -> mpsc::Receiver<FundingUpdate> {
+        self.funding_rx.take().expect("Funding receiver already taken")
+    }
+
+    fn get_event_receiver(&mut self) -> mpsc::Receiver<FeedEvent> {
+        self.event_rx.take().expect("Event receiver already taken")
+    }
+}
@@ -3,9 +3,16 @@ pub mod binance;
 pub mod hyperliquid;
 pub mod bybit;
 pub mod extended;
+pub mod orderbook;
+pub mod symbols;
+
+pub use orderbook::{BookIntegrity, Level};
+pub use symbols::{SymbolId, SymbolMap};
 
 use async_trait::async_trait;
+use std::time::Instant;
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use serde::{Deserialize, Serialize};
 
 // 2. ACTUALIZAR EL ENUM
@@ -18,6 +25,8 @@ pub enum Exchange {
 }
 
 impl Exchange {
+    pub const ALL: [Exchange; 4] = [Exchange::Binance, Exchange::Hyperliquid, Exchange::Bybit, Exchange::Extended];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Exchange::Binance => "Binance",
@@ -26,22 +35,71 @@ impl Exchange {
             Exchange::Extended => "Extended",
         }
     }
+
+    // Cada cuántas horas liquida funding el perp (valor por defecto del venue)
+    pub fn funding_interval_hours(&self) -> f64 {
+        match self {
+            Exchange::Binance => 8.0,
+            Exchange::Bybit => 8.0,
+            Exchange::Hyperliquid => 1.0,
+            Exchange::Extended => 1.0,
+        }
+    }
+}
+
+// Spot o perpetuo: el mismo símbolo puede cotizar en ambos mercados del mismo venue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MarketType {
+    Spot,
+    Perp,
 }
 
 #[derive(Debug, Clone)]
 pub struct BookUpdate {
     pub symbol: String,
     pub exchange: Exchange,
+    pub market_type: MarketType,
     pub bid: f64,
     pub ask: f64,
     pub bid_size: f64,
     pub ask_size: f64,
+    pub exchange_ts: Option<u64>, // Hora del evento según el venue (ms epoch); None si no la informa
+    pub received_at: Instant,     // Llegada local (monotónico): la edad del libro se mide con esto
+    pub integrity: BookIntegrity, // Resyncing: el top no es confiable hasta el próximo snapshot
+    // Primeros niveles de cada lado (mejor primero), hasta DEPTH_LEVELS; vacío si el venue no los da
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FundingUpdate {
+    pub symbol: String,
+    pub exchange: Exchange,
+    pub rate: f64,                 // Tasa por intervalo (fracción, 0.0001 = 0.01%)
+    pub next_funding_time: u64,    // Milisegundos; 0 si el venue no lo informa
     pub timestamp: u64,
 }
 
+// Lo que reporta el conector sobre su conexión, aparte de los datos
+#[derive(Debug, Clone, Copy)]
+pub enum FeedEvent {
+    Connected,
+    Disconnected,
+    ParseError, // Mensaje que no se pudo interpretar
+}
+
 #[async_trait]
 pub trait ExchangeConnector {
     fn name(&self) -> Exchange;
-    async fn connect(&mut self, symbols: Vec<String>) -> anyhow::Result<()>;
+    // Las tareas del feed quedan en el JoinSet: quien lo tiene las espera o, al
+    // soltarlo, las aborta (el conector no deja nada corriendo por su cuenta)
+    async fn connect(&mut self, symbols: SymbolMap) -> anyhow::Result<JoinSet<()>>;
     fn get_receiver(&mut self) -> mpsc::Receiver<BookUpdate>;
+    fn get_funding_receiver(&mut self) -> mpsc::Receiver<FundingUpdate>;
+    fn get_event_receiver(&mut self) -> mpsc::Receiver<FeedEvent>;
+}
+
+// Próximo cambio de hora en ms (Hyperliquid y Extended liquidan cada hora en punto)
+pub fn next_hour_ms(now_ms: u64) -> u64 {
+    (now_ms / 3_600_000 + 1) * 3_600_000
 }
\ No newline at end of file
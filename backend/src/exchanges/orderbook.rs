@@ -0,0 +1,458 @@
+// src/exchanges/orderbook.rs
+//
+// Libro local armado con snapshot + deltas. Un delta perdido corrompe el libro
+// en silencio, así que cada venue valida la continuidad de su secuencia y ante
+// un hueco el libro queda en `Resyncing` hasta que llega un snapshot nuevo.
+
+use super::{BookUpdate, Exchange, MarketType, SymbolId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BookIntegrity {
+    #[default]
+    Synced,
+    Resyncing, // Hueco de secuencia o libro cruzado: esperando snapshot
+}
+
+// f64 ordenable para usar como clave del BTreeMap
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+pub type Level = (f64, f64); // (precio, cantidad)
+
+// Niveles por lado que viajan en cada BookUpdate (grabación de ticks)
+pub const DEPTH_LEVELS: usize = 10;
+
+// [["price","qty"], ...] (Binance y Bybit)
+pub fn parse_levels(value: &Value) -> Vec<Level> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|l| Some((l.get(0)?.as_str()?.parse().ok()?, l.get(1)?.as_str()?.parse().ok()?)))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct LocalBook {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+}
+
+impl LocalBook {
+    pub fn reset(&mut self, bids: &[Level], asks: &[Level]) {
+        self.bids.clear();
+        self.asks.clear();
+        self.apply(bids, asks);
+    }
+
+    // Cantidades absolutas: 0 borra el nivel
+    pub fn apply(&mut self, bids: &[Level], asks: &[Level]) {
+        for (side, levels) in [(&mut self.bids, bids), (&mut self.asks, asks)] {
+            for &(price, qty) in levels {
+                if qty <= 0.0 {
+                    side.remove(&Price(price));
+                } else {
+                    side.insert(Price(price), qty);
+                }
+            }
+        }
+    }
+
+    // Cantidades relativas (Extended): se suman al nivel existente
+    pub fn apply_relative(&mut self, bids: &[Level], asks: &[Level]) {
+        for (side, levels) in [(&mut self.bids, bids), (&mut self.asks, asks)] {
+            for &(price, change) in levels {
+                let qty = side.get(&Price(price)).copied().unwrap_or(0.0) + change;
+                // Tolerancia por redondeo de los decimales que se van sumando
+                if qty <= 1e-12 {
+                    side.remove(&Price(price));
+                } else {
+                    side.insert(Price(price), qty);
+                }
+            }
+        }
+    }
+
+    // (bid, bid_size, ask, ask_size)
+    pub fn top(&self) -> Option<(f64, f64, f64, f64)> {
+        let (bid, bid_size) = self.bids.iter().next_back()?;
+        let (ask, ask_size) = self.asks.iter().next()?;
+        Some((bid.0, *bid_size, ask.0, *ask_size))
+    }
+
+    // Los `n` mejores niveles de cada lado, mejor primero
+    pub fn depth(&self, n: usize) -> (Vec<Level>, Vec<Level>) {
+        let bids = self.bids.iter().rev().take(n).map(|(p, q)| (p.0, *q)).collect();
+        let asks = self.asks.iter().take(n).map(|(p, q)| (p.0, *q)).collect();
+        (bids, asks)
+    }
+
+    // Ningún venue publica un checksum de libro, así que esta es la validación de
+    // contenido que nos queda: un libro cruzado solo puede venir de un delta perdido.
+    pub fn is_crossed(&self) -> bool {
+        matches!(self.top(), Some((bid, _, ask, _)) if bid >= ask)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DepthDelta {
+    pub first_seq: u64,
+    pub last_seq: u64,
+    pub prev_seq: Option<u64>, // Solo Binance futuros (`pu`)
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookSnapshot {
+    pub seq: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+pub enum Continuity {
+    Contiguous,
+    Stale, // Ya incluido en lo que tenemos: se descarta
+    Gap,
+}
+
+// Regla de cada venue para saber si `delta` sigue a la última secuencia aplicada
+// (los deltas viejos ya se descartaron antes de llegar acá)
+pub type ContinuityRule = fn(last_seq: u64, delta: &DepthDelta) -> bool;
+
+// Binance futuros: `pu` de cada evento es el `u` del anterior
+pub fn binance_futures_rule(last_seq: u64, delta: &DepthDelta) -> bool {
+    delta.prev_seq == Some(last_seq)
+}
+
+// Binance spot: `U` de cada evento es el `u` del anterior + 1
+pub fn binance_spot_rule(last_seq: u64, delta: &DepthDelta) -> bool {
+    delta.first_seq <= last_seq + 1
+}
+
+// Bybit: `u` sube de a uno por mensaje
+pub fn bybit_rule(last_seq: u64, delta: &DepthDelta) -> bool {
+    delta.first_seq == last_seq + 1
+}
+
+pub enum DeltaOutcome {
+    Applied,
+    Buffered,     // Esperando un snapshot ya pedido
+    NeedSnapshot, // Hay que pedir snapshot (el delta queda en el buffer)
+}
+
+// Libro con su estado de sincronización. Mientras espera snapshot guarda los
+// deltas para aplicar los que sean posteriores al snapshot.
+pub struct SyncedBook {
+    book: LocalBook,
+    rule: ContinuityRule,
+    last_seq: u64,
+    bridged: bool,                    // Ya se aplicó el primer delta después del snapshot
+    pending: Option<Vec<DepthDelta>>, // Some = Resyncing
+    snapshot_requested: bool,
+    snapshot_failures: u32, // Snapshots seguidos que fallaron o no empalmaron
+}
+
+// Tope del buffer mientras el snapshot no llega
+const MAX_PENDING_DELTAS: usize = 1000;
+
+// Un snapshot REST colgado deja el libro en Resyncing para siempre
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Espera antes de re-pedir snapshot tras un fallo: se duplica hasta el tope. Sin
+// esto un 429 se convierte en una ráfaga de pedidos de profundidad 1000 y el venue
+// banea la IP (418 en Binance).
+const SNAPSHOT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+const SNAPSHOT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+// Cliente para los snapshots REST de los conectores
+pub fn snapshot_client() -> reqwest::Client {
+    reqwest::Client::builder().timeout(SNAPSHOT_TIMEOUT).build().unwrap_or_default()
+}
+
+impl SyncedBook {
+    pub fn new(rule: ContinuityRule) -> Self {
+        Self { book: LocalBook::default(), rule, last_seq: 0, bridged: false, pending: Some(Vec::new()), snapshot_requested: false, snapshot_failures: 0 }
+    }
+
+    pub fn integrity(&self) -> BookIntegrity {
+        if self.is_synced() { BookIntegrity::Synced } else { BookIntegrity::Resyncing }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    // Top del libro para el aggregator. Mientras re-sincroniza se manda el último
+    // top conocido marcado como `Resyncing` para que nadie opere contra él.
+    pub fn book_update(&self, symbol: &SymbolId, exchange: Exchange, market_type: MarketType, exchange_ts: Option<u64>, received_at: Instant) -> Option<BookUpdate> {
+        let (bid, bid_size, ask, ask_size) = self.book.top()?;
+        let (bids, asks) = self.book.depth(DEPTH_LEVELS);
+        Some(BookUpdate {
+            symbol: symbol.to_string(),
+            exchange,
+            market_type,
+            bid,
+            ask,
+            bid_size,
+            ask_size,
+            exchange_ts,
+            received_at,
+            integrity: self.integrity(),
+            bids,
+            asks,
+        })
+    }
+
+    fn continuity(&self, delta: &DepthDelta) -> Continuity {
+        if delta.last_seq <= self.last_seq {
+            return Continuity::Stale;
+        }
+        // El primer delta tiene que cubrir la secuencia siguiente al snapshot;
+        // el id del snapshot no siempre cae en el borde de un evento
+        let contiguous = if self.bridged {
+            (self.rule)(self.last_seq, delta)
+        } else {
+            delta.first_seq <= self.last_seq + 1
+        };
+        if contiguous { Continuity::Contiguous } else { Continuity::Gap }
+    }
+
+    // Aplica un delta ya validado; false si dejó el libro cruzado
+    fn apply(&mut self, delta: &DepthDelta) -> bool {
+        self.book.apply(&delta.bids, &delta.asks);
+        self.last_seq = delta.last_seq;
+        self.bridged = true;
+        !self.book.is_crossed()
+    }
+
+    pub fn on_delta(&mut self, delta: DepthDelta) -> DeltaOutcome {
+        if let Some(pending) = &mut self.pending {
+            if pending.len() >= MAX_PENDING_DELTAS {
+                pending.remove(0);
+            }
+            pending.push(delta);
+            if self.snapshot_requested {
+                return DeltaOutcome::Buffered;
+            }
+            self.snapshot_requested = true;
+            return DeltaOutcome::NeedSnapshot;
+        }
+        match self.continuity(&delta) {
+            Continuity::Stale => DeltaOutcome::Applied,
+            Continuity::Contiguous => {
+                if self.apply(&delta) {
+                    DeltaOutcome::Applied
+                } else {
+                    self.resync(Vec::new());
+                    DeltaOutcome::NeedSnapshot
+                }
+            }
+            Continuity::Gap => {
+                self.resync(vec![delta]);
+                DeltaOutcome::NeedSnapshot
+            }
+        }
+    }
+
+    // El llamador queda a cargo de pedir el snapshot
+    fn resync(&mut self, pending: Vec<DepthDelta>) {
+        self.pending = Some(pending);
+        self.snapshot_requested = true;
+    }
+
+    // Marca el libro como corrupto sin esperar un hueco (p. ej. reconexión)
+    pub fn invalidate(&mut self) {
+        if self.pending.is_none() {
+            self.pending = Some(Vec::new());
+            self.snapshot_requested = false;
+        }
+    }
+
+    // El snapshot pedido no llegó: el próximo delta lo vuelve a pedir
+    pub fn snapshot_failed(&mut self) {
+        self.snapshot_requested = false;
+        self.snapshot_failures += 1;
+    }
+
+    // Cuánto esperar antes de pedir el próximo snapshot (cero si el anterior salió bien)
+    pub fn snapshot_delay(&self) -> Duration {
+        match self.snapshot_failures {
+            0 => Duration::ZERO,
+            n => SNAPSHOT_BACKOFF_BASE.saturating_mul(1 << (n - 1).min(16)).min(SNAPSHOT_BACKOFF_MAX),
+        }
+    }
+
+    // Devuelve false si el buffer no empalma con el snapshot (hay que pedir otro)
+    pub fn on_snapshot(&mut self, snapshot: BookSnapshot) -> bool {
+        let pending = self.pending.take().unwrap_or_default();
+        self.snapshot_requested = false;
+        self.book.reset(&snapshot.bids, &snapshot.asks);
+        self.last_seq = snapshot.seq;
+        self.bridged = false;
+
+        for delta in pending {
+            match self.continuity(&delta) {
+                Continuity::Stale => {}
+                Continuity::Contiguous => {
+                    if !self.apply(&delta) {
+                        return self.snapshot_rejected();
+                    }
+                }
+                Continuity::Gap => return self.snapshot_rejected(),
+            }
+        }
+        if self.book.is_crossed() {
+            return self.snapshot_rejected();
+        }
+        self.snapshot_failures = 0;
+        true
+    }
+
+    // Cuenta como fallo para el backoff: el próximo snapshot sale con demora
+    fn snapshot_rejected(&mut self) -> bool {
+        self.resync(Vec::new());
+        self.snapshot_failures += 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(first_seq: u64, last_seq: u64, prev_seq: Option<u64>, bids: &[Level], asks: &[Level]) -> DepthDelta {
+        DepthDelta { first_seq, last_seq, prev_seq, bids: bids.to_vec(), asks: asks.to_vec() }
+    }
+
+    fn snapshot(seq: u64) -> BookSnapshot {
+        BookSnapshot { seq, bids: vec![(100.0, 1.0)], asks: vec![(101.0, 1.0)] }
+    }
+
+    // Libro recién sincronizado con el snapshot `seq` y sin deltas en el buffer
+    fn synced(rule: ContinuityRule, seq: u64) -> SyncedBook {
+        let mut book = SyncedBook::new(rule);
+        assert!(book.on_snapshot(snapshot(seq)));
+        book
+    }
+
+    #[test]
+    fn futures_bridges_snapshot_and_follows_pu() {
+        let mut book = SyncedBook::new(binance_futures_rule);
+        assert!(matches!(book.on_delta(delta(90, 95, Some(89), &[(99.0, 1.0)], &[])), DeltaOutcome::NeedSnapshot));
+        assert!(matches!(book.on_delta(delta(96, 105, Some(95), &[(100.5, 2.0)], &[])), DeltaOutcome::Buffered));
+        assert!(matches!(book.on_delta(delta(106, 110, Some(105), &[], &[(101.0, 3.0)])), DeltaOutcome::Buffered));
+
+        // El snapshot cae en medio del segundo evento: el primero es viejo y el segundo empalma
+        assert!(book.on_snapshot(snapshot(100)));
+        assert_eq!(book.integrity(), BookIntegrity::Synced);
+        assert_eq!(book.book.top(), Some((100.5, 2.0, 101.0, 3.0)));
+        assert_eq!(book.last_seq, 110);
+
+        assert!(matches!(book.on_delta(delta(111, 115, Some(110), &[], &[])), DeltaOutcome::Applied));
+        // `pu` que no es el último `u`: se perdió un evento
+        assert!(matches!(book.on_delta(delta(120, 125, Some(118), &[], &[])), DeltaOutcome::NeedSnapshot));
+        assert_eq!(book.integrity(), BookIntegrity::Resyncing);
+    }
+
+    #[test]
+    fn spot_accepts_overlap_and_detects_gap() {
+        let mut book = synced(binance_spot_rule, 100);
+        assert!(matches!(book.on_delta(delta(95, 105, None, &[], &[])), DeltaOutcome::Applied));
+        assert!(matches!(book.on_delta(delta(104, 108, None, &[], &[])), DeltaOutcome::Applied));
+        assert!(matches!(book.on_delta(delta(109, 112, None, &[], &[])), DeltaOutcome::Applied));
+        assert_eq!(book.last_seq, 112);
+
+        assert!(matches!(book.on_delta(delta(114, 116, None, &[], &[])), DeltaOutcome::NeedSnapshot));
+        assert!(!book.is_synced());
+        // Mientras el snapshot está pedido los deltas se guardan
+        assert!(matches!(book.on_delta(delta(117, 118, None, &[], &[])), DeltaOutcome::Buffered));
+    }
+
+    #[test]
+    fn first_delta_must_cover_next_seq() {
+        let mut book = synced(binance_futures_rule, 100);
+        assert!(matches!(book.on_delta(delta(102, 105, Some(101), &[], &[])), DeltaOutcome::NeedSnapshot));
+    }
+
+    #[test]
+    fn stale_deltas_are_dropped() {
+        let mut book = synced(bybit_rule, 100);
+        assert!(matches!(book.on_delta(delta(101, 101, None, &[(100.0, 5.0)], &[])), DeltaOutcome::Applied));
+        assert!(matches!(book.on_delta(delta(99, 101, None, &[(100.0, 9.0)], &[])), DeltaOutcome::Applied));
+        assert!(matches!(book.on_delta(delta(100, 100, None, &[(100.0, 0.0)], &[])), DeltaOutcome::Applied));
+        assert_eq!(book.book.top(), Some((100.0, 5.0, 101.0, 1.0)));
+        assert_eq!(book.last_seq, 101);
+        assert!(book.is_synced());
+    }
+
+    #[test]
+    fn bybit_gap_needs_snapshot() {
+        let mut book = synced(bybit_rule, 100);
+        assert!(matches!(book.on_delta(delta(101, 101, None, &[], &[])), DeltaOutcome::Applied));
+        assert!(matches!(book.on_delta(delta(103, 103, None, &[], &[])), DeltaOutcome::NeedSnapshot));
+        assert_eq!(book.integrity(), BookIntegrity::Resyncing);
+    }
+
+    #[test]
+    fn overflowed_buffer_does_not_line_up() {
+        let mut book = SyncedBook::new(bybit_rule);
+        for seq in 101..=(101 + MAX_PENDING_DELTAS as u64) {
+            book.on_delta(delta(seq, seq, None, &[], &[]));
+        }
+        // El delta 101 se descartó para hacer lugar: el buffer arranca en 102
+        assert_eq!(book.pending.as_ref().map(Vec::len), Some(MAX_PENDING_DELTAS));
+        assert_eq!(book.snapshot_delay(), Duration::ZERO);
+        assert!(!book.on_snapshot(snapshot(100)));
+        assert_eq!(book.integrity(), BookIntegrity::Resyncing);
+        assert!(book.snapshot_delay() > Duration::ZERO);
+
+        // Un snapshot posterior al buffer perdido vuelve a sincronizar y resetea el backoff
+        assert!(book.on_snapshot(snapshot(200)));
+        assert_eq!(book.snapshot_delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn crossed_book_after_apply_resyncs() {
+        let mut book = synced(bybit_rule, 100);
+        assert!(matches!(book.on_delta(delta(101, 101, None, &[(101.5, 1.0)], &[])), DeltaOutcome::NeedSnapshot));
+        assert_eq!(book.integrity(), BookIntegrity::Resyncing);
+
+        // Un snapshot cruzado tampoco se acepta
+        let crossed = BookSnapshot { seq: 200, bids: vec![(102.0, 1.0)], asks: vec![(101.0, 1.0)] };
+        assert!(!book.on_snapshot(crossed));
+        assert_eq!(book.integrity(), BookIntegrity::Resyncing);
+    }
+
+    #[test]
+    fn snapshot_backoff_doubles_up_to_cap() {
+        let mut book = SyncedBook::new(bybit_rule);
+        let mut delays = Vec::new();
+        for _ in 0..10 {
+            book.snapshot_failed();
+            delays.push(book.snapshot_delay());
+        }
+        assert_eq!(delays[0], SNAPSHOT_BACKOFF_BASE);
+        assert_eq!(delays[1], SNAPSHOT_BACKOFF_BASE * 2);
+        assert_eq!(*delays.last().unwrap(), SNAPSHOT_BACKOFF_MAX);
+    }
+}
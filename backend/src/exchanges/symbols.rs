@@ -0,0 +1,107 @@
+// src/exchanges/symbols.rs
+//
+// Única fuente de verdad para traducir símbolos entre el sistema y cada venue.
+// Los conectores no arman ni parsean strings: reciben un `SymbolMap` ya resuelto.
+
+use super::{Exchange, MarketType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+// Par del sistema en unidades reales: base "PEPE" (no "1000PEPE"), quote "USDT"
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SymbolId {
+    pub base: String,
+    pub quote: String,
+}
+
+impl SymbolId {
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self { base: base.to_uppercase(), quote: quote.to_uppercase() }
+    }
+
+    // "ETH-BTC" -> SymbolId { base: "ETH", quote: "BTC" }
+    pub fn parse(symbol: &str) -> Option<Self> {
+        let (base, quote) = symbol.split_once('-')?;
+        (!base.is_empty() && !quote.is_empty()).then(|| Self::new(base, quote))
+    }
+}
+
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.base, self.quote)
+    }
+}
+
+// Moneda en la que el venue cotiza lo que el sistema llama USDT (None = USDT de verdad).
+// El símbolo es el mismo; el aggregator convierte el precio con el FX vivo.
+pub fn stable_quote(exchange: Exchange) -> Option<&'static str> {
+    match exchange {
+        Exchange::Hyperliquid => Some("USDC"),
+        Exchange::Extended => Some("USD"),
+        Exchange::Binance | Exchange::Bybit => None,
+    }
+}
+
+// Quote tal como lo lista el venue
+pub fn native_quote(exchange: Exchange, quote: &str) -> &str {
+    match stable_quote(exchange) {
+        Some(native) if quote == "USDT" => native,
+        _ => quote,
+    }
+}
+
+pub fn canonical_quote(exchange: Exchange, native: &str) -> &str {
+    match stable_quote(exchange) {
+        Some(stable) if native == stable => "USDT",
+        _ => native,
+    }
+}
+
+// Nombre nativo por convención del venue, para cuando no hay metadata (sin red ni caché).
+// Hyperliquid spot no tiene convención: los pares se llaman "@107".
+pub fn default_native(exchange: Exchange, market_type: MarketType, id: &SymbolId) -> Option<String> {
+    let quote = native_quote(exchange, &id.quote);
+    match (exchange, market_type) {
+        (Exchange::Binance, _) | (Exchange::Bybit, _) => Some(format!("{}{}", id.base, quote)),
+        (Exchange::Hyperliquid, MarketType::Perp) => (quote == "USDC").then(|| id.base.clone()),
+        (Exchange::Hyperliquid, MarketType::Spot) => None,
+        (Exchange::Extended, MarketType::Perp) => Some(format!("{}-{}", id.base, quote)),
+        (Exchange::Extended, MarketType::Spot) => None,
+    }
+}
+
+// Mapeo bidireccional de un venue/mercado, solo con los símbolos pedidos
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    to_native: HashMap<SymbolId, String>,
+    to_symbol: HashMap<String, SymbolId>,
+}
+
+impl SymbolMap {
+    pub fn insert(&mut self, id: SymbolId, native: String) {
+        self.to_symbol.insert(native.clone(), id.clone());
+        self.to_native.insert(id, native);
+    }
+
+    // Lo que no está en el mapa se descarta en lugar de adivinarlo
+    pub fn symbol(&self, native: &str) -> Option<&SymbolId> {
+        self.to_symbol.get(native)
+    }
+
+    pub fn natives(&self) -> impl Iterator<Item = &String> {
+        self.to_symbol.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SymbolId, &String)> {
+        self.to_native.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.to_native.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_native.is_empty()
+    }
+}
@@ -1,9 +1,12 @@
 // src/execution.rs
 
-use crate::exchanges::Exchange;
+use crate::exchanges::{Exchange, MarketType};
+use crate::instruments::InstrumentRegistry;
+use anyhow::anyhow;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Side {
     Buy,
     Sell,
@@ -18,20 +21,41 @@ pub trait Executor {
         amount: f64, 
         price: Option<f64>
     ) -> anyhow::Result<String>;
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> anyhow::Result<()>;
     
     async fn get_balance(&self, asset: &str) -> anyhow::Result<f64>;
 }
 
-// Estructura vacía por ahora para que compile si decidimos usarla luego
-pub struct MockExecutor;
+// Ejecutor simulado: valida la orden contra la metadata del venue como lo haría el exchange
+pub struct MockExecutor {
+    exchange: Exchange,
+    instruments: InstrumentRegistry,
+}
+
+impl MockExecutor {
+    pub fn new(exchange: Exchange, instruments: InstrumentRegistry) -> Self {
+        Self { exchange, instruments }
+    }
+}
 
 #[async_trait]
 impl Executor for MockExecutor {
-    async fn place_order(&self, _symbol: &str, _side: Side, _amount: f64, _price: Option<f64>) -> anyhow::Result<String> {
+    async fn place_order(&self, symbol: &str, side: Side, amount: f64, price: Option<f64>) -> anyhow::Result<String> {
+        let qty = self.instruments.round_qty(self.exchange, MarketType::Perp, symbol, amount);
+        let price = price.map(|p| self.instruments.round_price(self.exchange, MarketType::Perp, symbol, p, side == Side::Sell));
+        // Sin precio (market) validamos el nominal con lo que haya: el mínimo de cantidad sí aplica
+        if !self.instruments.meets_minimums(self.exchange, MarketType::Perp, symbol, qty, price.unwrap_or(f64::MAX)) {
+            return Err(anyhow!("orden bajo el mínimo de {:?} {}: qty {} @ {:?}", self.exchange, symbol, qty, price));
+        }
         Ok("mock_order_id".to_string())
     }
+
+    async fn cancel_order(&self, _symbol: &str, _order_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
     
     async fn get_balance(&self, _asset: &str) -> anyhow::Result<f64> {
         Ok(1000.0)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,134 @@
+// src/fees/live.rs
+//
+// Comisiones reales de la cuenta desde los endpoints privados de cada exchange.
+// Solo se consultan los exchanges con credenciales en el entorno (.env).
+
+use super::{ExchangeFees, FeeConfig};
+use crate::clock::ClockSync;
+use crate::exchanges::Exchange;
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+
+const BINANCE_COMMISSION_URL: &str = "https://fapi.binance.com/fapi/v1/commissionRate";
+const BYBIT_FEE_RATE_URL: &str = "https://api.bybit.com/v5/account/fee-rate";
+const HYPERLIQUID_INFO_URL: &str = "https://api.hyperliquid.xyz/info";
+const EXTENDED_FEES_URL: &str = "https://api.starknet.extended.exchange/api/v1/user/fees";
+// Las comisiones son por cuenta; usamos un símbolo representativo donde el endpoint lo exige
+const REFERENCE_SYMBOL: &str = "BTCUSDT";
+const RECV_WINDOW_MS: u64 = 5000;
+// Un endpoint colgado no debe frenar el refresco de los demás exchanges
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC acepta cualquier largo de clave");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// "0.0002" (fracción) -> 0.02 (%)
+fn pct(value: &Value) -> Option<f64> {
+    value.as_str()?.parse::<f64>().ok().map(|v| v * 100.0)
+}
+
+fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+async fn fetch_binance(client: &reqwest::Client, clock: &ClockSync, key: &str, secret: &str) -> Result<ExchangeFees> {
+    let ts = clock.server_now(Exchange::Binance);
+    let query = format!("symbol={}&recvWindow={}&timestamp={}", REFERENCE_SYMBOL, RECV_WINDOW_MS, ts);
+    let url = format!("{}?{}&signature={}", BINANCE_COMMISSION_URL, query, sign(secret, &query));
+    // {"symbol":"BTCUSDT","makerCommissionRate":"0.0002","takerCommissionRate":"0.0004"}
+    let json: Value = client.get(url).header("X-MBX-APIKEY", key).send().await?.json().await?;
+    Ok(ExchangeFees {
+        maker: pct(&json["makerCommissionRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+        taker: pct(&json["takerCommissionRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+    })
+}
+
+async fn fetch_bybit(client: &reqwest::Client, clock: &ClockSync, key: &str, secret: &str) -> Result<ExchangeFees> {
+    let ts = clock.server_now(Exchange::Bybit).to_string();
+    let query = format!("category=linear&symbol={}", REFERENCE_SYMBOL);
+    let signature = sign(secret, &format!("{}{}{}{}", ts, key, RECV_WINDOW_MS, query));
+    // {"result":{"list":[{"symbol":"BTCUSDT","takerFeeRate":"0.0006","makerFeeRate":"0.0001"}]}}
+    let json: Value = client
+        .get(format!("{}?{}", BYBIT_FEE_RATE_URL, query))
+        .header("X-BAPI-API-KEY", key)
+        .header("X-BAPI-TIMESTAMP", ts)
+        .header("X-BAPI-RECV-WINDOW", RECV_WINDOW_MS.to_string())
+        .header("X-BAPI-SIGN", signature)
+        .send()
+        .await?
+        .json()
+        .await?;
+    let entry = &json["result"]["list"][0];
+    Ok(ExchangeFees {
+        maker: pct(&entry["makerFeeRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+        taker: pct(&entry["takerFeeRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+    })
+}
+
+async fn fetch_hyperliquid(client: &reqwest::Client, address: &str) -> Result<ExchangeFees> {
+    // Público por dirección: {"userCrossRate":"0.00035","userAddRate":"0.0001",...}
+    let json: Value = client
+        .post(HYPERLIQUID_INFO_URL)
+        .json(&json!({ "type": "userFees", "user": address }))
+        .send()
+        .await?
+        .json()
+        .await?;
+    Ok(ExchangeFees {
+        maker: pct(&json["userAddRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+        taker: pct(&json["userCrossRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+    })
+}
+
+async fn fetch_extended(client: &reqwest::Client, key: &str) -> Result<ExchangeFees> {
+    // {"data":[{"market":"BTC-USD","makerFeeRate":"0.00000","takerFeeRate":"0.00025"}]}
+    let json: Value = client
+        .get(format!("{}?market=BTC-USD", EXTENDED_FEES_URL))
+        .header("X-Api-Key", key)
+        .header("User-Agent", "Mozilla/5.0...")
+        .send()
+        .await?
+        .json()
+        .await?;
+    let entry = &json["data"][0];
+    Ok(ExchangeFees {
+        maker: pct(&entry["makerFeeRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+        taker: pct(&entry["takerFeeRate"]).ok_or_else(|| anyhow!("respuesta inesperada: {}", json))?,
+    })
+}
+
+// Consulta cada exchange con credenciales y actualiza el modelo compartido.
+// Los requests firmados usan la hora del exchange para no caer fuera del recvWindow.
+pub async fn refresh(config: &FeeConfig, clock: &ClockSync) {
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default();
+
+    let mut results: Vec<(Exchange, Result<ExchangeFees>)> = Vec::new();
+    if let (Some(key), Some(secret)) = (env("BINANCE_API_KEY"), env("BINANCE_API_SECRET")) {
+        results.push((Exchange::Binance, fetch_binance(&client, clock, &key, &secret).await));
+    }
+    if let (Some(key), Some(secret)) = (env("BYBIT_API_KEY"), env("BYBIT_API_SECRET")) {
+        results.push((Exchange::Bybit, fetch_bybit(&client, clock, &key, &secret).await));
+    }
+    if let Some(address) = env("HYPERLIQUID_ADDRESS") {
+        results.push((Exchange::Hyperliquid, fetch_hyperliquid(&client, &address).await));
+    }
+    if let Some(key) = env("EXTENDED_API_KEY") {
+        results.push((Exchange::Extended, fetch_extended(&client, &key).await));
+    }
+
+    for (exchange, result) in results {
+        match result {
+            Ok(fees) => {
+                tracing::info!("💸 Comisiones reales {:?}: maker {:.4}% / taker {:.4}%", exchange, fees.maker, fees.taker);
+                config.set_live_fees(exchange, fees);
+            }
+            Err(e) => tracing::warn!("⚠️ No se pudieron leer comisiones de {:?}: {:?}", exchange, e),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// src/fees/mod.rs
+
+pub mod live;
+
+use crate::exchanges::{Exchange, MarketType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+const DEFAULT_FEE_CONFIG_PATH: &str = "fees.json";
+const VOLUME_WINDOW_DAYS: i64 = 30;
+
+// Fees en % (0.05 = 0.05%). Un maker negativo es un rebate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExchangeFees {
+    pub maker: f64,
+    pub taker: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub name: String,
+    pub min_volume_30d_usd: f64,
+    pub maker: f64,
+    pub taker: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExchangeFeeSchedule {
+    pub perp_tiers: Vec<FeeTier>,
+    pub spot_tiers: Vec<FeeTier>,
+    // Descuento por pagar fees con el token del exchange (BNB en Binance), en %
+    pub token_discount_pct: f64,
+    pub symbol_overrides: HashMap<String, ExchangeFees>,
+    // Volumen previo al arranque (el tracker solo ve lo que opera este proceso)
+    pub initial_volume_30d_usd: f64,
+}
+
+impl ExchangeFeeSchedule {
+    fn single(perp: ExchangeFees, spot: Option<ExchangeFees>) -> Self {
+        let tier = |f: ExchangeFees| vec![FeeTier { name: "Base".into(), min_volume_30d_usd: 0.0, maker: f.maker, taker: f.taker }];
+        Self {
+            perp_tiers: tier(perp),
+            spot_tiers: spot.map(tier).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    // Tier más alto alcanzado con el volumen dado
+    fn tier(&self, market_type: MarketType, volume_30d_usd: f64) -> Option<&FeeTier> {
+        let tiers = match market_type {
+            MarketType::Perp => &self.perp_tiers,
+            MarketType::Spot => &self.spot_tiers,
+        };
+        tiers
+            .iter()
+            .filter(|t| t.min_volume_30d_usd <= volume_30d_usd)
+            .max_by(|a, b| a.min_volume_30d_usd.total_cmp(&b.min_volume_30d_usd))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeStatus {
+    pub exchange: Exchange,
+    pub tier: String,
+    pub volume_30d_usd: f64,
+    pub maker: f64,
+    pub taker: f64,
+    pub live: bool,
+}
+
+struct FeeModel {
+    schedules: HashMap<Exchange, ExchangeFeeSchedule>,
+    // Comisiones reales de la cuenta (endpoint del exchange); pisan a los tiers
+    live: HashMap<Exchange, ExchangeFees>,
+    // Volumen operado por día (YYYY-MM-DD) para la ventana de 30 días
+    daily_volume: HashMap<Exchange, BTreeMap<String, f64>>,
+}
+
+impl FeeModel {
+    fn volume_30d(&self, exchange: Exchange) -> f64 {
+        let initial = self.schedules.get(&exchange).map(|s| s.initial_volume_30d_usd).unwrap_or(0.0);
+        let tracked: f64 = self.daily_volume.get(&exchange).map(|days| days.values().sum()).unwrap_or(0.0);
+        initial + tracked
+    }
+
+    fn fees(&self, exchange: Exchange, market_type: MarketType, symbol: Option<&str>) -> ExchangeFees {
+        let Some(schedule) = self.schedules.get(&exchange) else {
+            return ExchangeFees { maker: 0.02, taker: 0.06 };
+        };
+
+        // Prioridad: override por símbolo > comisión real de la cuenta > tier por volumen
+        let base = symbol
+            .and_then(|s| schedule.symbol_overrides.get(s).copied())
+            .or_else(|| match market_type {
+                MarketType::Perp => self.live.get(&exchange).copied(),
+                MarketType::Spot => None,
+            })
+            .or_else(|| {
+                schedule
+                    .tier(market_type, self.volume_30d(exchange))
+                    .map(|t| ExchangeFees { maker: t.maker, taker: t.taker })
+            })
+            .unwrap_or(match market_type {
+                MarketType::Perp => ExchangeFees { maker: 0.02, taker: 0.06 },
+                MarketType::Spot => ExchangeFees { maker: 0.10, taker: 0.10 },
+            });
+
+        // El descuento por token no aplica a los rebates
+        let discount = 1.0 - schedule.token_discount_pct / 100.0;
+        ExchangeFees {
+            maker: if base.maker > 0.0 { base.maker * discount } else { base.maker },
+            taker: base.taker * discount,
+        }
+    }
+}
+
+// Handle compartido: todas las estrategias leen el mismo modelo, así un cambio
+// de tier o una comisión real se ve en todas a la vez.
+#[derive(Clone)]
+pub struct FeeConfig {
+    model: Arc<RwLock<FeeModel>>,
+}
+
+impl FeeConfig {
+    pub fn default() -> Self {
+        let mut schedules = HashMap::new();
+        // Fees conservadores (Taker)
+        schedules.insert(Exchange::Binance, ExchangeFeeSchedule {
+            perp_tiers: vec![
+                FeeTier { name: "VIP0".into(), min_volume_30d_usd: 0.0, maker: 0.02, taker: 0.05 },
+                FeeTier { name: "VIP1".into(), min_volume_30d_usd: 15_000_000.0, maker: 0.016, taker: 0.04 },
+                FeeTier { name: "VIP2".into(), min_volume_30d_usd: 50_000_000.0, maker: 0.014, taker: 0.035 },
+                FeeTier { name: "VIP3".into(), min_volume_30d_usd: 100_000_000.0, maker: 0.012, taker: 0.032 },
+            ],
+            spot_tiers: vec![
+                FeeTier { name: "VIP0".into(), min_volume_30d_usd: 0.0, maker: 0.10, taker: 0.10 },
+                FeeTier { name: "VIP1".into(), min_volume_30d_usd: 1_000_000.0, maker: 0.09, taker: 0.10 },
+            ],
+            ..Default::default()
+        });
+        schedules.insert(Exchange::Hyperliquid, ExchangeFeeSchedule::single(
+            ExchangeFees { maker: 0.00, taker: 0.025 },
+            Some(ExchangeFees { maker: 0.04, taker: 0.07 }),
+        ));
+        schedules.insert(Exchange::Bybit, ExchangeFeeSchedule {
+            perp_tiers: vec![
+                FeeTier { name: "VIP0".into(), min_volume_30d_usd: 0.0, maker: 0.02, taker: 0.06 },
+                FeeTier { name: "VIP1".into(), min_volume_30d_usd: 10_000_000.0, maker: 0.018, taker: 0.04 },
+                FeeTier { name: "VIP2".into(), min_volume_30d_usd: 25_000_000.0, maker: 0.016, taker: 0.0375 },
+            ],
+            spot_tiers: vec![
+                FeeTier { name: "VIP0".into(), min_volume_30d_usd: 0.0, maker: 0.10, taker: 0.10 },
+            ],
+            ..Default::default()
+        });
+        // Extended no tiene mercado spot
+        schedules.insert(Exchange::Extended, ExchangeFeeSchedule::single(
+            ExchangeFees { maker: 0.05, taker: 0.05 },
+            None,
+        ));
+        Self::from_schedules(schedules)
+    }
+
+    fn from_schedules(schedules: HashMap<Exchange, ExchangeFeeSchedule>) -> Self {
+        Self {
+            model: Arc::new(RwLock::new(FeeModel {
+                schedules,
+                live: HashMap::new(),
+                daily_volume: HashMap::new(),
+            })),
+        }
+    }
+
+    // Carga `FEE_CONFIG_PATH` (o fees.json). Los exchanges que no estén en el
+    // archivo conservan el schedule por defecto.
+    pub fn load() -> Self {
+        let path = std::env::var("FEE_CONFIG_PATH").unwrap_or_else(|_| DEFAULT_FEE_CONFIG_PATH.to_string());
+        let config = Self::default();
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            tracing::info!("💸 Sin {}: usando fees por defecto", path);
+            return config;
+        };
+        match serde_json::from_str::<HashMap<Exchange, ExchangeFeeSchedule>>(&raw) {
+            Ok(schedules) => {
+                tracing::info!("💸 Fees cargados de {} ({} exchanges)", path, schedules.len());
+                config.model.write().unwrap().schedules.extend(schedules);
+            }
+            Err(e) => tracing::error!("❌ {} inválido, usando fees por defecto: {}", path, e),
+        }
+        config
+    }
+
+    pub fn get_taker_fee(&self, exchange: Exchange) -> f64 {
+        self.get_fees(exchange, MarketType::Perp, None).taker
+    }
+
+    pub fn get_market_taker_fee(&self, exchange: Exchange, market_type: MarketType) -> f64 {
+        self.get_fees(exchange, market_type, None).taker
+    }
+
+    pub fn get_fees(&self, exchange: Exchange, market_type: MarketType, symbol: Option<&str>) -> ExchangeFees {
+        self.model.read().unwrap().fees(exchange, market_type, symbol)
+    }
+
+    pub fn set_live_fees(&self, exchange: Exchange, fees: ExchangeFees) {
+        self.model.write().unwrap().live.insert(exchange, fees);
+    }
+
+    // Suma volumen operado hoy y descarta días fuera de la ventana
+    pub fn record_volume(&self, exchange: Exchange, notional_usd: f64) {
+        let today = chrono::Utc::now().date_naive();
+        let cutoff = (today - chrono::Duration::days(VOLUME_WINDOW_DAYS)).format("%Y-%m-%d").to_string();
+
+        let mut model = self.model.write().unwrap();
+        let days = model.daily_volume.entry(exchange).or_default();
+        *days.entry(today.format("%Y-%m-%d").to_string()).or_insert(0.0) += notional_usd;
+        days.retain(|day, _| *day > cutoff);
+    }
+
+    // Volumen diario por venue, para que el tier sobreviva a un reinicio
+    pub fn daily_volume(&self) -> HashMap<Exchange, BTreeMap<String, f64>> {
+        self.model.read().unwrap().daily_volume.clone()
+    }
+
+    pub fn restore_daily_volume(&self, daily_volume: HashMap<Exchange, BTreeMap<String, f64>>) {
+        let cutoff = (chrono::Utc::now().date_naive() - chrono::Duration::days(VOLUME_WINDOW_DAYS)).format("%Y-%m-%d").to_string();
+        let mut model = self.model.write().unwrap();
+        for (exchange, mut days) in daily_volume {
+            days.retain(|day, _| *day > cutoff);
+            model.daily_volume.insert(exchange, days);
+        }
+    }
+
+    pub fn status(&self) -> Vec<FeeStatus> {
+        let model = self.model.read().unwrap();
+        let mut status: Vec<FeeStatus> = model
+            .schedules
+            .iter()
+            .map(|(exchange, schedule)| {
+                let volume = model.volume_30d(*exchange);
+                let fees = model.fees(*exchange, MarketType::Perp, None);
+                FeeStatus {
+                    exchange: *exchange,
+                    tier: schedule.tier(MarketType::Perp, volume).map(|t| t.name.clone()).unwrap_or_default(),
+                    volume_30d_usd: volume,
+                    maker: fees.maker,
+                    taker: fees.taker,
+                    live: model.live.contains_key(exchange),
+                }
+            })
+            .collect();
+        status.sort_by_key(|s| s.exchange.as_str());
+        status
+    }
+}
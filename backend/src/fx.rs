@@ -0,0 +1,51 @@
+// src/fx.rs
+//
+// Tipos de cambio entre stablecoins para llevar todos los libros a USDT.
+// Binance no lista USD, así que ambos pares salen de Kraken.
+
+use crate::aggregator::{FxRate, PriceAggregator};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::time::Duration;
+
+const KRAKEN_TICKER_URL: &str = "https://api.kraken.com/0/public/Ticker";
+const FX_POLL_SECS: u64 = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// (par de Kraken, moneda, true si el par cotiza la moneda en USDT; false si es USDT en la moneda)
+const FX_PAIRS: [(&str, &str, bool); 2] = [
+    ("USDCUSDT", "USDC", true),
+    ("USDTUSD", "USD", false),
+];
+
+// {"error":[],"result":{"USDTZUSD":{"a":["1.00010","1","1.000"],"b":["1.00000","1","1.000"],...}}}
+// La clave del resultado no siempre coincide con el par pedido, así que tomamos la primera.
+async fn fetch_mid(client: &reqwest::Client, pair: &str) -> Result<f64> {
+    let json: Value = client.get(KRAKEN_TICKER_URL).query(&[("pair", pair)]).send().await?.json().await?;
+    let ticker = json["result"]
+        .as_object()
+        .and_then(|r| r.values().next())
+        .ok_or_else(|| anyhow!("respuesta inesperada de Kraken: {}", json["error"]))?;
+    let side = |key: &str| ticker[key][0].as_str().and_then(|p| p.parse::<f64>().ok());
+    match (side("b"), side("a")) {
+        (Some(bid), Some(ask)) if bid > 0.0 && ask > 0.0 => Ok((bid + ask) / 2.0),
+        _ => Err(anyhow!("ticker sin bid/ask para {}", pair)),
+    }
+}
+
+pub async fn run_feed(aggregator: PriceAggregator) {
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default();
+    let mut interval = tokio::time::interval(Duration::from_secs(FX_POLL_SECS));
+    loop {
+        interval.tick().await;
+        for (pair, ccy, quoted_in_usdt) in FX_PAIRS {
+            match fetch_mid(&client, pair).await {
+                Ok(mid) => {
+                    let rate = if quoted_in_usdt { mid } else { 1.0 / mid };
+                    aggregator.update_fx(ccy, FxRate { rate, timestamp: chrono::Utc::now().timestamp_millis() as u64 });
+                }
+                Err(e) => tracing::warn!("⚠️ FX {} no disponible: {:?}", pair, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+// src/health.rs
+//
+// Salud de cada feed (exchange + mercado): estado de la conexión, reconexiones,
+// errores de parseo y, por símbolo, último update y mensajes por segundo.
+// Un conector que se cuelga sin cortar el socket solo se ve acá: sus símbolos
+// dejan de actualizarse y quedan marcados como stale.
+
+use crate::exchanges::{Exchange, FeedEvent, MarketType, SymbolMap};
+use crate::metrics;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Sin updates por más de esto el símbolo se considera colgado
+const STALE_AFTER_MS: u64 = 10_000;
+// Ventana para medir la tasa de mensajes
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+const MONITOR_INTERVAL_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConnectionState {
+    Connecting,   // Todavía no conectó nunca
+    Connected,
+    Disconnected,
+}
+
+struct SymbolStats {
+    last_update: Option<Instant>,
+    messages: u64,
+    window_start: Instant,
+    window_count: u64,
+    rate: f64, // Mensajes/s de la última ventana cerrada
+}
+
+impl SymbolStats {
+    fn new() -> Self {
+        Self { last_update: None, messages: 0, window_start: Instant::now(), window_count: 0, rate: 0.0 }
+    }
+
+    // Si la ventana ya venció sin cerrarse (feed colgado) la tasa real es la parcial
+    fn current_rate(&self) -> f64 {
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            self.window_count as f64 / elapsed.as_secs_f64()
+        } else {
+            self.rate
+        }
+    }
+}
+
+struct FeedState {
+    open_connections: u32, // Extended abre un socket por mercado
+    ever_connected: bool,
+    reconnects: u64,
+    parse_errors: u64,
+    symbols: HashMap<String, SymbolStats>,
+}
+
+impl FeedState {
+    fn new() -> Self {
+        Self { open_connections: 0, ever_connected: false, reconnects: 0, parse_errors: 0, symbols: HashMap::new() }
+    }
+
+    fn state(&self) -> ConnectionState {
+        if self.open_connections > 0 {
+            ConnectionState::Connected
+        } else if self.ever_connected {
+            ConnectionState::Disconnected
+        } else {
+            ConnectionState::Connecting
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolHealth {
+    pub symbol: String,
+    pub age_ms: Option<u64>, // None = nunca llegó un update
+    pub messages: u64,
+    pub messages_per_sec: f64,
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedHealthStatus {
+    pub exchange: Exchange,
+    pub market_type: MarketType,
+    pub state: ConnectionState,
+    pub reconnects: u64,
+    pub parse_errors: u64,
+    pub messages_per_sec: f64,
+    pub stale_symbols: Vec<String>,
+    pub symbols: Vec<SymbolHealth>,
+}
+
+impl FeedHealthStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.state == ConnectionState::Connected && (self.symbols.is_empty() || self.stale_symbols.len() < self.symbols.len())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub feeds: Vec<FeedHealthStatus>,
+}
+
+#[derive(Clone, Default)]
+pub struct FeedHealth {
+    feeds: Arc<DashMap<(Exchange, MarketType), FeedState>>,
+}
+
+impl FeedHealth {
+    // Símbolos que el feed debería publicar: los que nunca llegan también son stale
+    pub fn register(&self, exchange: Exchange, market_type: MarketType, symbols: &SymbolMap) {
+        let mut feed = self.feeds.entry((exchange, market_type)).or_insert_with(FeedState::new);
+        for (id, _) in symbols.iter() {
+            feed.symbols.entry(id.to_string()).or_insert_with(SymbolStats::new);
+        }
+    }
+
+    pub fn on_event(&self, exchange: Exchange, market_type: MarketType, event: FeedEvent) {
+        let mut feed = self.feeds.entry((exchange, market_type)).or_insert_with(FeedState::new);
+        match event {
+            FeedEvent::Connected => {
+                feed.open_connections += 1;
+                feed.ever_connected = true;
+            }
+            FeedEvent::Disconnected => {
+                feed.open_connections = feed.open_connections.saturating_sub(1);
+                feed.reconnects += 1;
+                metrics::feed_reconnect(exchange, market_type);
+            }
+            FeedEvent::ParseError => feed.parse_errors += 1,
+        }
+    }
+
+    pub fn on_book(&self, exchange: Exchange, market_type: MarketType, symbol: &str) {
+        let mut feed = self.feeds.entry((exchange, market_type)).or_insert_with(FeedState::new);
+        let stats = feed.symbols.entry(symbol.to_string()).or_insert_with(SymbolStats::new);
+        let now = Instant::now();
+        stats.last_update = Some(now);
+        stats.messages += 1;
+        stats.window_count += 1;
+        let elapsed = now.duration_since(stats.window_start);
+        if elapsed >= RATE_WINDOW {
+            stats.rate = stats.window_count as f64 / elapsed.as_secs_f64();
+            stats.window_start = now;
+            stats.window_count = 0;
+        }
+    }
+
+    pub fn report(&self) -> HealthReport {
+        let mut feeds: Vec<FeedHealthStatus> = self
+            .feeds
+            .iter()
+            .map(|entry| {
+                let (exchange, market_type) = *entry.key();
+                let feed = entry.value();
+                let mut symbols: Vec<SymbolHealth> = feed
+                    .symbols
+                    .iter()
+                    .map(|(symbol, stats)| {
+                        let age_ms = stats.last_update.map(|t| t.elapsed().as_millis() as u64);
+                        SymbolHealth {
+                            symbol: symbol.clone(),
+                            age_ms,
+                            messages: stats.messages,
+                            messages_per_sec: stats.current_rate(),
+                            stale: age_ms.is_none_or(|age| age > STALE_AFTER_MS),
+                        }
+                    })
+                    .collect();
+                symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+                FeedHealthStatus {
+                    exchange,
+                    market_type,
+                    state: feed.state(),
+                    reconnects: feed.reconnects,
+                    parse_errors: feed.parse_errors,
+                    messages_per_sec: symbols.iter().map(|s| s.messages_per_sec).sum(),
+                    stale_symbols: symbols.iter().filter(|s| s.stale).map(|s| s.symbol.clone()).collect(),
+                    symbols,
+                }
+            })
+            .collect();
+        feeds.sort_by_key(|f| (f.exchange.as_str(), f.market_type == MarketType::Perp));
+        HealthReport { healthy: feeds.iter().all(|f| f.is_healthy()), feeds }
+    }
+}
+
+// Avisa por log cuando un feed se cae o un símbolo se cuelga (solo en la transición)
+pub async fn run_monitor(health: FeedHealth) {
+    let mut interval = tokio::time::interval(Duration::from_secs(MONITOR_INTERVAL_SECS));
+    let mut stale: HashSet<(Exchange, MarketType, String)> = HashSet::new();
+    let mut down: HashSet<(Exchange, MarketType)> = HashSet::new();
+    loop {
+        interval.tick().await;
+        for feed in health.report().feeds {
+            let key = (feed.exchange, feed.market_type);
+            if feed.state == ConnectionState::Disconnected {
+                if down.insert(key) {
+                    tracing::warn!("🩺 Feed {:?} {:?} desconectado ({} reconexiones)", feed.exchange, feed.market_type, feed.reconnects);
+                }
+            } else if feed.state == ConnectionState::Connected && down.remove(&key) {
+                tracing::info!("🩺 Feed {:?} {:?} conectado de nuevo", feed.exchange, feed.market_type);
+            }
+
+            for symbol in feed.symbols {
+                let key = (feed.exchange, feed.market_type, symbol.symbol);
+                if symbol.stale {
+                    if stale.insert(key.clone()) && symbol.age_ms.is_some() {
+                        tracing::warn!("🩺 {} en {:?} {:?} sin updates hace {} ms", key.2, key.0, key.1, symbol.age_ms.unwrap_or(0));
+                    }
+                } else if stale.remove(&key) {
+                    tracing::info!("🩺 {} en {:?} {:?} volvió a actualizarse", key.2, key.0, key.1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// src/instruments/fetch.rs
+//
+// Endpoints públicos de metadata de cada venue.
+
+use super::{split_multiplier, Instrument};
+use crate::exchanges::{symbols::canonical_quote, Exchange, MarketType, SymbolId};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const BINANCE_FUTURES_INFO_URL: &str = "https://fapi.binance.com/fapi/v1/exchangeInfo";
+const BINANCE_SPOT_INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo?permissions=SPOT";
+const BYBIT_INSTRUMENTS_URL: &str = "https://api.bybit.com/v5/market/instruments-info";
+const HYPERLIQUID_INFO_URL: &str = "https://api.hyperliquid.xyz/info";
+const EXTENDED_MARKETS_URL: &str = "https://api.starknet.extended.exchange/api/v1/info/markets";
+// Hyperliquid: mínimo de $10 por orden; precio con hasta 6 (perp) u 8 (spot) decimales menos szDecimals
+const HYPERLIQUID_MIN_NOTIONAL: f64 = 10.0;
+const HYPERLIQUID_PERP_PRICE_DECIMALS: i32 = 6;
+const HYPERLIQUID_SPOT_PRICE_DECIMALS: i32 = 8;
+// Un venue colgado no puede frenar el arranque: sin respuesta a tiempo se usa el cache
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn num(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        _ => value.as_f64(),
+    }
+}
+
+// `base` y `quote` como los lista el venue ("1000PEPE", "USDC")
+#[allow(clippy::too_many_arguments)]
+fn instrument(
+    exchange: Exchange, market_type: MarketType, base: &str, quote: &str, native_symbol: &str,
+    tick_size: f64, lot_size: f64, min_qty: f64, min_notional: f64,
+) -> Instrument {
+    let (real_base, multiplier) = split_multiplier(exchange, base);
+    Instrument {
+        exchange,
+        market_type,
+        symbol: SymbolId::new(&real_base, canonical_quote(exchange, quote)).to_string(),
+        native_symbol: native_symbol.to_string(),
+        tick_size,
+        lot_size,
+        min_qty,
+        min_notional,
+        multiplier,
+    }
+}
+
+pub async fn fetch_all() -> Vec<(Exchange, MarketType, Result<Vec<Instrument>>)> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    // En paralelo: el peor caso es un timeout, no la suma de todos
+    let (binance_perp, binance_spot, bybit_perp, bybit_spot, hyperliquid_perp, hyperliquid_spot, extended) = tokio::join!(
+        fetch_binance(&client, MarketType::Perp),
+        fetch_binance(&client, MarketType::Spot),
+        fetch_bybit(&client, MarketType::Perp),
+        fetch_bybit(&client, MarketType::Spot),
+        fetch_hyperliquid_perp(&client),
+        fetch_hyperliquid_spot(&client),
+        fetch_extended(&client),
+    );
+    vec![
+        (Exchange::Binance, MarketType::Perp, binance_perp),
+        (Exchange::Binance, MarketType::Spot, binance_spot),
+        (Exchange::Bybit, MarketType::Perp, bybit_perp),
+        (Exchange::Bybit, MarketType::Spot, bybit_spot),
+        (Exchange::Hyperliquid, MarketType::Perp, hyperliquid_perp),
+        (Exchange::Hyperliquid, MarketType::Spot, hyperliquid_spot),
+        (Exchange::Extended, MarketType::Perp, extended),
+    ]
+}
+
+// {"symbols":[{"symbol":"1000PEPEUSDT","baseAsset":"1000PEPE","quoteAsset":"USDT","status":"TRADING",
+//   "filters":[{"filterType":"PRICE_FILTER","tickSize":"0.0000001"},{"filterType":"LOT_SIZE","stepSize":"1","minQty":"1"},...]}]}
+async fn fetch_binance(client: &reqwest::Client, market_type: MarketType) -> Result<Vec<Instrument>> {
+    let url = match market_type {
+        MarketType::Perp => BINANCE_FUTURES_INFO_URL,
+        MarketType::Spot => BINANCE_SPOT_INFO_URL,
+    };
+    let json: Value = client.get(url).send().await?.json().await?;
+    let symbols = json["symbols"].as_array().ok_or_else(|| anyhow!("respuesta inesperada de Binance"))?;
+
+    let mut out = Vec::new();
+    for s in symbols {
+        if s["status"].as_str() != Some("TRADING") { continue; }
+        if market_type == MarketType::Perp && s["contractType"].as_str() != Some("PERPETUAL") { continue; }
+        let (Some(native), Some(base), Some(quote)) = (s["symbol"].as_str(), s["baseAsset"].as_str(), s["quoteAsset"].as_str()) else { continue };
+
+        let filter = |kind: &str| s["filters"].as_array().and_then(|f| f.iter().find(|f| f["filterType"].as_str() == Some(kind)));
+        let tick = filter("PRICE_FILTER").and_then(|f| num(&f["tickSize"])).unwrap_or(0.0);
+        let lot = filter("LOT_SIZE").and_then(|f| num(&f["stepSize"])).unwrap_or(0.0);
+        let min_qty = filter("LOT_SIZE").and_then(|f| num(&f["minQty"])).unwrap_or(0.0);
+        // Futuros: MIN_NOTIONAL.notional; spot: NOTIONAL.minNotional
+        let min_notional = filter("MIN_NOTIONAL").and_then(|f| num(&f["notional"]).or_else(|| num(&f["minNotional"])))
+            .or_else(|| filter("NOTIONAL").and_then(|f| num(&f["minNotional"])))
+            .unwrap_or(0.0);
+
+        out.push(instrument(Exchange::Binance, market_type, base, quote, native, tick, lot, min_qty, min_notional));
+    }
+    Ok(out)
+}
+
+// {"result":{"list":[{"symbol":"1000PEPEUSDT","baseCoin":"1000PEPE","quoteCoin":"USDT","status":"Trading",
+//   "priceFilter":{"tickSize":"0.0000001"},"lotSizeFilter":{"qtyStep":"100","minOrderQty":"100","minNotionalValue":"5"}}],
+//   "nextPageCursor":""}}
+async fn fetch_bybit(client: &reqwest::Client, market_type: MarketType) -> Result<Vec<Instrument>> {
+    let category = match market_type {
+        MarketType::Perp => "linear",
+        MarketType::Spot => "spot",
+    };
+    let mut out = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let json: Value = client
+            .get(BYBIT_INSTRUMENTS_URL)
+            .query(&[("category", category), ("limit", "1000"), ("cursor", cursor.as_str())])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let list = json["result"]["list"].as_array().ok_or_else(|| anyhow!("respuesta inesperada de Bybit: {}", json["retMsg"]))?;
+
+        for s in list {
+            if s["status"].as_str() != Some("Trading") { continue; }
+            if market_type == MarketType::Perp && s["contractType"].as_str() != Some("LinearPerpetual") { continue; }
+            let (Some(native), Some(base), Some(quote)) = (s["symbol"].as_str(), s["baseCoin"].as_str(), s["quoteCoin"].as_str()) else { continue };
+
+            let lot_filter = &s["lotSizeFilter"];
+            let tick = num(&s["priceFilter"]["tickSize"]).unwrap_or(0.0);
+            let (lot, min_notional) = match market_type {
+                MarketType::Perp => (num(&lot_filter["qtyStep"]), num(&lot_filter["minNotionalValue"])),
+                MarketType::Spot => (num(&lot_filter["basePrecision"]), num(&lot_filter["minOrderAmt"])),
+            };
+            let min_qty = num(&lot_filter["minOrderQty"]).unwrap_or(0.0);
+
+            out.push(instrument(Exchange::Bybit, market_type, base, quote, native, tick, lot.unwrap_or(0.0), min_qty, min_notional.unwrap_or(0.0)));
+        }
+
+        cursor = json["result"]["nextPageCursor"].as_str().unwrap_or_default().to_string();
+        if cursor.is_empty() {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+// {"universe":[{"name":"kPEPE","szDecimals":0,"maxLeverage":10},...]}
+async fn fetch_hyperliquid_perp(client: &reqwest::Client) -> Result<Vec<Instrument>> {
+    let json: Value = client.post(HYPERLIQUID_INFO_URL).json(&json!({ "type": "meta" })).send().await?.json().await?;
+    let universe = json["universe"].as_array().ok_or_else(|| anyhow!("respuesta inesperada de Hyperliquid"))?;
+
+    Ok(universe
+        .iter()
+        .filter(|a| !a["isDelisted"].as_bool().unwrap_or(false))
+        .filter_map(|a| {
+            let name = a["name"].as_str()?;
+            let sz_decimals = a["szDecimals"].as_i64()? as i32;
+            let lot = 10f64.powi(-sz_decimals);
+            // Además rige el límite de 5 cifras significativas, que depende del precio
+            let tick = 10f64.powi(-(HYPERLIQUID_PERP_PRICE_DECIMALS - sz_decimals).max(0));
+            Some(instrument(Exchange::Hyperliquid, MarketType::Perp, name, "USDC", name, tick, lot, lot, HYPERLIQUID_MIN_NOTIONAL))
+        })
+        .collect())
+}
+
+// {"tokens":[{"name":"USDC","index":0,"szDecimals":8},...],"universe":[{"name":"@107","tokens":[150,0]},...]}
+async fn fetch_hyperliquid_spot(client: &reqwest::Client) -> Result<Vec<Instrument>> {
+    let json: Value = client.post(HYPERLIQUID_INFO_URL).json(&json!({ "type": "spotMeta" })).send().await?.json().await?;
+    let (Some(tokens), Some(universe)) = (json["tokens"].as_array(), json["universe"].as_array()) else {
+        return Err(anyhow!("respuesta inesperada de Hyperliquid spot"));
+    };
+    let token = |index: u64| tokens.iter().find(|t| t["index"].as_u64() == Some(index));
+
+    let mut out = Vec::new();
+    for pair in universe {
+        let (Some(name), Some(pair_tokens)) = (pair["name"].as_str(), pair["tokens"].as_array()) else { continue };
+        // Solo pares contra USDC (token 0), igual que el conector
+        if pair_tokens.get(1).and_then(|q| q.as_u64()) != Some(0) { continue; }
+        let Some(base) = pair_tokens.first().and_then(|b| b.as_u64()).and_then(token) else { continue };
+        let (Some(base_name), Some(sz_decimals)) = (base["name"].as_str(), base["szDecimals"].as_i64()) else { continue };
+
+        let sz_decimals = sz_decimals as i32;
+        let lot = 10f64.powi(-sz_decimals);
+        let tick = 10f64.powi(-(HYPERLIQUID_SPOT_PRICE_DECIMALS - sz_decimals).max(0));
+        // El nombre del par no dice nada ("@107"); base y quote salen de los tokens
+        let mut inst = instrument(Exchange::Hyperliquid, MarketType::Spot, base_name, "USDC", name, tick, lot, lot, HYPERLIQUID_MIN_NOTIONAL);
+        inst.symbol = SymbolId::new(base_name, canonical_quote(Exchange::Hyperliquid, "USDC")).to_string();
+        inst.multiplier = 1.0;
+        out.push(inst);
+    }
+    Ok(out)
+}
+
+// {"status":"OK","data":[{"name":"1000PEPE-USD","assetName":"1000PEPE","active":true,
+//   "tradingConfig":{"minOrderSize":"1","minOrderSizeChange":"1","minPriceChange":"0.000001"}}]}
+async fn fetch_extended(client: &reqwest::Client) -> Result<Vec<Instrument>> {
+    let json: Value = client
+        .get(EXTENDED_MARKETS_URL)
+        .header("User-Agent", "Mozilla/5.0...")
+        .send()
+        .await?
+        .json()
+        .await?;
+    let markets = json["data"].as_array().ok_or_else(|| anyhow!("respuesta inesperada de Extended"))?;
+
+    Ok(markets
+        .iter()
+        .filter(|m| m["active"].as_bool().unwrap_or(true))
+        .filter_map(|m| {
+            let name = m["name"].as_str()?;
+            let (base, quote) = name.split_once('-')?;
+            let config = &m["tradingConfig"];
+            let tick = num(&config["minPriceChange"]).unwrap_or(0.0);
+            let lot = num(&config["minOrderSizeChange"]).unwrap_or(0.0);
+            let min_qty = num(&config["minOrderSize"]).unwrap_or(0.0);
+            Some(instrument(Exchange::Extended, MarketType::Perp, base, quote, name, tick, lot, min_qty, 0.0))
+        })
+        .collect())
+}
@@ -0,0 +1,277 @@
+// src/instruments/mod.rs
+//
+// Metadata de cada instrumento por venue: tick, lote, mínimos y multiplicador
+// de contrato. Los venues listan algunos memes en lotes de 1000 (Binance
+// `1000PEPEUSDT`, Hyperliquid `kPEPE`); acá se normaliza todo a una unidad.
+
+pub mod fetch;
+
+use crate::exchanges::{symbols::default_native, BookUpdate, Exchange, MarketType, SymbolId, SymbolMap};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+const DEFAULT_CACHE_PATH: &str = "instruments.json";
+// Tolerancia para que 0.3 / 0.1 no redondee a 2 lotes
+const STEP_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instrument {
+    pub exchange: Exchange,
+    pub market_type: MarketType,
+    pub symbol: String,        // Símbolo del sistema, en unidades reales ("PEPE-USDT")
+    pub native_symbol: String, // Como lo lista el venue ("1000PEPEUSDT", "kPEPE", "@107")
+    // Los siguientes en unidades del contrato nativo
+    pub tick_size: f64,
+    pub lot_size: f64,
+    pub min_qty: f64,
+    pub min_notional: f64,
+    pub multiplier: f64,       // Unidades reales por unidad del contrato (1000 en 1000PEPE)
+}
+
+impl Instrument {
+    // Tick y lote expresados en unidades reales
+    pub fn tick_size_real(&self) -> f64 {
+        self.tick_size / self.multiplier
+    }
+
+    pub fn lot_size_real(&self) -> f64 {
+        self.lot_size * self.multiplier
+    }
+
+    pub fn min_qty_real(&self) -> f64 {
+        self.min_qty * self.multiplier
+    }
+}
+
+// "1000PEPE" -> ("PEPE", 1000), "kBONK" -> ("BONK", 1000) en Hyperliquid, "1MBABYDOGE" -> ("BABYDOGE", 1e6)
+pub fn split_multiplier(exchange: Exchange, base: &str) -> (String, f64) {
+    if exchange == Exchange::Hyperliquid {
+        if let Some(rest) = base.strip_prefix('k') {
+            if rest.chars().next().is_some_and(|c| c.is_ascii_uppercase()) {
+                return (rest.to_string(), 1000.0);
+            }
+        }
+    }
+    if let Some(rest) = base.strip_prefix("1M") {
+        if rest.chars().next().is_some_and(|c| c.is_ascii_uppercase()) {
+            return (rest.to_string(), 1_000_000.0);
+        }
+    }
+    let digits: String = base.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let rest = &base[digits.len()..];
+    // Solo potencias de 10 desde 100: "1INCH" es un token, no un lote
+    if let Ok(mult) = digits.parse::<u64>() {
+        if mult >= 100 && mult.to_string().trim_end_matches('0') == "1" && !rest.is_empty() {
+            return (rest.to_string(), mult as f64);
+        }
+    }
+    (base.to_string(), 1.0)
+}
+
+fn floor_to_step(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step + STEP_EPSILON).floor() * step
+}
+
+fn round_to_step(value: f64, step: f64, up: bool) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    let steps = value / step;
+    let steps = if up { (steps - STEP_EPSILON).ceil() } else { (steps + STEP_EPSILON).floor() };
+    steps * step
+}
+
+type InstrumentKey = (Exchange, MarketType, String);
+
+// REST de cada venue; si alguno falla se usa lo último guardado en disco.
+// También devuelve cuántos venues respondieron en vivo.
+async fn fetch_with_cache() -> (Vec<Instrument>, usize) {
+    let path = std::env::var("INSTRUMENTS_CACHE_PATH").unwrap_or_else(|_| DEFAULT_CACHE_PATH.to_string());
+    let cached: Vec<Instrument> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default();
+
+    let mut instruments = Vec::new();
+    let mut fetched = 0;
+    for (exchange, market_type, result) in fetch::fetch_all().await {
+        match result {
+            Ok(list) => {
+                tracing::info!("📐 {} instrumentos de {:?} {:?}", list.len(), exchange, market_type);
+                instruments.extend(list);
+                fetched += 1;
+            }
+            Err(e) => {
+                let fallback: Vec<Instrument> = cached
+                    .iter()
+                    .filter(|i| i.exchange == exchange && i.market_type == market_type)
+                    .cloned()
+                    .collect();
+                tracing::warn!("⚠️ Metadata de {:?} {:?} no disponible ({:?}), usando caché ({} instrumentos)",
+                    exchange, market_type, e, fallback.len());
+                instruments.extend(fallback);
+            }
+        }
+    }
+
+    match serde_json::to_string(&instruments) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                tracing::warn!("⚠️ No se pudo guardar {}: {:?}", path, e);
+            }
+        }
+        Err(e) => tracing::warn!("⚠️ No se pudo serializar instrumentos: {:?}", e),
+    }
+
+    (instruments, fetched)
+}
+
+#[derive(Default)]
+struct Tables {
+    by_symbol: HashMap<InstrumentKey, Instrument>,
+    // Símbolos que el venue lista con más de un nombre nativo (p. ej. PEPEUSDT y 1000PEPEUSDT)
+    ambiguous: HashMap<InstrumentKey, Vec<String>>,
+    // Venues con metadata cargada; en el resto se usa la convención de nombres
+    venues: HashSet<(Exchange, MarketType)>,
+}
+
+impl Tables {
+    fn build(instruments: Vec<Instrument>) -> Self {
+        let mut by_symbol: HashMap<InstrumentKey, Instrument> = HashMap::new();
+        let mut ambiguous: HashMap<InstrumentKey, Vec<String>> = HashMap::new();
+        let mut venues = HashSet::new();
+        for inst in instruments {
+            venues.insert((inst.exchange, inst.market_type));
+            let key = (inst.exchange, inst.market_type, inst.symbol.clone());
+            // Si el venue lista las dos variantes, nos quedamos con la de multiplicador 1
+            match by_symbol.get(&key) {
+                Some(existing) => {
+                    ambiguous.entry(key.clone()).or_insert_with(|| vec![existing.native_symbol.clone()]).push(inst.native_symbol.clone());
+                    if inst.multiplier < existing.multiplier {
+                        by_symbol.insert(key, inst);
+                    }
+                }
+                None => { by_symbol.insert(key, inst); }
+            }
+        }
+        Self { by_symbol, ambiguous, venues }
+    }
+}
+
+// Se comparte por clon barato. `refresh` reemplaza las tablas enteras de una vez:
+// quien esté leyendo termina con las viejas.
+#[derive(Clone, Default)]
+pub struct InstrumentRegistry {
+    tables: Arc<RwLock<Arc<Tables>>>,
+}
+
+impl InstrumentRegistry {
+    pub fn from_instruments(instruments: Vec<Instrument>) -> Self {
+        Self { tables: Arc::new(RwLock::new(Arc::new(Tables::build(instruments)))) }
+    }
+
+    fn tables(&self) -> Arc<Tables> {
+        self.tables.read().unwrap().clone()
+    }
+
+    // REST de cada venue; si alguno falla se usa lo último guardado en disco
+    pub async fn load() -> Self {
+        Self::from_instruments(fetch_with_cache().await.0)
+    }
+
+    // Vuelve a pedir la metadata (cambios de tick o lote, listados nuevos). Los
+    // conectores mapean sus símbolos al arrancar: un listado nuevo recién se
+    // suscribe en el próximo reinicio. Si ningún venue responde se conservan las
+    // tablas vigentes y se devuelve el error.
+    pub async fn refresh(&self) -> Result<usize, String> {
+        let (instruments, fetched) = fetch_with_cache().await;
+        if fetched == 0 {
+            return Err("ningún venue respondió: se mantiene la metadata anterior".to_string());
+        }
+        let tables = Tables::build(instruments);
+        let count = tables.by_symbol.len();
+        *self.tables.write().unwrap() = Arc::new(tables);
+        tracing::info!("📐 Metadata de instrumentos recargada: {} instrumentos", count);
+        Ok(count)
+    }
+
+    // Consulta sin clonar el instrumento (camino caliente: cada libro pasa por `normalize`)
+    fn with<R>(&self, exchange: Exchange, market_type: MarketType, symbol: &str, f: impl FnOnce(&Instrument) -> R) -> Option<R> {
+        self.tables.read().unwrap().by_symbol.get(&(exchange, market_type, symbol.to_string())).map(f)
+    }
+
+    // Mapeo para el conector de un venue. Lo que no se puede mapear se reporta acá,
+    // una vez al arrancar, en lugar de descubrirlo parseando mensajes.
+    pub fn symbol_map(&self, exchange: Exchange, market_type: MarketType, symbols: &[String]) -> SymbolMap {
+        let tables = self.tables();
+        let has_metadata = tables.venues.contains(&(exchange, market_type));
+        let mut map = SymbolMap::default();
+        let mut unmapped = Vec::new();
+
+        for symbol in symbols {
+            let Some(id) = SymbolId::parse(symbol) else {
+                tracing::warn!("⚠️ Símbolo inválido {:?}: se esperaba BASE-QUOTE", symbol);
+                continue;
+            };
+            let key = (exchange, market_type, id.to_string());
+            let native = if has_metadata {
+                tables.by_symbol.get(&key).map(|i| i.native_symbol.clone())
+            } else {
+                default_native(exchange, market_type, &id)
+            };
+            let Some(native) = native else {
+                unmapped.push(symbol.as_str());
+                continue;
+            };
+            if let Some(natives) = tables.ambiguous.get(&key) {
+                tracing::warn!("⚠️ {} es ambiguo en {:?} {:?} ({}), usando {}", symbol, exchange, market_type, natives.join(" / "), native);
+            }
+            map.insert(id, native);
+        }
+
+        if !unmapped.is_empty() {
+            tracing::warn!("⚠️ {:?} {:?} no lista {} símbolos: {}", exchange, market_type, unmapped.len(), unmapped.join(", "));
+        }
+        if map.is_empty() {
+            tracing::warn!("⚠️ {:?} {:?}: ningún símbolo mapeado, el conector no recibirá libros", exchange, market_type);
+        }
+        tracing::info!("🗺️ {:?} {:?}: {}/{} símbolos mapeados{}", exchange, market_type, map.len(), symbols.len(),
+            if has_metadata { "" } else { " (por convención, sin metadata)" });
+        map
+    }
+
+    // Libro del conector (en unidades del contrato) -> unidades reales
+    pub fn normalize(&self, mut update: BookUpdate) -> BookUpdate {
+        let multiplier = self.with(update.exchange, update.market_type, &update.symbol, |inst| inst.multiplier);
+        if let Some(multiplier) = multiplier.filter(|m| *m != 1.0) {
+            update.bid /= multiplier;
+            update.ask /= multiplier;
+            update.bid_size *= multiplier;
+            update.ask_size *= multiplier;
+            for (price, qty) in update.bids.iter_mut().chain(update.asks.iter_mut()) {
+                *price /= multiplier;
+                *qty *= multiplier;
+            }
+        }
+        update
+    }
+
+    // Cantidad (unidades reales) redondeada hacia abajo al lote del venue
+    pub fn round_qty(&self, exchange: Exchange, market_type: MarketType, symbol: &str, qty: f64) -> f64 {
+        self.with(exchange, market_type, symbol, |inst| floor_to_step(qty, inst.lot_size_real())).unwrap_or(qty)
+    }
+
+    // Precio al tick: compras hacia abajo y ventas hacia arriba (nunca mejoramos el precio pedido)
+    pub fn round_price(&self, exchange: Exchange, market_type: MarketType, symbol: &str, price: f64, round_up: bool) -> f64 {
+        self.with(exchange, market_type, symbol, |inst| round_to_step(price, inst.tick_size_real(), round_up)).unwrap_or(price)
+    }
+
+    pub fn meets_minimums(&self, exchange: Exchange, market_type: MarketType, symbol: &str, qty: f64, price: f64) -> bool {
+        self.with(exchange, market_type, symbol, |inst| qty >= inst.min_qty_real() && qty * price >= inst.min_notional)
+            .unwrap_or(qty > 0.0)
+    }
+}
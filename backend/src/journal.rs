@@ -0,0 +1,529 @@
+// src/journal.rs
+//
+// Diario de operaciones en SQLite embebido: trades con sus patas, órdenes,
+// oportunidades vistas, balances y acciones de control de los operadores. Es
+// la fuente de verdad del historial; el CSV queda solo como exportación.
+
+use crate::arbitrage::ArbitrageOpportunity;
+use crate::exchanges::Exchange;
+use crate::execution::Side;
+use crate::metrics;
+use crate::simulator::TradeLog;
+use anyhow::Result;
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_JOURNAL_PATH: &str = "journal.db";
+// Log de la versión anterior: se importa una sola vez al crear la base
+const LEGACY_CSV_PATH: &str = "trades_log.csv";
+
+pub const STRATEGY_SIMULATOR: &str = "simulador";
+pub const STRATEGY_MAKER_TAKER: &str = "maker_taker";
+pub const STRATEGY_CONVERGENCE: &str = "convergencia";
+
+// Cada entrada sube `user_version` en uno. Nunca se edita una ya publicada:
+// los cambios de esquema van en una migración nueva al final.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE trades (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        executed_at INTEGER,              -- ms UTC; NULL en los importados del CSV viejo
+        strategy TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        buy_exchange TEXT NOT NULL,
+        sell_exchange TEXT NOT NULL,
+        buy_price REAL NOT NULL,
+        sell_price REAL NOT NULL,
+        profit_usd REAL NOT NULL,
+        balance_after REAL,
+        note TEXT NOT NULL DEFAULT ''
+    );
+    CREATE INDEX trades_by_time ON trades(executed_at);
+    CREATE TABLE legs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        trade_id INTEGER NOT NULL REFERENCES trades(id),
+        exchange TEXT NOT NULL,
+        side TEXT NOT NULL,
+        price REAL NOT NULL,
+        qty REAL NOT NULL,
+        fee_usd REAL NOT NULL
+    );
+    CREATE INDEX legs_by_trade ON legs(trade_id);
+    CREATE TABLE orders (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        strategy TEXT NOT NULL,
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        side TEXT NOT NULL,
+        price REAL,                       -- NULL = orden a mercado
+        qty REAL NOT NULL,
+        order_id TEXT NOT NULL,
+        status TEXT NOT NULL
+    );
+    CREATE INDEX orders_by_order_id ON orders(order_id);
+    CREATE TABLE opportunities (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        seen_at INTEGER NOT NULL,
+        symbol TEXT NOT NULL,
+        buy_exchange TEXT NOT NULL,
+        sell_exchange TEXT NOT NULL,
+        buy_price REAL NOT NULL,
+        sell_price REAL NOT NULL,
+        net_profit_pct REAL NOT NULL,
+        net_profit_usd REAL NOT NULL,
+        max_tradeable_usd REAL NOT NULL
+    );
+    CREATE INDEX opportunities_by_time ON opportunities(seen_at);
+    CREATE TABLE balance_snapshots (
+        taken_at INTEGER NOT NULL,
+        exchange TEXT NOT NULL,
+        balance_usd REAL NOT NULL,
+        PRIMARY KEY (taken_at, exchange)
+    );",
+    // Para el reporte: edge capturado (profit / nocional) contra el detectado
+    "ALTER TABLE trades ADD COLUMN notional_usd REAL;
+    ALTER TABLE trades ADD COLUMN detected_edge_pct REAL;",
+    // Auditoría de la API de control: quién, qué y cómo terminó
+    "CREATE TABLE control_actions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        params TEXT NOT NULL,             -- JSON de la acción pedida
+        outcome TEXT NOT NULL             -- 'ok' o el motivo del rechazo
+    );
+    CREATE INDEX control_actions_by_time ON control_actions(at);",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum OrderStatus {
+    Placed,
+    Canceled,
+    Filled,
+    Rejected,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Placed => "placed",
+            OrderStatus::Canceled => "canceled",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Rejected => "rejected",
+        }
+    }
+}
+
+pub struct OrderRecord<'a> {
+    pub strategy: &'a str,
+    pub exchange: Exchange,
+    pub symbol: &'a str,
+    pub side: Side,
+    pub price: Option<f64>,
+    pub qty: f64,
+    pub order_id: &'a str,
+    pub status: OrderStatus,
+}
+
+pub struct TradeLeg {
+    pub exchange: Exchange,
+    pub side: Side,
+    pub price: f64,
+    pub qty: f64,
+    pub fee_usd: f64,
+}
+
+// Un trade cerrado. `balance_after` solo lo tiene el simulador (las otras estrategias no llevan ledger)
+pub struct TradeRecord<'a> {
+    pub strategy: &'a str,
+    pub executed_at: u64,
+    pub symbol: &'a str,
+    pub buy_exchange: Exchange,
+    pub sell_exchange: Exchange,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub profit_usd: f64,
+    pub notional_usd: f64,             // Pata compradora
+    pub detected_edge_pct: Option<f64>, // Edge neto esperado al decidir el trade
+    pub balance_after: Option<f64>,
+    pub note: &'a str,
+    pub legs: Vec<TradeLeg>,
+}
+
+// Trade tal como está en el diario (reportes y API)
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalTrade {
+    pub id: i64,
+    pub executed_at: Option<u64>,
+    pub strategy: String,
+    pub symbol: String,
+    pub buy_exchange: String,
+    pub sell_exchange: String,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub profit_usd: f64,
+    pub notional_usd: Option<f64>,
+    pub detected_edge_pct: Option<f64>,
+    pub balance_after: Option<f64>,
+    pub note: String,
+}
+
+// Filtros del historial paginado; todo opcional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TradeFilter {
+    pub strategy: Option<String>,
+    pub symbol: Option<String>,
+    pub exchange: Option<String>, // En cualquiera de las dos patas
+    pub since: Option<u64>,       // ms UTC, inclusive
+    pub until: Option<u64>,       // ms UTC, exclusive
+    pub page: Option<usize>,      // Desde 1
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TradePage {
+    pub total: usize,
+    pub page: usize,
+    pub limit: usize,
+    pub trades: Vec<JournalTrade>, // El más nuevo primero
+}
+
+const TRADE_COLUMNS: &str = "id, executed_at, strategy, symbol, buy_exchange, sell_exchange, buy_price, sell_price, profit_usd, notional_usd, detected_edge_pct, balance_after, note";
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 500;
+
+// Handle compartido: el simulador y las estrategias escriben en la misma base
+#[derive(Clone)]
+pub struct Journal {
+    conn: Arc<Mutex<Connection>>,   // Escrituras del motor
+    reader: Arc<Mutex<Connection>>, // Consultas de la API y reportes
+}
+
+impl Journal {
+    pub fn path() -> String {
+        std::env::var("JOURNAL_PATH").unwrap_or_else(|_| DEFAULT_JOURNAL_PATH.to_string())
+    }
+
+    pub fn open() -> Result<Self> {
+        let path = Self::path();
+        let conn = Connection::open(&path)?;
+        // WAL: la conexión de lectura ve lo último confirmado sin esperar a la de escritura
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        // Conexión aparte para las consultas: una exportación o una página de
+        // trades no toman el lock que usa el motor para registrar
+        let reader = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        Self::init(conn, Some(reader))
+    }
+
+    // Sin disco: el motor sigue operando aunque no quede registro. Una base en
+    // memoria no se puede abrir dos veces, así que lecturas y escrituras comparten conexión.
+    pub fn in_memory() -> Self {
+        let conn = Connection::open_in_memory().expect("SQLite en memoria");
+        Self::init(conn, None).expect("migraciones en memoria")
+    }
+
+    fn init(mut conn: Connection, reader: Option<Connection>) -> Result<Self> {
+        let created = migrate(&mut conn)?;
+        let conn = Arc::new(Mutex::new(conn));
+        let reader = reader.map(|r| Arc::new(Mutex::new(r))).unwrap_or_else(|| conn.clone());
+        let journal = Self { conn, reader };
+        if created {
+            match journal.import_legacy_csv(LEGACY_CSV_PATH) {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("📥 {} trades importados de {}", n, LEGACY_CSV_PATH),
+                Err(e) => tracing::warn!("⚠️ No se pudo importar {}: {:?}", LEGACY_CSV_PATH, e),
+            }
+        }
+        Ok(journal)
+    }
+
+    // Las escrituras no frenan el motor: un error queda en el log y se sigue
+    fn write(&self, what: &str, f: impl FnOnce(&mut Connection) -> rusqlite::Result<()>) {
+        let mut conn = self.conn.lock().unwrap();
+        if let Err(e) = f(&mut conn) {
+            tracing::warn!("⚠️ Diario: no se pudo registrar {}: {:?}", what, e);
+        }
+    }
+
+    pub fn record_trade(&self, trade: &TradeRecord) {
+        metrics::trade(trade.strategy, trade.profit_usd);
+        self.write("trade", |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO trades (executed_at, strategy, symbol, buy_exchange, sell_exchange, buy_price, sell_price, profit_usd, balance_after, note, notional_usd, detected_edge_pct)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    trade.executed_at as i64,
+                    trade.strategy,
+                    trade.symbol,
+                    trade.buy_exchange.as_str(),
+                    trade.sell_exchange.as_str(),
+                    trade.buy_price,
+                    trade.sell_price,
+                    trade.profit_usd,
+                    trade.balance_after,
+                    trade.note,
+                    trade.notional_usd,
+                    trade.detected_edge_pct,
+                ],
+            )?;
+            let trade_id = tx.last_insert_rowid();
+            for leg in &trade.legs {
+                tx.execute(
+                    "INSERT INTO legs (trade_id, exchange, side, price, qty, fee_usd) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![trade_id, leg.exchange.as_str(), format!("{:?}", leg.side), leg.price, leg.qty, leg.fee_usd],
+                )?;
+            }
+            tx.commit()
+        });
+    }
+
+    pub fn record_order(&self, order: &OrderRecord) {
+        metrics::order(order.strategy, order.exchange, order.status);
+        self.write("orden", |conn| {
+            conn.execute(
+                "INSERT INTO orders (at, strategy, exchange, symbol, side, price, qty, order_id, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    chrono::Utc::now().timestamp_millis(),
+                    order.strategy,
+                    order.exchange.as_str(),
+                    order.symbol,
+                    format!("{:?}", order.side),
+                    order.price,
+                    order.qty,
+                    order.order_id,
+                    order.status.as_str(),
+                ],
+            )?;
+            Ok(())
+        });
+    }
+
+    // Una transacción por barrido
+    pub fn record_opportunities<'a>(&self, opportunities: impl IntoIterator<Item = &'a ArbitrageOpportunity>) {
+        self.write("oportunidades", |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO opportunities (seen_at, symbol, buy_exchange, sell_exchange, buy_price, sell_price, net_profit_pct, net_profit_usd, max_tradeable_usd)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )?;
+                for op in opportunities {
+                    stmt.execute(params![
+                        op.created_at as i64,
+                        op.symbol,
+                        op.buy_exchange.as_str(),
+                        op.sell_exchange.as_str(),
+                        op.buy_price,
+                        op.sell_price,
+                        op.net_profit_pct,
+                        op.net_profit_usd,
+                        op.max_tradeable_usd,
+                    ])?;
+                }
+            }
+            tx.commit()
+        });
+    }
+
+    pub fn record_balances(&self, balances: &HashMap<Exchange, f64>) {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.write("balances", |conn| {
+            let tx = conn.transaction()?;
+            for (exchange, balance) in balances {
+                tx.execute(
+                    "INSERT OR REPLACE INTO balance_snapshots (taken_at, exchange, balance_usd) VALUES (?1, ?2, ?3)",
+                    params![now, exchange.as_str(), balance],
+                )?;
+            }
+            tx.commit()
+        });
+    }
+
+    pub fn record_control(&self, actor: &str, action: &str, params: &str, outcome: &str) {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.write("acción de control", |conn| {
+            conn.execute(
+                "INSERT INTO control_actions (at, actor, action, params, outcome) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![now, actor, action, params, outcome],
+            )?;
+            Ok(())
+        });
+    }
+
+    // Historial del simulador para el dashboard, el más nuevo primero
+    pub fn recent_trades(&self, limit: usize) -> Result<Vec<TradeLog>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT executed_at, symbol, buy_exchange, sell_exchange, buy_price, sell_price, profit_usd, balance_after, note
+             FROM trades WHERE strategy = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![STRATEGY_SIMULATOR, limit as i64], trade_log_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // (estrategia, trades, PnL) de todo el diario
+    pub fn strategy_totals(&self) -> Result<Vec<(String, u64, f64)>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT strategy, COUNT(*), COALESCE(SUM(profit_usd), 0) FROM trades GROUP BY strategy")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64, row.get(2)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    // Todos los trades en orden de ejecución, opcionalmente de una sola estrategia
+    pub fn trades(&self, strategy: Option<&str>) -> Result<Vec<JournalTrade>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM trades WHERE ?1 IS NULL OR strategy = ?1 ORDER BY id", TRADE_COLUMNS))?;
+        let rows = stmt.query_map(params![strategy], journal_trade_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn trade_page(&self, filter: &TradeFilter) -> Result<TradePage> {
+        // Un NULL en el parámetro desactiva ese filtro
+        let conditions = "(?1 IS NULL OR strategy = ?1)
+            AND (?2 IS NULL OR symbol = ?2)
+            AND (?3 IS NULL OR buy_exchange = ?3 OR sell_exchange = ?3)
+            AND (?4 IS NULL OR executed_at >= ?4)
+            AND (?5 IS NULL OR executed_at < ?5)";
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+        let page = filter.page.unwrap_or(1).max(1);
+        let args = params![
+            filter.strategy,
+            filter.symbol,
+            filter.exchange,
+            filter.since.map(|t| t as i64),
+            filter.until.map(|t| t as i64),
+        ];
+
+        let conn = self.reader.lock().unwrap();
+        let total: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM trades WHERE {}", conditions), args, |r| r.get(0))?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM trades WHERE {} ORDER BY id DESC LIMIT {} OFFSET {}",
+            TRADE_COLUMNS, conditions, limit, (page - 1) * limit
+        ))?;
+        let trades = stmt.query_map(args, journal_trade_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(TradePage { total: total as usize, page, limit, trades })
+    }
+
+    // Todos los trades (todas las estrategias) a CSV; devuelve cuántos se escribieron
+    pub fn export_csv(&self, path: &str) -> Result<usize> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, executed_at, strategy, symbol, buy_exchange, sell_exchange, buy_price, sell_price, profit_usd, balance_after, note
+             FROM trades ORDER BY id",
+        )?;
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["Id", "ExecutedAt", "Strategy", "Symbol", "BuyEx", "SellEx", "BuyPrice", "SellPrice", "Profit", "Balance", "Note"])?;
+        let mut rows = stmt.query([])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let executed_at: Option<i64> = row.get(1)?;
+            let balance_after: Option<f64> = row.get(9)?;
+            wtr.write_record([
+                row.get::<_, i64>(0)?.to_string(),
+                executed_at.map(|ms| format_ts(ms as u64)).unwrap_or_default(),
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get::<_, f64>(6)?.to_string(),
+                row.get::<_, f64>(7)?.to_string(),
+                row.get::<_, f64>(8)?.to_string(),
+                balance_after.map(|b| b.to_string()).unwrap_or_default(),
+                row.get(10)?,
+            ])?;
+            count += 1;
+        }
+        wtr.flush()?;
+        Ok(count)
+    }
+
+    // trades_log.csv: Timestamp,Symbol,BuyEx,SellEx,BuyPrice,SellPrice,Profit,Balance,Note
+    // La hora venía sin fecha, así que esos trades quedan con executed_at NULL.
+    fn import_legacy_csv(&self, path: &str) -> Result<usize> {
+        let mut rdr = match csv::ReaderBuilder::new().flexible(true).from_path(path) {
+            Ok(rdr) => rdr,
+            Err(e) if matches!(e.kind(), csv::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::NotFound) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut count = 0;
+        for record in rdr.records() {
+            let r = record?;
+            if r.len() < 8 {
+                continue;
+            }
+            let num = |i: usize| r.get(i).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+            let note = format!("{} [importado de CSV, hora {}]", r.get(8).unwrap_or(""), &r[0]);
+            tx.execute(
+                "INSERT INTO trades (executed_at, strategy, symbol, buy_exchange, sell_exchange, buy_price, sell_price, profit_usd, balance_after, note)
+                 VALUES (NULL, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![STRATEGY_SIMULATOR, &r[1], &r[2], &r[3], num(4), num(5), num(6), num(7), note.trim_start()],
+            )?;
+            count += 1;
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+}
+
+// Aplica las migraciones pendientes. true si la base estaba vacía.
+fn migrate(conn: &mut Connection) -> Result<bool> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get::<_, i64>(0))? as usize;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+        tracing::info!("🗄️ Diario migrado a la versión {}", i + 1);
+    }
+    Ok(version == 0)
+}
+
+fn journal_trade_from_row(row: &rusqlite::Row) -> rusqlite::Result<JournalTrade> {
+    Ok(JournalTrade {
+        id: row.get(0)?,
+        executed_at: row.get::<_, Option<i64>>(1)?.map(|t| t as u64),
+        strategy: row.get(2)?,
+        symbol: row.get(3)?,
+        buy_exchange: row.get(4)?,
+        sell_exchange: row.get(5)?,
+        buy_price: row.get(6)?,
+        sell_price: row.get(7)?,
+        profit_usd: row.get(8)?,
+        notional_usd: row.get(9)?,
+        detected_edge_pct: row.get(10)?,
+        balance_after: row.get(11)?,
+        note: row.get(12)?,
+    })
+}
+
+fn trade_log_from_row(row: &rusqlite::Row) -> rusqlite::Result<TradeLog> {
+    let executed_at: Option<i64> = row.get(0)?;
+    Ok(TradeLog {
+        timestamp: executed_at.map(|ms| format_ts(ms as u64)).unwrap_or_default(),
+        executed_at: executed_at.unwrap_or(0) as u64,
+        symbol: row.get(1)?,
+        buy_exchange: row.get(2)?,
+        sell_exchange: row.get(3)?,
+        buy_price: row.get(4)?,
+        sell_price: row.get(5)?,
+        profit_usd: row.get(6)?,
+        balance_after: row.get::<_, Option<f64>>(7)?.unwrap_or(0.0),
+        note: row.get(8)?,
+    })
+}
+
+// Hora local con fecha, para humanos (dashboard y CSV)
+pub fn format_ts(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
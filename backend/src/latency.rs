@@ -0,0 +1,75 @@
+// src/latency.rs
+//
+// Latencia de cada feed: cuánto tarda un evento del venue en llegarnos
+// (hora de recepción local - hora del evento llevada a nuestro reloj).
+// Sin offset medido para el venue incluye el desfase y puede salir negativa.
+
+use crate::exchanges::Exchange;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+
+// Muestras que se guardan por venue para los percentiles
+const LATENCY_WINDOW: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedLatency {
+    pub exchange: Exchange,
+    pub samples: usize,
+    pub last_ms: i64,
+    pub mean_ms: f64,
+    pub p50_ms: i64,
+    pub p99_ms: i64,
+    pub max_ms: i64,
+}
+
+#[derive(Clone, Default)]
+pub struct LatencyTracker {
+    windows: Arc<DashMap<Exchange, VecDeque<i64>>>,
+}
+
+// Hora de pared (ms epoch) en la que se tomó `instant`
+pub fn wall_clock_ms(instant: Instant) -> u64 {
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    now.saturating_sub(instant.elapsed().as_millis() as u64)
+}
+
+impl LatencyTracker {
+    // Devuelve la latencia medida (ms) para quien también la exporte
+    pub fn record(&self, exchange: Exchange, exchange_ts: u64, received_at: Instant) -> i64 {
+        let latency = wall_clock_ms(received_at) as i64 - exchange_ts as i64;
+        let mut window = self.windows.entry(exchange).or_default();
+        if window.len() >= LATENCY_WINDOW {
+            window.pop_front();
+        }
+        window.push_back(latency);
+        latency
+    }
+
+    pub fn snapshot(&self) -> Vec<FeedLatency> {
+        let mut stats: Vec<FeedLatency> = self
+            .windows
+            .iter()
+            .filter_map(|entry| {
+                let window = entry.value();
+                let last_ms = *window.back()?;
+                let mut sorted: Vec<i64> = window.iter().copied().collect();
+                sorted.sort_unstable();
+                let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+                Some(FeedLatency {
+                    exchange: *entry.key(),
+                    samples: sorted.len(),
+                    last_ms,
+                    mean_ms: sorted.iter().sum::<i64>() as f64 / sorted.len() as f64,
+                    p50_ms: percentile(0.5),
+                    p99_ms: percentile(0.99),
+                    max_ms: *sorted.last()?,
+                })
+            })
+            .collect();
+        stats.sort_by_key(|s| s.exchange.as_str());
+        stats
+    }
+}
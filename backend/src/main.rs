@@ -1,85 +1,175 @@
 // backend/src/main.rs
 
 mod aggregator;
+mod api;
+mod auth;
 mod arbitrage;
+mod clock;
+mod control;
 mod exchanges;
 mod execution;
+mod fees;
+mod fx;
+mod health;
+mod instruments;
+mod journal;
+mod latency;
+mod metrics;
+mod recorder;
+mod report;
+mod simulator;
+mod state;
+mod stream;
+mod supervisor;
 
-use aggregator::{PriceAggregator, MarketBook};
-use arbitrage::{ArbitrageDetector, ArbitrageOpportunity};
-use exchanges::Exchange;
+use aggregator::{FundingInfo, PriceAggregator, MarketBook};
+use api::{ApiState, DashboardPayload, EngineConfig, ServerConfig};
+use arbitrage::{detector::sort_by_profit, ArbitrageDetector, BasisDetector, BasisOpportunity, ArbitrageOpportunity, ConvergenceConfig, ConvergenceStrategy, FundingDetector, FundingOpportunity, MakerTakerConfig, MakerTakerStrategy, TriangularDetector};
+use auth::{Auth, Principal};
+use clock::ClockSync;
+use control::{ControlAction, ControlRequest, RuntimeLimits, TradingControl};
+use execution::{Executor, MockExecutor};
+use fees::FeeConfig;
+use health::FeedHealth;
+use instruments::InstrumentRegistry;
+use journal::Journal;
+use metrics::RiskLimit;
+use recorder::TickRecorder;
+use report::ReportFormat;
+use simulator::SimEngine;
+use state::SavedState;
+use stream::ClientMetrics;
+use supervisor::Supervisor;
+use exchanges::{Exchange, MarketType, SymbolMap};
 use exchanges::{binance::BinanceConnector, hyperliquid::HyperliquidConnector, bybit::BybitConnector, extended::ExtendedConnector, ExchangeConnector};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
 use warp::Filter;
-use tracing::info;
-use futures_util::{SinkExt, StreamExt};
-use serde::Serialize;
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader};
-
-const WS_PORT: u16 = 3030;
-
-#[derive(Serialize, Clone)]
-struct SimStats {
-    total_usd: f64,
-    binance_usd: f64,
-    bybit_usd: f64,
-    hyperliquid_usd: f64,
-    extended_usd: f64,
-    trade_count: u32,
-    last_action: String,
-}
-
-#[derive(Serialize, Clone)]
-struct DashboardPayload {
-    opportunities: Vec<ArbitrageOpportunity>,
-    stats: SimStats,
-    recent_trades: Vec<TradeLog>,    
-}
+use tracing::{error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-#[derive(Serialize, Clone)] // Se requiere Clone para el historial en memoria
-struct TradeLog {
-    timestamp: String,
-    symbol: String,
-    buy_exchange: String,
-    sell_exchange: String,
-    buy_price: f64,
-    sell_price: f64,
-    profit_usd: f64,
-    balance_after: f64,
-    note: String,
-}
+// Barrido completo periódico: limpia oportunidades cuyos libros quedaron viejos
+const FULL_SWEEP_INTERVAL_MS: u64 = 1000;
+// Ritmo de envío al Dashboard (la detección ya no depende de esto)
+const PUBLISH_INTERVAL_MS: u64 = 50;
+// Funding: horizonte de la posición y edge mínimo neto de costos (en %)
+const FUNDING_HOLDING_HOURS: f64 = 8.0;
+const FUNDING_MIN_NET_EDGE_PCT: f64 = 0.01;
+// Basis spot-perp: edge mínimo neto de fees (en %)
+const BASIS_MIN_NET_PCT: f64 = 0.0;
+// Comisiones reales de la cuenta (endpoints privados)
+const FEE_REFRESH_INTERVAL_SECS: u64 = 3600;
+// Tiempo que se le da a las tareas para cerrar antes de abortarlas
+const SHUTDOWN_GRACE_SECS: u64 = 5;
+// Cada cuánto se guarda el estado a disco (además de en cada trade y al apagar)
+const STATE_SNAPSHOT_INTERVAL_SECS: u64 = 30;
+// Trades del simulador que se muestran en el dashboard
+const MAX_HISTORY_TRADES: usize = 10;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    // Logs a stderr: stdout queda limpio para `--report` (tabla o JSON)
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    dotenv::dotenv().ok();
     info!("🤖 FLASH-ARB: Motor v2.0 Online (Multi-Balance + VWAP)");
 
-    init_csv();
+    // Diario de trades, órdenes, oportunidades y balances (SQLite)
+    let journal = Journal::open().unwrap_or_else(|e| {
+        warn!("⚠️ No se pudo abrir el diario en {} ({:?}): se sigue sin persistencia", Journal::path(), e);
+        Journal::in_memory()
+    });
 
-    let (tx, _rx) = broadcast::channel::<DashboardPayload>(100);
-    let tx_clone = tx.clone();
+    // `--export-csv <archivo>`: vuelca los trades del diario y termina
+    if let Some(path) = arg_value("--export-csv") {
+        match journal.export_csv(&path) {
+            Ok(n) => info!("📤 {} trades exportados a {}", n, path),
+            Err(e) => warn!("⚠️ No se pudo exportar a {}: {:?}", path, e),
+        }
+        return;
+    }
 
-    let routes = warp::path("ws").and(warp::ws()).map(move |ws: warp::ws::Ws| {
-        let rx = tx_clone.subscribe();
-        ws.on_upgrade(move |socket| handle_socket(socket, rx))
-    });
+    // `--report [table|json|html] [--strategy <nombre>] [--out <archivo>]`: reporte del diario y termina
+    if std::env::args().any(|arg| arg == "--report") {
+        let format = arg_value("--report").filter(|v| !v.starts_with("--")).unwrap_or_else(|| "table".to_string());
+        let result = ReportFormat::parse(&format)
+            .and_then(|format| report::run(&journal, format, arg_value("--strategy").as_deref(), arg_value("--out").as_deref()));
+        if let Err(e) = result {
+            warn!("⚠️ No se pudo generar el reporte: {:?}", e);
+        }
+        return;
+    }
 
-    tokio::spawn(async move {
-        warp::serve(routes).run(([127, 0, 0, 1], WS_PORT)).await;
+    // Usuarios, dirección y TLS del servidor: si la configuración está mal no se arranca
+    let (auth, server_config) = match Auth::from_env().and_then(|auth| ServerConfig::from_env(&auth).map(|config| (auth, Arc::new(config)))) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("❌ Configuración del servidor inválida: {:#}", e);
+            return;
+        }
+    };
+    info!("🔐 Acceso al servidor: {}", auth.describe());
+
+    // Trades y PnL de /metrics arrancan desde lo que ya tiene el diario
+    match journal.strategy_totals() {
+        Ok(totals) => metrics::seed_trades(&totals),
+        Err(e) => warn!("⚠️ No se pudieron leer los totales del diario: {:?}", e),
+    }
+
+    // Todas las tareas de fondo cuelgan del supervisor: se relanzan si caen y se cierran al apagar
+    let mut supervisor = Supervisor::new();
+
+    // Salud de los feeds: la completan los conectores y se publica en /health y en el dashboard
+    let health = FeedHealth::default();
+
+    // Desfase de reloj contra cada exchange (horas de evento y requests firmados)
+    let clock = ClockSync::new();
+    let clock_sync = clock.clone();
+    supervisor.spawn("reloj", move || clock::run(clock_sync.clone()));
+
+    // Modelo de fees compartido por todas las estrategias
+    let fee_config = FeeConfig::load();
+    let fee_refresher = fee_config.clone();
+    let fee_clock = clock.clone();
+    supervisor.spawn("comisiones", move || {
+        let (fee_refresher, fee_clock) = (fee_refresher.clone(), fee_clock.clone());
+        async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(FEE_REFRESH_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                fees::live::refresh(&fee_refresher, &fee_clock).await;
+            }
+        }
     });
 
-    // --- BALANCES INICIALES ---
-    let mut b_bal = 5000.0;
-    let mut by_bal = 5000.0;
-    let mut hl_bal = 5000.0;
-    let mut ex_bal = 5000.0;
-    let mut sim_balance = 20000.0;
-    let mut trade_count = 0;
-    let mut last_trade_log = "Sistema Iniciado".to_string();
-    
+    // Estado de la corrida anterior; `--reset` arranca de cero
+    let previous = if std::env::args().any(|arg| arg == "--reset") {
+        match state::discard() {
+            Ok(()) => info!("🧽 --reset: se descarta el estado anterior ({}.bak)", state::path()),
+            Err(e) => warn!("⚠️ No se pudo apartar el estado anterior: {:?}", e),
+        }
+        None
+    } else {
+        match state::load() {
+            Ok(saved) => saved,
+            Err(e) => {
+                // No se pisa un archivo que no pudimos leer: se aparta y se arranca de cero
+                warn!("⚠️ Estado ilegible en {}, arrancando de cero: {:?}", state::path(), e);
+                let _ = state::discard();
+                None
+            }
+        }
+    };
+
     // Lista para el historial en el Dashboard
-    let mut recent_trades_list = load_history_from_csv();
+    let history = if previous.is_some() {
+        Vec::new()
+    } else {
+        journal.recent_trades(MAX_HISTORY_TRADES).unwrap_or_else(|e| {
+            warn!("⚠️ No se pudo leer el historial del diario: {:?}", e);
+            Vec::new()
+        })
+    };
+    let mut sim = SimEngine::new(fee_config.clone(), journal.clone(), history);
 
     let all_symbols = vec![
         // Hyperliquid & Ecosystem Leaders
@@ -99,200 +189,423 @@ async fn main() {
         "INJ-USDT".into(), "STX-USDT".into(), "ORDI-USDT".into(),
         "BTC-USDT".into(),
     ];
+
+    // Cruces spot para arbitraje triangular (solo Binance/Bybit los listan)
+    let cross_symbols: Vec<String> = vec![
+        "ETH-BTC".into(), "SOL-BTC".into(), "SOL-ETH".into(), "LINK-BTC".into(),
+        "LINK-ETH".into(), "DOGE-BTC".into(), "AVAX-BTC".into(), "NEAR-BTC".into(),
+        "SUI-BTC".into(), "APT-BTC".into(), "ARB-BTC".into(), "OP-BTC".into(),
+    ];
+    let spot_symbols: Vec<String> = all_symbols.iter().chain(cross_symbols.iter()).cloned().collect();
     
-    let aggregator = PriceAggregator::new();
+    let aggregator = PriceAggregator::new(clock.clone());
+    // USDC/USDT y USD/USDT para normalizar Hyperliquid y Extended
+    let fx_aggregator = aggregator.clone();
+    supervisor.spawn("fx", move || fx::run_feed(fx_aggregator.clone()));
+    // Tick, lote, mínimos y multiplicadores de cada venue (con caché en disco)
+    let instruments = InstrumentRegistry::load().await;
+
+    // Grabación de libros y oportunidades a Parquet (opcional, para análisis offline)
+    let recorder = match std::env::var("TICK_RECORD_DIR") {
+        Ok(dir) => {
+            let (recorder, writer) = recorder::channel(dir);
+            supervisor.spawn_graceful("grabador", move |signal| writer.clone().run(signal));
+            recorder
+        }
+        Err(_) => TickRecorder::disabled(),
+    };
 
     // Conectores
-    let mut binance = BinanceConnector::new();
-    if let Ok(_) = binance.connect(all_symbols.clone()).await {
-        let mut rx = binance.get_receiver(); let agg = aggregator.clone();
-        tokio::spawn(async move { while let Some(u) = rx.recv().await { agg.update(u.symbol.clone(), u.exchange, MarketBook::from(u)); }});
-    }
-    let mut hl = HyperliquidConnector::new();
-    if let Ok(_) = hl.connect(all_symbols.clone()).await {
-        let mut rx = hl.get_receiver(); let agg = aggregator.clone();
-        tokio::spawn(async move { while let Some(u) = rx.recv().await { agg.update(u.symbol.clone(), u.exchange, MarketBook::from(u)); }});
-    }
-    let mut bybit = BybitConnector::new();
-    if let Ok(_) = bybit.connect(all_symbols.clone()).await {
-        let mut rx = bybit.get_receiver(); let agg = aggregator.clone();
-        tokio::spawn(async move { while let Some(u) = rx.recv().await { agg.update(u.symbol.clone(), u.exchange, MarketBook::from(u)); }});
-    }
-    let mut extended = ExtendedConnector::new();
-    if let Ok(_) = extended.connect(all_symbols.clone()).await {
-        let mut rx = extended.get_receiver(); let agg = aggregator.clone();
-        tokio::spawn(async move { while let Some(u) = rx.recv().await { agg.update(u.symbol.clone(), u.exchange, MarketBook::from(u)); }});
+    let sinks = FeedSinks { aggregator: aggregator.clone(), instruments: instruments.clone(), health: health.clone(), recorder: recorder.clone() };
+    supervise_connector(&mut supervisor, BinanceConnector::new, MarketType::Perp, &all_symbols, &sinks);
+    supervise_connector(&mut supervisor, HyperliquidConnector::new, MarketType::Perp, &all_symbols, &sinks);
+    supervise_connector(&mut supervisor, BybitConnector::new, MarketType::Perp, &all_symbols, &sinks);
+    supervise_connector(&mut supervisor, ExtendedConnector::new, MarketType::Perp, &all_symbols, &sinks);
+
+    // Mercados spot (para basis spot-perp)
+    supervise_connector(&mut supervisor, BinanceConnector::spot, MarketType::Spot, &spot_symbols, &sinks);
+    supervise_connector(&mut supervisor, BybitConnector::spot, MarketType::Spot, &spot_symbols, &sinks);
+    supervise_connector(&mut supervisor, HyperliquidConnector::spot, MarketType::Spot, &spot_symbols, &sinks);
+    let monitor = health.clone();
+    supervisor.spawn("salud", move || health::run_monitor(monitor.clone()));
+
+    let detector = ArbitrageDetector::new(aggregator.clone(), fee_config.clone(), instruments.clone(), 0.0);
+    let funding_detector = FundingDetector::new(aggregator.clone(), fee_config.clone(), FUNDING_HOLDING_HOURS, FUNDING_MIN_NET_EDGE_PCT);
+    let mut funding_opportunities: Vec<FundingOpportunity> = Vec::new();
+    let basis_detector = BasisDetector::new(aggregator.clone(), fee_config.clone(), BASIS_MIN_NET_PCT);
+    let mut basis_opportunities: Vec<BasisOpportunity> = Vec::new();
+    let triangular_detector = TriangularDetector::new(aggregator.clone(), fee_config.clone());
+    let mut triangular_opportunities: Vec<ArbitrageOpportunity> = Vec::new();
+
+    // Modo maker-taker: por ahora contra ejecutores simulados
+    let executors: HashMap<Exchange, Arc<dyn Executor + Send + Sync>> =
+        [Exchange::Binance, Exchange::Bybit, Exchange::Hyperliquid, Exchange::Extended]
+            .into_iter()
+            .map(|ex| (ex, Arc::new(MockExecutor::new(ex, instruments.clone())) as Arc<dyn Executor + Send + Sync>))
+            .collect();
+    let maker_taker_config = MakerTakerConfig::default();
+    let convergence_config = ConvergenceConfig::default();
+    let mut maker_taker = MakerTakerStrategy::new(aggregator.clone(), fee_config.clone(), maker_taker_config, executors, journal.clone());
+    let mut convergence = ConvergenceStrategy::new(aggregator.clone(), fee_config.clone(), convergence_config, journal.clone());
+    // Pausas, límites en caliente y kill switch; los cambia la API de control
+    let mut control = TradingControl::default();
+    if let Some(saved) = previous {
+        info!("♻️ Estado restaurado de {}: {} trades, ${:.2} en balances, {} posiciones abiertas",
+            state::path(), saved.sim.trade_count, saved.sim.balances.values().sum::<f64>(), saved.convergence.open_positions.len());
+        sim.restore(saved.sim);
+        maker_taker.restore(saved.maker_taker);
+        convergence.restore(saved.convergence);
+        fee_config.restore_daily_volume(saved.daily_volume);
+        control = saved.control;
+        if let Some(kill) = &control.kill_switch {
+            warn!("🚨 Kill switch activo desde {} ({}, por {}): no se opera hasta rearmarlo",
+                journal::format_ts(kill.tripped_at), kill.reason, kill.actor);
+        }
     }
+    apply_limits(&control.limits, &mut sim, &mut maker_taker, &mut convergence);
+
+    // Dashboard por /ws (payload completo o topics con deltas, ver stream.rs) y API REST para consultas puntuales
+    let (tx, _rx) = broadcast::channel::<Arc<DashboardPayload>>(100);
+    let (latest_tx, latest_rx) = watch::channel(None);
+    let (control_handle, mut control_rx) = control::channel();
+    let tx_clone = tx.clone();
+    let ws_clients = ClientMetrics::default();
+    let (ws_latest, ws_aggregator, ws_metrics) = (latest_rx.clone(), aggregator.clone(), ws_clients.clone());
+    let ws_route = warp::path("ws").and(auth::require_ws(auth.clone())).and(warp::ws()).map(move |_: Principal, ws: warp::ws::Ws| {
+        let (rx, latest, aggregator, metrics) = (tx_clone.subscribe(), ws_latest.clone(), ws_aggregator.clone(), ws_metrics.clone());
+        ws.on_upgrade(move |socket| stream::handle_socket(socket, rx, latest, aggregator, metrics))
+    });
+    let api_state = ApiState {
+        latest: latest_rx,
+        journal: journal.clone(),
+        health: health.clone(),
+        aggregator: aggregator.clone(),
+        clock: clock.clone(),
+        fees: fee_config.clone(),
+        config: Arc::new(EngineConfig {
+            perp_symbols: all_symbols.clone(),
+            spot_symbols: spot_symbols.clone(),
+            maker_taker: maker_taker_config,
+            convergence: convergence_config,
+            funding_holding_hours: FUNDING_HOLDING_HOURS,
+            funding_min_net_edge_pct: FUNDING_MIN_NET_EDGE_PCT,
+            basis_min_net_pct: BASIS_MIN_NET_PCT,
+            journal_path: Journal::path(),
+            state_path: state::path(),
+            tick_record_dir: std::env::var("TICK_RECORD_DIR").ok(),
+        }),
+        control: control_handle,
+        auth,
+        ws_clients,
+    };
+    let routes = ws_route.or(api::routes(api_state)).recover(api::recover);
+
+    supervisor.spawn_graceful("servidor", move |signal| api::serve(routes.clone(), server_config.clone(), signal));
 
-    let detector = ArbitrageDetector::new(aggregator.clone(), 0.0);
+    let mut updates = aggregator.subscribe();
     tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
 
+    // Últimas oportunidades conocidas por símbolo. Solo se recalcula el símbolo
+    // que cambió; el barrido completo se encarga de la caducidad.
+    let mut opportunities_by_symbol: HashMap<String, Vec<ArbitrageOpportunity>> = HashMap::new();
+    let mut sweep = tokio::time::interval(tokio::time::Duration::from_millis(FULL_SWEEP_INTERVAL_MS));
+    let mut publish = tokio::time::interval(tokio::time::Duration::from_millis(PUBLISH_INTERVAL_MS));
+    let mut snapshot = tokio::time::interval(tokio::time::Duration::from_secs(STATE_SNAPSHOT_INTERVAL_SECS));
+    snapshot.tick().await; // El primer tick es inmediato: recién restauramos
+    // Un solo futuro para toda la vida del loop: una señal entre dos vueltas no se pierde
+    let os_signal = supervisor::wait_for_os_signal();
+    tokio::pin!(os_signal);
+
+    // Cada rama corre completa antes de volver al select: un trade o una orden
+    // en curso siempre termina antes de atender la señal de apagado
     loop {
-        let mut opportunities = detector.detect_opportunities();
-        
-        let slippage_bps = 0.5; 
-        let slippage_factor = slippage_bps / 10000.0; 
-        
-        // Simulación de fricción para todas las oportunidades en el feed
-        for op in opportunities.iter_mut() {
-            let cap_sim = f64::min(2000.0, op.max_tradeable_usd);
-            let liq_impact_sim = (cap_sim / op.max_tradeable_usd) * 0.0003;
-            let total_fric_sim = slippage_factor + liq_impact_sim;
-            
-            // Usamos data_age_ms para pasar temporalmente este valor al Dashboard 
-            // o simplemente deja que el backend lo use para filtrar visualmente.
-            op.total_fees_pct = (total_fric_sim * 100.0) + 0.06; // Fricción + Fee estimado
-        }
+        tokio::select! {
+            _ = &mut os_signal => {
+                info!("🛑 Señal de apagado recibida: no se abren operaciones nuevas");
+                break;
+            }
+            update = updates.recv() => {
+                let mut changed = HashSet::new();
+                // El detector y el maker-taker operan perps: un libro spot no dispara nada
+                match update {
+                    Ok((symbol, MarketType::Perp)) => { changed.insert(symbol); }
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("⚠️ Detector atrasado ({} updates perdidos), barrido completo", skipped);
+                        opportunities_by_symbol = group_by_symbol(detector.detect_opportunities());
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+                // Agrupamos todo lo que ya está en cola para no evaluar dos veces el mismo símbolo
+                while let Ok(update) = updates.try_recv() {
+                    if let (symbol, MarketType::Perp) = update {
+                        changed.insert(symbol);
+                    }
+                }
 
-        if !opportunities.is_empty() {
-            let best_op = &opportunities[0];
-            let base_fee_pct = 0.0006;
-        
-            let trade_capital = f64::min(2000.0, best_op.max_tradeable_usd);
-            let liquidity_impact = (trade_capital / best_op.max_tradeable_usd) * 0.0003;
-        
-            let total_friction = slippage_factor + liquidity_impact;
-            let final_buy_price = best_op.buy_price * (1.0 + total_friction);
-            let final_sell_price = best_op.sell_price * (1.0 - total_friction);
-        
-            if trade_capital > 10.0 {
-                let has_funds = match best_op.buy_exchange {
-                    Exchange::Binance => b_bal >= trade_capital,
-                    Exchange::Bybit => by_bal >= trade_capital,
-                    Exchange::Hyperliquid => hl_bal >= trade_capital,
-                    Exchange::Extended => ex_bal >= trade_capital,
-                };
+                let started = std::time::Instant::now();
+                let fills_before = (maker_taker.fill_count(), maker_taker.unhedged_count());
+                let mut fresh = Vec::new();
+                for symbol in changed {
+                    let ops = detector.detect_for_symbol(&symbol);
+                    fresh.extend(ops.iter().cloned());
+                    // Fills y re-cotización de las órdenes pasivas de este símbolo
+                    maker_taker.on_symbol(&symbol, &control.paused_exchanges(&symbol)).await;
+                    opportunities_by_symbol.insert(symbol, ops);
+                }
 
-                if has_funds {
-                    let trade_qty = trade_capital / final_buy_price;
-                    let cost_real = (trade_qty * final_buy_price) * (1.0 + base_fee_pct);
-                    let revenue_real = (trade_qty * final_sell_price) * (1.0 - base_fee_pct);
-                    let profit_net_real = revenue_real - cost_real;
-
-                    if profit_net_real > 0.0001 {
-                        // Gestión de Balances
-                        match best_op.buy_exchange {
-                            Exchange::Binance => b_bal -= trade_capital,
-                            Exchange::Bybit => by_bal -= trade_capital,
-                            Exchange::Hyperliquid => hl_bal -= trade_capital,
-                            Exchange::Extended => ex_bal -= trade_capital,
-                        }
-
-                        match best_op.sell_exchange {
-                            Exchange::Binance => b_bal += trade_capital + profit_net_real,
-                            Exchange::Bybit => by_bal += trade_capital + profit_net_real,
-                            Exchange::Hyperliquid => hl_bal += trade_capital + profit_net_real,
-                            Exchange::Extended => ex_bal += trade_capital + profit_net_real,
-                        }
-
-                        sim_balance = b_bal + by_bal + hl_bal + ex_bal;
-                        trade_count += 1;
-                        last_trade_log = format!("WIN: {} (+${:.4})", best_op.symbol, profit_net_real);
-                        
-                        let new_trade_entry = TradeLog {
-                            timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
-                            symbol: best_op.symbol.clone(),
-                            buy_exchange: format!("{:?}", best_op.buy_exchange),
-                            sell_exchange: format!("{:?}", best_op.sell_exchange),
-                            buy_price: final_buy_price,
-                            sell_price: final_sell_price,
-                            profit_usd: profit_net_real,
-                            balance_after: sim_balance,
-                            note: format!("Tokio Sim (Fric: {:.2}bps)", total_friction * 10000.0),
-                        };
-
-                        // Actualizar historial para el Frontend
-                        recent_trades_list.insert(0, new_trade_entry.clone());
-                        recent_trades_list.truncate(10);
-
-                        info!("💰 TRADE #{}: +${:.4} en {} (Fricción: {:.4}%)", 
-                            trade_count, profit_net_real, best_op.symbol, total_friction * 100.0);
-                            
-                        log_trade_to_csv(new_trade_entry);
-                    }
+                metrics::detection("event", started.elapsed());
+                recorder.record_opportunities(&fresh);
+
+                // Solo se opera sobre oportunidades recién evaluadas con datos nuevos
+                sort_by_profit(&mut fresh);
+                // Una cuenta por decisión: solo si la mejor candidata quedó frenada
+                if fresh.first().is_some_and(|op| op.net_profit_pct > 0.0 && !control.allows(&op.symbol, &[op.buy_exchange, op.sell_exchange])) {
+                    metrics::risk_limit(if control.kill_switch.is_some() { RiskLimit::KillSwitch } else { RiskLimit::Paused });
                 }
+                let traded = fresh
+                    .iter()
+                    .find(|op| control.allows(&op.symbol, &[op.buy_exchange, op.sell_exchange]))
+                    .and_then(|best_op| sim.try_trade(best_op))
+                    .is_some();
+                // El diario registra cada trade en el acto: el estado se guarda junto con él
+                // para que un corte no deje balances y contadores atrás del diario (ni
+                // pierda un fill maker que quedó sin cubrir)
+                if traded || (maker_taker.fill_count(), maker_taker.unhedged_count()) != fills_before {
+                    save_state(&saved_state(&sim, &maker_taker, &convergence, &fee_config, &control));
+                }
+            }
+            _ = sweep.tick() => {
+                let started = std::time::Instant::now();
+                opportunities_by_symbol = group_by_symbol(detector.detect_opportunities());
+                // El funding cambia lento; con el barrido periódico alcanza
+                funding_opportunities = funding_detector.detect_opportunities();
+                basis_opportunities = basis_detector.detect_opportunities();
+                // Los triángulos arman el grafo spot de cada exchange: solo en el barrido, no por cada update
+                triangular_opportunities = triangular_detector.detect_opportunities();
+                // Se graban solo cuando se recalculan, igual que las de cada símbolo
+                recorder.record_opportunities(&triangular_opportunities);
+                metrics::detection("sweep", started.elapsed());
+                // Una muestra por segundo de lo que estaba en positivo
+                journal.record_opportunities(opportunities_by_symbol.values().flatten().filter(|op| op.net_profit_pct > 0.0));
+                // Una muestra por segundo para la media móvil del spread
+                convergence.step(|symbol, exchanges| control.allows(symbol, exchanges));
+            }
+            _ = publish.tick() => {
+                let mut opportunities: Vec<ArbitrageOpportunity> = opportunities_by_symbol
+                    .values()
+                    .flatten()
+                    .chain(triangular_opportunities.iter())
+                    .cloned()
+                    .collect();
+                sort_by_profit(&mut opportunities);
+                sim.apply_friction(&mut opportunities);
+
+                // --- CONSTRUIR Y ENVIAR PAYLOAD ---
+                let payload = DashboardPayload {
+                    opportunities,
+                    funding_opportunities: funding_opportunities.clone(),
+                    basis_opportunities: basis_opportunities.clone(),
+                    convergence: convergence.snapshot(),
+                    maker_taker: maker_taker.snapshot(),
+                    fees: fee_config.status(),
+                    resyncing_books: aggregator.get_resyncing(),
+                    feed_latency: aggregator.feed_latency(),
+                    clock: clock.status(),
+                    health: health.report(),
+                    stats: sim.stats(),
+                    recent_trades: sim.recent_trades().to_vec(),
+                    control: control.clone(),
+                };
+
+                metrics::observe_payload(&payload);
+                let payload = Arc::new(payload);
+                latest_tx.send_replace(Some(payload.clone()));
+                let _ = tx.send(payload);
+            }
+            Some(request) = control_rx.recv() => {
+                apply_control(request, &mut control, &mut sim, &mut maker_taker, &mut convergence, &instruments, &journal).await;
+            }
+            _ = snapshot.tick() => {
+                journal.record_balances(&sim.ledger().balances);
+                save_state(&saved_state(&sim, &maker_taker, &convergence, &fee_config, &control));
             }
         }
+    }
 
-        // --- CONSTRUIR Y ENVIAR PAYLOAD ---
-        let payload = DashboardPayload {
-            opportunities, 
-            stats: SimStats {
-                total_usd: sim_balance,
-                binance_usd: b_bal,
-                bybit_usd: by_bal,
-                hyperliquid_usd: hl_bal,
-                extended_usd: ex_bal,
-                trade_count,
-                last_action: last_trade_log.clone(),
-            },
-            recent_trades: recent_trades_list.clone(),
-        };
-
-        let _ = tx.send(payload);
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    // Apagado ordenado. El diario SQLite confirma cada registro en su transacción, así que no queda nada en buffer.
+    maker_taker.cancel_all().await;
+    match state::save(&saved_state(&sim, &maker_taker, &convergence, &fee_config, &control)) {
+        Ok(()) => info!("💾 Estado guardado en {}", state::path()),
+        Err(e) => warn!("⚠️ No se pudo guardar el estado: {:?}", e),
     }
+    supervisor.shutdown(tokio::time::Duration::from_secs(SHUTDOWN_GRACE_SECS)).await;
+    info!("👋 Apagado completo");
 }
 
-async fn handle_socket(ws: warp::ws::WebSocket, mut rx: broadcast::Receiver<DashboardPayload>) {
-    let (mut sender, _) = ws.split();
-    while let Ok(payload) = rx.recv().await {
-        if let Ok(json) = serde_json::to_string(&payload) {
-            if sender.send(warp::ws::Message::text(json)).await.is_err() { break; }
-        }
+fn saved_state(sim: &SimEngine, maker_taker: &MakerTakerStrategy, convergence: &ConvergenceStrategy, fee_config: &FeeConfig, control: &TradingControl) -> SavedState {
+    SavedState {
+        saved_at: chrono::Utc::now().timestamp_millis() as u64,
+        sim: sim.ledger(),
+        maker_taker: maker_taker.snapshot(),
+        convergence: convergence.snapshot(),
+        daily_volume: fee_config.daily_volume(),
+        control: control.clone(),
     }
 }
 
-fn init_csv() {
-    let path = "trades_log.csv";
-    if std::fs::metadata(path).is_err() {
-        let mut wtr = csv::Writer::from_path(path).unwrap();
-        wtr.write_record(&["Timestamp", "Symbol", "BuyEx", "SellEx", "BuyPrice", "SellPrice", "Profit", "Balance", "Note"]).unwrap();
-        wtr.flush().unwrap();
+fn save_state(state: &SavedState) {
+    if let Err(e) = state::save(state) {
+        warn!("⚠️ No se pudo guardar el estado: {:?}", e);
     }
 }
 
-fn load_history_from_csv() -> Vec<TradeLog> {
-    let path = "trades_log.csv";
-    let mut history = Vec::new();
-    
-    if let Ok(file) = std::fs::File::open(path) {
-        let reader = BufReader::new(file);
-        // Saltamos la cabecera y leemos las líneas
-        for line in reader.lines().skip(1) {
-            if let Ok(l) = line {
-                let v: Vec<&str> = l.split(',').collect();
-                if v.len() >= 8 {
-                    history.push(TradeLog {
-                        timestamp: v[0].to_string(),
-                        symbol: v[1].to_string(),
-                        buy_exchange: v[2].to_string(),
-                        sell_exchange: v[3].to_string(),
-                        buy_price: v[4].parse().unwrap_or(0.0),
-                        sell_price: v[5].parse().unwrap_or(0.0),
-                        profit_usd: v[6].parse().unwrap_or(0.0),
-                        balance_after: v[7].parse().unwrap_or(0.0),
-                        note: v.get(8).unwrap_or(&"").to_string(),
-                    });
+fn apply_limits(limits: &RuntimeLimits, sim: &mut SimEngine, maker_taker: &mut MakerTakerStrategy, convergence: &mut ConvergenceStrategy) {
+    sim.set_max_trade_usd(limits.sim_max_trade_usd);
+    maker_taker.set_config(limits.maker_taker);
+    convergence.set_config(limits.convergence);
+}
+
+// Acciones de la API de control, aplicadas entre dos vueltas del loop
+async fn apply_control(
+    request: ControlRequest,
+    control: &mut TradingControl,
+    sim: &mut SimEngine,
+    maker_taker: &mut MakerTakerStrategy,
+    convergence: &mut ConvergenceStrategy,
+    instruments: &InstrumentRegistry,
+    journal: &Journal,
+) {
+    // La recarga va por REST a cada venue: no se frena el loop esperándola
+    if let ControlAction::RefreshInstruments = request.action {
+        let (instruments, journal, status) = (instruments.clone(), journal.clone(), control.clone());
+        tokio::spawn(async move {
+            match instruments.refresh().await {
+                Ok(count) => request.finish_with(&journal, Ok(status), &format!("ok: {} instrumentos", count)),
+                Err(e) => request.finish(&journal, Err(e)),
+            }
+        });
+        return;
+    }
+
+    let result = control.apply(&request.action, &request.actor);
+    if result.is_ok() {
+        match &request.action {
+            ControlAction::SetLimits(_) => apply_limits(&control.limits, sim, maker_taker, convergence),
+            ControlAction::Pause(_) => {
+                maker_taker.cancel_where(|q| !control.allows(&q.symbol, &[q.maker_exchange])).await;
+            }
+            ControlAction::TripKillSwitch { reason } => {
+                warn!("🚨 KILL SWITCH por {}: {}", request.actor, reason);
+                maker_taker.cancel_all().await;
+                convergence.close_all("Kill switch");
+            }
+            _ => {}
+        }
+    }
+    request.finish(journal, result.map(|()| control.clone()));
+}
+
+// Destinos de todo lo que llega de un conector
+#[derive(Clone)]
+struct FeedSinks {
+    aggregator: PriceAggregator,
+    instruments: InstrumentRegistry,
+    health: FeedHealth,
+    recorder: TickRecorder,
+}
+
+// Mapea los símbolos del venue una sola vez y deja el conector bajo el supervisor
+fn supervise_connector<C, F>(
+    supervisor: &mut Supervisor,
+    make_connector: F,
+    market_type: MarketType,
+    symbols: &[String],
+    sinks: &FeedSinks,
+) where
+    C: ExchangeConnector + Send + 'static,
+    F: Fn() -> C + Send + Sync + 'static,
+{
+    let exchange = make_connector().name();
+    let symbol_map = sinks.instruments.symbol_map(exchange, market_type, symbols);
+    sinks.health.register(exchange, market_type, &symbol_map);
+    let sinks = sinks.clone();
+    supervisor.spawn(format!("{:?} {:?}", exchange, market_type), move || {
+        run_connector(make_connector(), market_type, symbol_map.clone(), sinks.clone())
+    });
+}
+
+// Conecta y reenvía libros, funding y eventos de conexión hasta que termina
+// cualquiera de las tareas del conector; ahí el supervisor lo relanza. Las tareas
+// viven en el JoinSet de esta función: al salir (o si el supervisor la aborta al
+// apagar) se abortan con ella.
+// Todo libro pasa por el registro: precios y tamaños en unidades reales (1000PEPE -> PEPE)
+async fn run_connector<C: ExchangeConnector + Send>(
+    mut connector: C,
+    market_type: MarketType,
+    symbol_map: SymbolMap,
+    sinks: FeedSinks,
+) {
+    let FeedSinks { aggregator, instruments, health, recorder } = sinks;
+    let exchange = connector.name();
+    let mut tasks = match connector.connect(symbol_map).await {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            warn!("⚠️ No se pudo conectar {:?} {:?}: {:?}", exchange, market_type, e);
+            return;
+        }
+    };
+    let mut rx = connector.get_receiver();
+    let mut funding_rx = connector.get_funding_receiver();
+    let mut event_rx = connector.get_event_receiver();
+    // Sin esto los canales nunca se cerrarían: el conector guarda su propio sender
+    drop(connector);
+
+    loop {
+        tokio::select! {
+            Some(u) = rx.recv() => {
+                let u = instruments.normalize(u);
+                health.on_book(u.exchange, u.market_type, &u.symbol);
+                recorder.record_book(&u);
+                aggregator.update(u.symbol.clone(), u.exchange, u.market_type, MarketBook::from(u));
+            }
+            Some(f) = funding_rx.recv() => aggregator.update_funding(f.symbol.clone(), f.exchange, FundingInfo::from(f)),
+            Some(event) = event_rx.recv() => health.on_event(exchange, market_type, event),
+            Some(result) = tasks.join_next() => {
+                if let Err(e) = result {
+                    warn!("⚠️ Tarea de {:?} {:?} cayó: {:?}", exchange, market_type, e);
                 }
+                break;
             }
+            else => break,
         }
     }
-    // Invertimos para que los más nuevos estén arriba y limitamos a 10
-    history.reverse();
-    history.truncate(10);
-    history
 }
 
-fn log_trade_to_csv(trade: TradeLog) {
-    let file = OpenOptions::new().write(true).append(true).open("trades_log.csv").unwrap();
-    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(file);
-    wtr.serialize(trade).unwrap();
-    wtr.flush().unwrap();
+fn group_by_symbol(opportunities: Vec<ArbitrageOpportunity>) -> HashMap<String, Vec<ArbitrageOpportunity>> {
+    let mut grouped: HashMap<String, Vec<ArbitrageOpportunity>> = HashMap::new();
+    for op in opportunities {
+        grouped.entry(op.symbol.clone()).or_default().push(op);
+    }
+    grouped
+}
+
+// Valor que sigue a un flag de la línea de comandos
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()
 }
 
 impl From<exchanges::BookUpdate> for MarketBook {
     fn from(u: exchanges::BookUpdate) -> Self {
-        MarketBook { bid: u.bid, ask: u.ask, bid_size: u.bid_size, ask_size: u.ask_size, timestamp: u.timestamp }
+        MarketBook { bid: u.bid, ask: u.ask, bid_size: u.bid_size, ask_size: u.ask_size, exchange_ts: u.exchange_ts, received_at: u.received_at, fx: None, integrity: u.integrity }
     }
-}
\ No newline at end of file
+}
+
+impl From<exchanges::FundingUpdate> for FundingInfo {
+    fn from(f: exchanges::FundingUpdate) -> Self {
+        FundingInfo { rate: f.rate, interval_hours: f.exchange.funding_interval_hours(), next_funding_time: f.next_funding_time, timestamp: f.timestamp }
+    }
+}
@@ -0,0 +1,234 @@
+// src/metrics.rs
+//
+// Métricas en formato Prometheus, servidas en GET /metrics (rol viewer: en el
+// scrape config van `basic_auth` o `authorization` si hay usuarios).
+//
+// Los contadores e histogramas se actualizan donde ocurre cada cosa (libros en
+// el agregador, reconexiones en health, trades y órdenes en el diario, topes en
+// el simulador y el loop). Balances, oportunidades abiertas y órdenes vivas se
+// toman de cada foto que publica el loop; el scrape solo lee.
+
+use crate::api::DashboardPayload;
+use crate::arbitrage::detector::OpportunityKind;
+use crate::exchanges::{Exchange, MarketType};
+use crate::journal::OrderStatus;
+use prometheus::{Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+const NAMESPACE: &str = "arb";
+
+// Latencia de feeds: de un par de ms (mismo datacenter) a segundos (venue atrasado)
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+// Una vuelta de detección normal está muy por debajo del milisegundo
+const DETECTION_BUCKETS: &[f64] = &[0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1];
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY.register(Box::new(metric.clone())).expect("métrica registrada dos veces");
+    metric
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+
+fn histogram_opts(name: &str, help: &str, buckets: &[f64]) -> HistogramOpts {
+    HistogramOpts::new(name, help).namespace(NAMESPACE).buckets(buckets.to_vec())
+}
+
+static BOOK_UPDATES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(opts("book_updates_total", "Updates de libro recibidos"), &["exchange", "market"]).unwrap())
+});
+
+static BOOK_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            histogram_opts("book_latency_seconds", "Recepción local menos hora del venue (reloj corregido)", LATENCY_BUCKETS),
+            &["exchange"],
+        )
+        .unwrap(),
+    )
+});
+
+static FEED_RECONNECTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(opts("feed_reconnects_total", "Conexiones de feed caídas"), &["exchange", "market"]).unwrap())
+});
+
+static DETECTION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            histogram_opts("detection_seconds", "Duración de una vuelta de detección (event: por símbolo, sweep: barrido completo)", DETECTION_BUCKETS),
+            &["loop"],
+        )
+        .unwrap(),
+    )
+});
+
+static OPPORTUNITIES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register(IntGaugeVec::new(opts("opportunities_open", "Oportunidades con profit neto positivo"), &["kind", "symbol", "pair"]).unwrap())
+});
+
+static OPPORTUNITY_BEST: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register(GaugeVec::new(opts("opportunity_best_net_pct", "Mejor profit neto (%) entre las oportunidades abiertas"), &["kind", "symbol", "pair"]).unwrap())
+});
+
+static TRADES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(opts("trades_total", "Trades cerrados (incluye el diario previo al arranque)"), &["strategy"]).unwrap())
+});
+
+static PNL: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register(GaugeVec::new(opts("pnl_usd", "PnL realizado acumulado según el diario"), &["strategy"]).unwrap())
+});
+
+static BALANCES: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register(GaugeVec::new(opts("balance_usd", "Balance simulado por venue"), &["exchange"]).unwrap())
+});
+
+static ORDERS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(opts("orders_total", "Órdenes por estado"), &["strategy", "exchange", "status"]).unwrap())
+});
+
+static ORDERS_OPEN: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register(IntGaugeVec::new(opts("orders_open", "Órdenes pasivas vivas"), &["exchange"]).unwrap())
+});
+
+static RISK_LIMIT_HITS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(opts("risk_limit_hits_total", "Veces que un tope o control frenó o recortó una operación"), &["limit"]).unwrap())
+});
+
+// (kind, symbol, pair) de una serie de oportunidades
+type OpportunityLabels = (&'static str, String, String);
+
+// Series de oportunidades de la última foto, para borrar las que ya no están
+static OPEN_SERIES: LazyLock<Mutex<HashSet<OpportunityLabels>>> = LazyLock::new(Default::default);
+
+// Tope o control que frenó o achicó una operación
+#[derive(Debug, Clone, Copy)]
+pub enum RiskLimit {
+    MaxTradeUsd, // El tamaño del trade quedó recortado por sim_max_trade_usd
+    Balance,     // Trade rentable sin balance suficiente en el venue de compra
+    Paused,      // La mejor oportunidad positiva cayó en un símbolo o venue pausado
+    KillSwitch,
+    MaxHold, // Convergencia cerrada por tiempo
+}
+
+impl RiskLimit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RiskLimit::MaxTradeUsd => "max_trade_usd",
+            RiskLimit::Balance => "balance",
+            RiskLimit::Paused => "paused",
+            RiskLimit::KillSwitch => "kill_switch",
+            RiskLimit::MaxHold => "max_hold",
+        }
+    }
+}
+
+pub fn book_update(exchange: Exchange, market_type: MarketType, latency_ms: Option<i64>) {
+    BOOK_UPDATES.with_label_values(&[exchange.as_str(), market_label(market_type)]).inc();
+    if let Some(latency_ms) = latency_ms {
+        // Relojes mal corregidos pueden dar negativo: cuenta en el primer bucket
+        BOOK_LATENCY.with_label_values(&[exchange.as_str()]).observe(latency_ms.max(0) as f64 / 1000.0);
+    }
+}
+
+pub fn feed_reconnect(exchange: Exchange, market_type: MarketType) {
+    FEED_RECONNECTS.with_label_values(&[exchange.as_str(), market_label(market_type)]).inc();
+}
+
+pub fn detection(name: &str, elapsed: Duration) {
+    DETECTION.with_label_values(&[name]).observe(elapsed.as_secs_f64());
+}
+
+pub fn trade(strategy: &str, profit_usd: f64) {
+    TRADES.with_label_values(&[strategy]).inc();
+    PNL.with_label_values(&[strategy]).add(profit_usd);
+}
+
+// Arranque: lo que ya estaba en el diario, para que el PnL no vuelva a cero al reiniciar
+pub fn seed_trades(totals: &[(String, u64, f64)]) {
+    for (strategy, count, pnl) in totals {
+        TRADES.with_label_values(&[strategy]).inc_by(*count);
+        PNL.with_label_values(&[strategy]).set(*pnl);
+    }
+}
+
+pub fn order(strategy: &str, exchange: Exchange, status: OrderStatus) {
+    ORDERS.with_label_values(&[strategy, exchange.as_str(), status.as_str()]).inc();
+}
+
+pub fn risk_limit(limit: RiskLimit) {
+    RISK_LIMIT_HITS.with_label_values(&[limit.as_str()]).inc();
+}
+
+fn market_label(market_type: MarketType) -> &'static str {
+    match market_type {
+        MarketType::Spot => "spot",
+        MarketType::Perp => "perp",
+    }
+}
+
+// Lo que se lee de cada foto publicada. Las series que desaparecen se borran de a
+// una (sin reset) para que un scrape concurrente nunca vea los gauges vacíos.
+pub fn observe_payload(payload: &DashboardPayload) {
+    let stats = &payload.stats;
+    for (exchange, balance) in [
+        (Exchange::Binance, stats.binance_usd),
+        (Exchange::Bybit, stats.bybit_usd),
+        (Exchange::Hyperliquid, stats.hyperliquid_usd),
+        (Exchange::Extended, stats.extended_usd),
+    ] {
+        BALANCES.with_label_values(&[exchange.as_str()]).set(balance);
+    }
+
+    let mut open: HashMap<OpportunityLabels, (i64, f64)> = HashMap::new();
+    let arbitrage = payload.opportunities.iter().filter(|o| o.net_profit_pct > 0.0).map(|o| {
+        let kind = match o.kind {
+            OpportunityKind::CrossVenue => "cross_venue",
+            OpportunityKind::Triangular => "triangular",
+        };
+        ((kind, o.symbol.clone(), format!("{}>{}", o.buy_exchange.as_str(), o.sell_exchange.as_str())), o.net_profit_pct)
+    });
+    let funding = payload.funding_opportunities.iter().filter(|o| o.net_edge_pct > 0.0).map(|o| {
+        (("funding", o.symbol.clone(), format!("{}>{}", o.long_exchange.as_str(), o.short_exchange.as_str())), o.net_edge_pct)
+    });
+    let basis = payload.basis_opportunities.iter().filter(|o| o.net_basis_pct > 0.0).map(|o| {
+        (("basis", o.symbol.clone(), format!("{}>{}", o.spot_exchange.as_str(), o.perp_exchange.as_str())), o.net_basis_pct)
+    });
+    for (key, net_pct) in arbitrage.chain(funding).chain(basis) {
+        let entry = open.entry(key).or_insert((0, f64::NEG_INFINITY));
+        entry.0 += 1;
+        entry.1 = entry.1.max(net_pct);
+    }
+    for ((kind, symbol, pair), (count, best)) in &open {
+        OPPORTUNITIES.with_label_values(&[kind, symbol.as_str(), pair.as_str()]).set(*count);
+        OPPORTUNITY_BEST.with_label_values(&[kind, symbol.as_str(), pair.as_str()]).set(*best);
+    }
+    let mut series = OPEN_SERIES.lock().unwrap();
+    for (kind, symbol, pair) in series.iter().filter(|key| !open.contains_key(*key)) {
+        let _ = OPPORTUNITIES.remove_label_values(&[kind, symbol.as_str(), pair.as_str()]);
+        let _ = OPPORTUNITY_BEST.remove_label_values(&[kind, symbol.as_str(), pair.as_str()]);
+    }
+    *series = open.into_keys().collect();
+
+    let mut resting: HashMap<Exchange, i64> = HashMap::new();
+    for quote in &payload.maker_taker.resting {
+        *resting.entry(quote.maker_exchange).or_default() += 1;
+    }
+    for exchange in Exchange::ALL {
+        ORDERS_OPEN.with_label_values(&[exchange.as_str()]).set(resting.get(&exchange).copied().unwrap_or(0));
+    }
+}
+
+// Texto para el scrape: solo lee el registro
+pub fn render() -> (String, String) {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::warn!("⚠️ No se pudieron codificar las métricas: {:?}", e);
+    }
+    (encoder.format_type().to_string(), String::from_utf8(buffer).unwrap_or_default())
+}
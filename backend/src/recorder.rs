@@ -0,0 +1,403 @@
+// src/recorder.rs
+//
+// Grabación del stream de libros normalizados y de cada oportunidad detectada
+// en Parquet, particionado estilo Hive para leerlo directo con pyarrow o DataFusion:
+//   books/date=2024-01-31/exchange=Binance/symbol=BTC-USDT/part-<ms>.parquet
+//   opportunities/date=2024-01-31/symbol=BTC-USDT/part-<ms>.parquet
+// El motor solo encola (try_send, nunca espera); armar columnas, comprimir y
+// escribir a disco va en una tarea aparte.
+
+use crate::arbitrage::ArbitrageOpportunity;
+use crate::exchanges::{BookUpdate, Level};
+use crate::latency::wall_clock_ms;
+use crate::supervisor::ShutdownSignal;
+use anyhow::Result;
+use arrow_array::builder::{Float64Builder, ListBuilder};
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+
+// Ticks en cola antes de empezar a descartar (el motor no se frena por el disco)
+const CHANNEL_CAPACITY: usize = 100_000;
+const FLUSH_INTERVAL_SECS: u64 = 60;
+// Filas por row group: se escribe antes del flush si una partición llega a esto
+const ROW_GROUP_ROWS: usize = 10_000;
+// Un archivo por partición cada tanto: solo los cerrados (con footer) quedan visibles
+const ROTATE_SECS: u64 = 900;
+
+#[derive(Debug, Clone)]
+pub struct BookTick {
+    pub symbol: String,
+    pub exchange: String,
+    pub market_type: String,
+    pub exchange_ts: Option<u64>, // Reloj del venue
+    pub received_ts: u64,         // Nuestro reloj (ms UTC)
+    pub integrity: String,
+    pub bid: f64,
+    pub ask: f64,
+    pub bid_size: f64,
+    pub ask_size: f64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+enum Record {
+    Book(BookTick),
+    Opportunity(Box<ArbitrageOpportunity>),
+}
+
+// Handle del lado del motor. Sin directorio configurado no graba nada.
+#[derive(Clone)]
+pub struct TickRecorder {
+    tx: Option<mpsc::Sender<Record>>,
+    dropped: Arc<AtomicU64>,
+}
+
+// Lado que escribe: corre bajo el supervisor y cierra los archivos al apagar
+#[derive(Clone)]
+pub struct TickWriter {
+    dir: PathBuf,
+    rx: Arc<Mutex<mpsc::Receiver<Record>>>,
+    dropped: Arc<AtomicU64>,
+}
+
+pub fn channel(dir: impl Into<PathBuf>) -> (TickRecorder, TickWriter) {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+    (
+        TickRecorder { tx: Some(tx), dropped: dropped.clone() },
+        TickWriter { dir: dir.into(), rx: Arc::new(Mutex::new(rx)), dropped },
+    )
+}
+
+impl TickRecorder {
+    pub fn disabled() -> Self {
+        Self { tx: None, dropped: Arc::new(AtomicU64::new(0)) }
+    }
+
+    fn send(&self, record: Record) {
+        if let Some(tx) = &self.tx {
+            if tx.try_send(record).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn record_book(&self, update: &BookUpdate) {
+        if self.tx.is_none() {
+            return;
+        }
+        self.send(Record::Book(BookTick {
+            symbol: update.symbol.clone(),
+            exchange: update.exchange.as_str().to_string(),
+            market_type: format!("{:?}", update.market_type),
+            exchange_ts: update.exchange_ts,
+            received_ts: wall_clock_ms(update.received_at),
+            integrity: format!("{:?}", update.integrity),
+            bid: update.bid,
+            ask: update.ask,
+            bid_size: update.bid_size,
+            ask_size: update.ask_size,
+            bids: update.bids.clone(),
+            asks: update.asks.clone(),
+        }));
+    }
+
+    pub fn record_opportunities<'a>(&self, opportunities: impl IntoIterator<Item = &'a ArbitrageOpportunity>) {
+        if self.tx.is_none() {
+            return;
+        }
+        for op in opportunities {
+            self.send(Record::Opportunity(Box::new(op.clone())));
+        }
+    }
+}
+
+impl TickWriter {
+    pub async fn run(self, mut signal: ShutdownSignal) {
+        // Un solo escritor a la vez; si la tarea se relanza retoma la misma cola
+        let mut rx = self.rx.lock().await;
+        let mut books: Partitions<BookTick> = Partitions::new(self.dir.join("books"));
+        let mut opportunities: Partitions<ArbitrageOpportunity> = Partitions::new(self.dir.join("opportunities"));
+        let mut flush = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+        tracing::info!("📼 Grabando ticks en {}", self.dir.display());
+
+        loop {
+            tokio::select! {
+                record = rx.recv() => match record {
+                    Some(Record::Book(tick)) => books.push(book_partition(&tick), tick),
+                    Some(Record::Opportunity(op)) => opportunities.push(opportunity_partition(&op), *op),
+                    None => break,
+                },
+                _ = flush.tick() => {
+                    let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        tracing::warn!("⚠️ Grabador saturado: {} ticks descartados", dropped);
+                    }
+                    tokio::task::block_in_place(|| {
+                        books.flush(false);
+                        opportunities.flush(false);
+                    });
+                }
+                _ = signal.wait() => {
+                    // Lo que ya estaba en cola también se graba
+                    while let Ok(record) = rx.try_recv() {
+                        match record {
+                            Record::Book(tick) => books.push(book_partition(&tick), tick),
+                            Record::Opportunity(op) => opportunities.push(opportunity_partition(&op), *op),
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+
+        tokio::task::block_in_place(|| {
+            books.flush(true);
+            opportunities.flush(true);
+        });
+        tracing::info!("📼 Grabación cerrada");
+    }
+}
+
+// Una partición por directorio (`date=/exchange=/symbol=`)
+fn book_partition(tick: &BookTick) -> PathBuf {
+    PathBuf::from(format!("date={}", date_of(tick.received_ts)))
+        .join(format!("exchange={}", tick.exchange))
+        .join(format!("symbol={}", path_safe(&tick.symbol)))
+}
+
+fn opportunity_partition(op: &ArbitrageOpportunity) -> PathBuf {
+    PathBuf::from(format!("date={}", date_of(op.created_at)))
+        .join(format!("symbol={}", path_safe(&op.symbol)))
+}
+
+fn date_of(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Los triangulares usan "A/B/USDT" como símbolo
+fn path_safe(symbol: &str) -> String {
+    symbol.replace(['/', '\\'], "_")
+}
+
+// Filas que saben convertirse a un RecordBatch con un esquema fijo
+trait TickRow: Sized {
+    fn schema() -> SchemaRef;
+    fn batch(rows: &[Self]) -> Result<RecordBatch>;
+}
+
+fn utc_ms() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+}
+
+fn float_list() -> DataType {
+    DataType::List(Arc::new(Field::new("item", DataType::Float64, true)))
+}
+
+fn level_column(rows: &[BookTick], side: impl Fn(&BookTick) -> &[Level], value: impl Fn(&Level) -> f64) -> ArrayRef {
+    let mut builder = ListBuilder::new(Float64Builder::new());
+    for row in rows {
+        for level in side(row) {
+            builder.values().append_value(value(level));
+        }
+        builder.append(true);
+    }
+    Arc::new(builder.finish())
+}
+
+static BOOK_SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("received_ts", utc_ms(), false),
+        Field::new("exchange_ts", utc_ms(), true),
+        Field::new("market_type", DataType::Utf8, false),
+        Field::new("integrity", DataType::Utf8, false),
+        Field::new("bid", DataType::Float64, false),
+        Field::new("ask", DataType::Float64, false),
+        Field::new("bid_size", DataType::Float64, false),
+        Field::new("ask_size", DataType::Float64, false),
+        // Niveles mejor primero; precio y cantidad en listas paralelas
+        Field::new("bid_prices", float_list(), false),
+        Field::new("bid_sizes", float_list(), false),
+        Field::new("ask_prices", float_list(), false),
+        Field::new("ask_sizes", float_list(), false),
+    ]))
+});
+
+impl TickRow for BookTick {
+    fn schema() -> SchemaRef {
+        BOOK_SCHEMA.clone()
+    }
+
+    fn batch(rows: &[Self]) -> Result<RecordBatch> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from_iter_values(rows.iter().map(|r| r.received_ts as i64)).with_timezone("UTC")),
+            Arc::new(TimestampMillisecondArray::from_iter(rows.iter().map(|r| r.exchange_ts.map(|t| t as i64))).with_timezone("UTC")),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.market_type.as_str()))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.integrity.as_str()))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.bid))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.ask))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.bid_size))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.ask_size))),
+            level_column(rows, |r| &r.bids, |l| l.0),
+            level_column(rows, |r| &r.bids, |l| l.1),
+            level_column(rows, |r| &r.asks, |l| l.0),
+            level_column(rows, |r| &r.asks, |l| l.1),
+        ];
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+}
+
+static OPPORTUNITY_SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("created_at", utc_ms(), false),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("buy_exchange", DataType::Utf8, false),
+        Field::new("sell_exchange", DataType::Utf8, false),
+        Field::new("buy_price", DataType::Float64, false),
+        Field::new("sell_price", DataType::Float64, false),
+        Field::new("spread_pct", DataType::Float64, false),
+        Field::new("total_fees_pct", DataType::Float64, false),
+        Field::new("net_profit_pct", DataType::Float64, false),
+        Field::new("net_profit_usd", DataType::Float64, false),
+        Field::new("max_tradeable_qty", DataType::Float64, false),
+        Field::new("max_tradeable_usd", DataType::Float64, false),
+        Field::new("liquidity_bottleneck", DataType::Utf8, false),
+        Field::new("data_age_ms", DataType::Int64, false),
+    ]))
+});
+
+impl TickRow for ArbitrageOpportunity {
+    fn schema() -> SchemaRef {
+        OPPORTUNITY_SCHEMA.clone()
+    }
+
+    fn batch(rows: &[Self]) -> Result<RecordBatch> {
+        let f64_col = |f: fn(&ArbitrageOpportunity) -> f64| -> ArrayRef { Arc::new(Float64Array::from_iter_values(rows.iter().map(f))) };
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from_iter_values(rows.iter().map(|r| r.created_at as i64)).with_timezone("UTC")),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| format!("{:?}", r.kind)))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.buy_exchange.as_str()))),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.sell_exchange.as_str()))),
+            f64_col(|r| r.buy_price),
+            f64_col(|r| r.sell_price),
+            f64_col(|r| r.spread_pct),
+            f64_col(|r| r.total_fees_pct),
+            f64_col(|r| r.net_profit_pct),
+            f64_col(|r| r.net_profit_usd),
+            f64_col(|r| r.max_tradeable_qty),
+            f64_col(|r| r.max_tradeable_usd),
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.liquidity_bottleneck.as_str()))),
+            Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.data_age_ms as i64))),
+        ];
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+}
+
+// Archivo abierto de una partición. Se escribe con punto adelante (los lectores
+// lo ignoran) y se renombra recién al cerrarlo con footer.
+struct OpenFile {
+    writer: ArrowWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+    opened_at: Instant,
+}
+
+impl OpenFile {
+    fn create(dir: &Path, schema: SchemaRef) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let name = format!("part-{}.parquet", chrono::Utc::now().timestamp_millis());
+        let tmp = dir.join(format!(".{}.tmp", name));
+        let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let writer = ArrowWriter::try_new(File::create(&tmp)?, schema, Some(props))?;
+        Ok(Self { writer, tmp, path: dir.join(name), opened_at: Instant::now() })
+    }
+
+    fn close(self) -> Result<()> {
+        self.writer.close()?;
+        std::fs::rename(&self.tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+struct Partition<T> {
+    rows: Vec<T>,
+    file: Option<OpenFile>,
+}
+
+struct Partitions<T> {
+    root: PathBuf,
+    parts: HashMap<PathBuf, Partition<T>>,
+}
+
+impl<T: TickRow> Partitions<T> {
+    fn new(root: PathBuf) -> Self {
+        Self { root, parts: HashMap::new() }
+    }
+
+    // Partición muy activa: su row group se escribe apenas se llena, sin esperar al flush
+    fn push(&mut self, key: PathBuf, row: T) {
+        match self.parts.get_mut(&key) {
+            Some(part) => {
+                part.rows.push(row);
+                if part.rows.len() >= ROW_GROUP_ROWS {
+                    tokio::task::block_in_place(|| self.write(&key));
+                }
+            }
+            None => {
+                self.parts.insert(key, Partition { rows: vec![row], file: None });
+            }
+        }
+    }
+
+    // Escribe todo lo pendiente; cierra los archivos viejos (o todos si `close_all`)
+    fn flush(&mut self, close_all: bool) {
+        let keys: Vec<PathBuf> = self.parts.keys().cloned().collect();
+        for key in keys {
+            self.write(&key);
+            let Some(part) = self.parts.get_mut(&key) else { continue };
+            let expired = part.file.as_ref().is_some_and(|f| f.opened_at.elapsed() >= Duration::from_secs(ROTATE_SECS));
+            if close_all || expired {
+                if let Some(file) = part.file.take() {
+                    if let Err(e) = file.close() {
+                        tracing::warn!("⚠️ Grabador: no se pudo cerrar {}: {:?}", key.display(), e);
+                    }
+                }
+                // Partición sin archivo ni filas (p. ej. el día anterior): se olvida
+                self.parts.remove(&key);
+            }
+        }
+    }
+
+    fn write(&mut self, key: &Path) {
+        let Some(part) = self.parts.get_mut(key) else { return };
+        if part.rows.is_empty() {
+            return;
+        }
+        let rows = std::mem::take(&mut part.rows);
+        let result = (|| -> Result<()> {
+            if part.file.is_none() {
+                part.file = Some(OpenFile::create(&self.root.join(key), T::schema())?);
+            }
+            let batch = T::batch(&rows)?;
+            part.file.as_mut().unwrap().writer.write(&batch)?;
+            // Cierra el row group: las filas no se acumulan en memoria hasta rotar
+            part.file.as_mut().unwrap().writer.flush()?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            tracing::warn!("⚠️ Grabador: {} filas perdidas en {}: {:?}", rows.len(), key.display(), e);
+        }
+    }
+}
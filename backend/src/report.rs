@@ -0,0 +1,483 @@
+// src/report.rs
+//
+// Reporte de resultados a partir del diario: PnL acumulado y diario, drawdown,
+// Sharpe/Sortino, win rate, edge capturado contra detectado y desgloses por
+// símbolo, par de exchanges y hora del día. Sale como tabla de terminal, JSON
+// o un HTML autocontenido (sin JS ni recursos externos).
+//
+//   arbitrage-bot --report [table|json|html] [--strategy simulador] [--out reporte.html]
+
+use crate::journal::{Journal, JournalTrade};
+use anyhow::{anyhow, Result};
+use chrono::Timelike;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Cripto opera todos los días: anualizamos con 365
+const TRADING_DAYS_PER_YEAR: f64 = 365.0;
+const DEFAULT_HTML_PATH: &str = "report.html";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            other => Err(anyhow!("formato desconocido '{}' (table, json o html)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PnlPoint {
+    pub trade_id: i64,
+    pub executed_at: Option<u64>,
+    pub cumulative_pnl_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyPnl {
+    pub date: String, // UTC
+    pub trades: usize,
+    pub pnl_usd: f64,
+    pub cumulative_pnl_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Breakdown {
+    pub key: String,
+    pub trades: usize,
+    pub pnl_usd: f64,
+    pub win_rate_pct: f64,
+    pub avg_captured_edge_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub generated_at: u64,
+    pub strategy: Option<String>,
+    pub trades: usize,
+    pub undated_trades: usize, // Importados del CSV viejo: sin fecha, fuera de diario/hora/Sharpe
+    pub total_pnl_usd: f64,
+    pub win_rate_pct: f64,
+    pub avg_pnl_usd: f64,
+    pub max_drawdown_usd: f64,
+    pub sharpe: Option<f64>,  // Anualizado sobre PnL diario
+    pub sortino: Option<f64>,
+    pub avg_captured_edge_pct: Option<f64>,
+    pub avg_detected_edge_pct: Option<f64>,
+    pub capture_ratio: Option<f64>, // Capturado / detectado, sobre los trades que tienen ambos
+    pub cumulative: Vec<PnlPoint>,
+    pub daily: Vec<DailyPnl>,
+    pub by_strategy: Vec<Breakdown>,
+    pub by_symbol: Vec<Breakdown>,
+    pub by_pair: Vec<Breakdown>,
+    pub by_hour: Vec<Breakdown>, // Hora UTC
+}
+
+// Edge realmente capturado: profit sobre el nocional comprado
+fn captured_edge_pct(t: &JournalTrade) -> Option<f64> {
+    t.notional_usd.filter(|n| *n > 0.0).map(|n| t.profit_usd / n * 100.0)
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn win_rate(trades: &[&JournalTrade]) -> f64 {
+    if trades.is_empty() {
+        return 0.0;
+    }
+    trades.iter().filter(|t| t.profit_usd > 0.0).count() as f64 / trades.len() as f64 * 100.0
+}
+
+// Mayor caída desde un máximo de la curva de PnL acumulado (empieza en 0)
+fn max_drawdown(cumulative: &[PnlPoint]) -> f64 {
+    let mut peak = 0.0_f64;
+    let mut worst = 0.0_f64;
+    for p in cumulative {
+        peak = peak.max(p.cumulative_pnl_usd);
+        worst = worst.max(peak - p.cumulative_pnl_usd);
+    }
+    worst
+}
+
+// Sharpe y Sortino sobre el PnL diario (tasa libre de riesgo 0). Necesitan al menos dos días.
+fn sharpe_sortino(daily: &[DailyPnl]) -> (Option<f64>, Option<f64>) {
+    if daily.len() < 2 {
+        return (None, None);
+    }
+    let pnl: Vec<f64> = daily.iter().map(|d| d.pnl_usd).collect();
+    let n = pnl.len() as f64;
+    let avg = pnl.iter().sum::<f64>() / n;
+    let std = (pnl.iter().map(|p| (p - avg).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    let downside = (pnl.iter().map(|p| p.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+    let annualize = TRADING_DAYS_PER_YEAR.sqrt();
+    let sharpe = (std > 0.0).then(|| avg / std * annualize);
+    let sortino = (downside > 0.0).then(|| avg / downside * annualize);
+    (sharpe, sortino)
+}
+
+fn breakdown<F: Fn(&JournalTrade) -> Option<String>>(trades: &[JournalTrade], key: F) -> Vec<Breakdown> {
+    let mut groups: BTreeMap<String, Vec<&JournalTrade>> = BTreeMap::new();
+    for t in trades {
+        if let Some(k) = key(t) {
+            groups.entry(k).or_default().push(t);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(key, group)| {
+            let captured: Vec<f64> = group.iter().filter_map(|t| captured_edge_pct(t)).collect();
+            Breakdown {
+                key,
+                trades: group.len(),
+                pnl_usd: group.iter().map(|t| t.profit_usd).sum(),
+                win_rate_pct: win_rate(&group),
+                avg_captured_edge_pct: mean(&captured),
+            }
+        })
+        .collect()
+}
+
+fn utc(ms: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+}
+
+pub fn build(trades: &[JournalTrade], strategy: Option<&str>) -> Report {
+    let all: Vec<&JournalTrade> = trades.iter().collect();
+    let total_pnl_usd: f64 = trades.iter().map(|t| t.profit_usd).sum();
+
+    let mut running = 0.0;
+    let cumulative: Vec<PnlPoint> = trades
+        .iter()
+        .map(|t| {
+            running += t.profit_usd;
+            PnlPoint { trade_id: t.id, executed_at: t.executed_at, cumulative_pnl_usd: running }
+        })
+        .collect();
+
+    let mut days: BTreeMap<chrono::NaiveDate, (usize, f64)> = BTreeMap::new();
+    for t in trades {
+        if let Some(day) = t.executed_at.and_then(utc) {
+            let entry = days.entry(day.date_naive()).or_default();
+            entry.0 += 1;
+            entry.1 += t.profit_usd;
+        }
+    }
+    // Los días sin trades entran con PnL 0: si no, Sharpe y Sortino solo ven los días activos
+    if let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) {
+        for day in first.iter_days().take_while(|d| *d <= last) {
+            days.entry(day).or_default();
+        }
+    }
+    let mut running = 0.0;
+    let daily: Vec<DailyPnl> = days
+        .into_iter()
+        .map(|(date, (count, pnl))| {
+            running += pnl;
+            DailyPnl { date: date.format("%Y-%m-%d").to_string(), trades: count, pnl_usd: pnl, cumulative_pnl_usd: running }
+        })
+        .collect();
+    let (sharpe, sortino) = sharpe_sortino(&daily);
+
+    // Promedios de edge solo sobre trades con ambos datos, para que sean comparables
+    let edges: Vec<(f64, f64)> = trades
+        .iter()
+        .filter_map(|t| Some((captured_edge_pct(t)?, t.detected_edge_pct?)))
+        .collect();
+    let avg_captured_edge_pct = mean(&edges.iter().map(|e| e.0).collect::<Vec<_>>());
+    let avg_detected_edge_pct = mean(&edges.iter().map(|e| e.1).collect::<Vec<_>>());
+    let capture_ratio = match (avg_captured_edge_pct, avg_detected_edge_pct) {
+        (Some(c), Some(d)) if d != 0.0 => Some(c / d),
+        _ => None,
+    };
+
+    Report {
+        generated_at: chrono::Utc::now().timestamp_millis() as u64,
+        strategy: strategy.map(str::to_string),
+        trades: trades.len(),
+        undated_trades: trades.iter().filter(|t| t.executed_at.is_none()).count(),
+        total_pnl_usd,
+        win_rate_pct: win_rate(&all),
+        avg_pnl_usd: if trades.is_empty() { 0.0 } else { total_pnl_usd / trades.len() as f64 },
+        max_drawdown_usd: max_drawdown(&cumulative),
+        sharpe,
+        sortino,
+        avg_captured_edge_pct,
+        avg_detected_edge_pct,
+        capture_ratio,
+        by_strategy: breakdown(trades, |t| Some(t.strategy.clone())),
+        by_symbol: breakdown(trades, |t| Some(t.symbol.clone())),
+        by_pair: breakdown(trades, |t| Some(format!("{} -> {}", t.buy_exchange, t.sell_exchange))),
+        by_hour: breakdown(trades, |t| t.executed_at.and_then(utc).map(|d| format!("{:02}h", d.hour()))),
+        cumulative,
+        daily,
+    }
+}
+
+fn opt(v: Option<f64>, decimals: usize) -> String {
+    v.map(|x| format!("{:.*}", decimals, x)).unwrap_or_else(|| "-".to_string())
+}
+
+fn push_table(out: &mut String, title: &str, headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[String]| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            // Primera columna a la izquierda, números a la derecha
+            .map(|(i, (c, w))| if i == 0 { format!("{:<w$}", c, w = *w) } else { format!("{:>w$}", c, w = *w) })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    let _ = writeln!(out, "\n{}", title);
+    let _ = writeln!(out, "{}", line(&headers.iter().map(|h| h.to_string()).collect::<Vec<_>>()));
+    let _ = writeln!(out, "{}", widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "));
+    for row in rows {
+        let _ = writeln!(out, "{}", line(row));
+    }
+}
+
+fn breakdown_rows(rows: &[Breakdown]) -> Vec<Vec<String>> {
+    rows.iter()
+        .map(|b| vec![b.key.clone(), b.trades.to_string(), format!("{:.4}", b.pnl_usd), format!("{:.1}", b.win_rate_pct), opt(b.avg_captured_edge_pct, 4)])
+        .collect()
+}
+
+fn summary_rows(r: &Report) -> Vec<(&'static str, String)> {
+    vec![
+        ("Trades", format!("{} ({} sin fecha)", r.trades, r.undated_trades)),
+        ("PnL total (USD)", format!("{:.4}", r.total_pnl_usd)),
+        ("PnL medio por trade (USD)", format!("{:.4}", r.avg_pnl_usd)),
+        ("Win rate (%)", format!("{:.1}", r.win_rate_pct)),
+        ("Max drawdown (USD)", format!("{:.4}", r.max_drawdown_usd)),
+        ("Sharpe (anual, diario)", opt(r.sharpe, 2)),
+        ("Sortino (anual, diario)", opt(r.sortino, 2)),
+        ("Edge detectado medio (%)", opt(r.avg_detected_edge_pct, 4)),
+        ("Edge capturado medio (%)", opt(r.avg_captured_edge_pct, 4)),
+        ("Capturado / detectado", opt(r.capture_ratio, 2)),
+    ]
+}
+
+const BREAKDOWN_HEADERS: [&str; 5] = ["", "Trades", "PnL USD", "Win %", "Edge cap. %"];
+
+pub fn to_table(r: &Report) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Reporte de trades{}", r.strategy.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default());
+    let summary: Vec<Vec<String>> = summary_rows(r).into_iter().map(|(k, v)| vec![k.to_string(), v]).collect();
+    push_table(&mut out, "Resumen", &["Métrica", "Valor"], &summary);
+    let daily: Vec<Vec<String>> = r
+        .daily
+        .iter()
+        .map(|d| vec![d.date.clone(), d.trades.to_string(), format!("{:.4}", d.pnl_usd), format!("{:.4}", d.cumulative_pnl_usd)])
+        .collect();
+    push_table(&mut out, "PnL diario (UTC)", &["Fecha", "Trades", "PnL USD", "Acumulado"], &daily);
+    for (title, rows) in [("Por estrategia", &r.by_strategy), ("Por símbolo", &r.by_symbol), ("Por par de exchanges", &r.by_pair), ("Por hora (UTC)", &r.by_hour)] {
+        push_table(&mut out, title, &BREAKDOWN_HEADERS, &breakdown_rows(rows));
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// `signed`: columnas de montos, en verde o rojo según el signo
+fn html_table(out: &mut String, title: &str, headers: &[&str], rows: &[Vec<String>], signed: &[usize]) {
+    let _ = write!(out, "<h2>{}</h2><table><tr>", escape(title));
+    for h in headers {
+        let _ = write!(out, "<th>{}</th>", escape(h));
+    }
+    out.push_str("</tr>");
+    for row in rows {
+        out.push_str("<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            let class = match cell.parse::<f64>() {
+                Ok(v) if signed.contains(&i) && v > 0.0 => " class=\"pos\"",
+                Ok(v) if signed.contains(&i) && v < 0.0 => " class=\"neg\"",
+                _ => "",
+            };
+            let _ = write!(out, "<td{}>{}</td>", class, escape(cell));
+        }
+        out.push_str("</tr>");
+    }
+    out.push_str("</table>");
+}
+
+// Curva de PnL acumulado como SVG inline
+fn equity_svg(points: &[PnlPoint]) -> String {
+    const W: f64 = 900.0;
+    const H: f64 = 240.0;
+    if points.len() < 2 {
+        return String::new();
+    }
+    let values: Vec<f64> = std::iter::once(0.0).chain(points.iter().map(|p| p.cumulative_pnl_usd)).collect();
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let span = if max > min { max - min } else { 1.0 };
+    let x = |i: usize| i as f64 / (values.len() - 1) as f64 * W;
+    let y = |v: f64| H - (v - min) / span * H;
+    let path: Vec<String> = values.iter().enumerate().map(|(i, v)| format!("{:.1},{:.1}", x(i), y(*v))).collect();
+    format!(
+        "<svg viewBox=\"0 0 {w} {h}\" preserveAspectRatio=\"none\"><line x1=\"0\" y1=\"{zero:.1}\" x2=\"{w}\" y2=\"{zero:.1}\" class=\"zero\"/><polyline points=\"{pts}\"/></svg>",
+        w = W,
+        h = H,
+        zero = y(0.0),
+        pts = path.join(" ")
+    )
+}
+
+pub fn to_html(r: &Report) -> String {
+    let mut out = String::new();
+    let title = format!("Reporte de trades{}", r.strategy.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default());
+    let _ = write!(
+        out,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{t}</title><style>\
+         body{{font-family:system-ui,sans-serif;background:#0b0f17;color:#d6dde8;margin:2rem}}\
+         h1{{font-size:1.4rem}}h2{{font-size:1rem;margin-top:2rem;color:#8aa4c8}}\
+         table{{border-collapse:collapse;font-family:ui-monospace,monospace;font-size:.85rem}}\
+         th,td{{padding:.25rem .75rem;border-bottom:1px solid #1e2633;text-align:right}}\
+         th:first-child,td:first-child{{text-align:left}}th{{color:#8aa4c8}}\
+         .pos{{color:#4ade80}}.neg{{color:#f87171}}\
+         svg{{width:100%;max-width:900px;height:240px;background:#111826}}\
+         polyline{{fill:none;stroke:#60a5fa;stroke-width:1.5}}.zero{{stroke:#334155;stroke-dasharray:4}}\
+         </style></head><body><h1>{t}</h1><p>Generado {g}</p>",
+        t = escape(&title),
+        g = crate::journal::format_ts(r.generated_at)
+    );
+    let summary: Vec<Vec<String>> = summary_rows(r).into_iter().map(|(k, v)| vec![k.to_string(), v]).collect();
+    html_table(&mut out, "Resumen", &["Métrica", "Valor"], &summary, &[]);
+    let _ = write!(out, "<h2>PnL acumulado</h2>{}", equity_svg(&r.cumulative));
+    let daily: Vec<Vec<String>> = r
+        .daily
+        .iter()
+        .map(|d| vec![d.date.clone(), d.trades.to_string(), format!("{:.4}", d.pnl_usd), format!("{:.4}", d.cumulative_pnl_usd)])
+        .collect();
+    html_table(&mut out, "PnL diario (UTC)", &["Fecha", "Trades", "PnL USD", "Acumulado"], &daily, &[2, 3]);
+    for (title, rows) in [("Por estrategia", &r.by_strategy), ("Por símbolo", &r.by_symbol), ("Por par de exchanges", &r.by_pair), ("Por hora (UTC)", &r.by_hour)] {
+        html_table(&mut out, title, &BREAKDOWN_HEADERS, &breakdown_rows(rows), &[2, 4]);
+    }
+    out.push_str("</body></html>");
+    out
+}
+
+// Punto de entrada de `--report`: tabla y JSON a stdout, HTML a archivo
+pub fn run(journal: &Journal, format: ReportFormat, strategy: Option<&str>, out: Option<&str>) -> Result<()> {
+    let report = build(&journal.trades(strategy)?, strategy);
+    let rendered = match format {
+        ReportFormat::Table => to_table(&report),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+        ReportFormat::Html => to_html(&report),
+    };
+    match (format, out) {
+        (_, Some(path)) => std::fs::write(path, rendered)?,
+        (ReportFormat::Html, None) => std::fs::write(DEFAULT_HTML_PATH, rendered)?,
+        _ => println!("{}", rendered),
+    }
+    if let Some(path) = out.or((format == ReportFormat::Html).then_some(DEFAULT_HTML_PATH)) {
+        tracing::info!("📊 Reporte escrito en {}", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_MS: u64 = 86_400_000;
+    // 2024-01-01 12:00 UTC
+    const NOON: u64 = 1_704_110_400_000;
+
+    fn trade(id: i64, executed_at: Option<u64>, profit_usd: f64) -> JournalTrade {
+        JournalTrade {
+            id,
+            executed_at,
+            strategy: "simulador".to_string(),
+            symbol: "BTC-USDT".to_string(),
+            buy_exchange: "Binance".to_string(),
+            sell_exchange: "Bybit".to_string(),
+            buy_price: 100.0,
+            sell_price: 101.0,
+            profit_usd,
+            notional_usd: Some(1000.0),
+            detected_edge_pct: Some(1.0),
+            balance_after: None,
+            note: String::new(),
+        }
+    }
+
+    fn curve(values: &[f64]) -> Vec<PnlPoint> {
+        values.iter().enumerate().map(|(i, v)| PnlPoint { trade_id: i as i64, executed_at: None, cumulative_pnl_usd: *v }).collect()
+    }
+
+    fn days(pnl: &[f64]) -> Vec<DailyPnl> {
+        pnl.iter().map(|p| DailyPnl { date: String::new(), trades: 1, pnl_usd: *p, cumulative_pnl_usd: 0.0 }).collect()
+    }
+
+    #[test]
+    fn max_drawdown_from_running_peak() {
+        assert_eq!(max_drawdown(&[]), 0.0);
+        assert_eq!(max_drawdown(&curve(&[5.0, 2.0, 8.0, 1.0, 3.0])), 7.0);
+        // La curva arranca en 0: perder desde el primer trade también es drawdown
+        assert_eq!(max_drawdown(&curve(&[-3.0, -1.0])), 3.0);
+    }
+
+    #[test]
+    fn sharpe_sortino_need_two_days_and_dispersion() {
+        assert_eq!(sharpe_sortino(&days(&[5.0])), (None, None));
+
+        let (sharpe, sortino) = sharpe_sortino(&days(&[1.0, 3.0]));
+        let expected = 2.0 / 2f64.sqrt() * TRADING_DAYS_PER_YEAR.sqrt();
+        assert!((sharpe.unwrap() - expected).abs() < 1e-9);
+        // Sin días en pérdida no hay riesgo a la baja
+        assert_eq!(sortino, None);
+
+        let (sharpe, sortino) = sharpe_sortino(&days(&[2.0, -1.0]));
+        assert!(sharpe.unwrap() > 0.0);
+        let expected = 0.5 / (0.5f64).sqrt() * TRADING_DAYS_PER_YEAR.sqrt();
+        assert!((sortino.unwrap() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn build_fills_idle_days_and_skips_undated_trades() {
+        let trades = [trade(1, Some(NOON), 10.0), trade(2, Some(NOON + 2 * DAY_MS), -4.0), trade(3, None, 1.0)];
+        let report = build(&trades, None);
+
+        assert_eq!(report.trades, 3);
+        assert_eq!(report.undated_trades, 1);
+        assert_eq!(report.total_pnl_usd, 7.0);
+        assert_eq!(report.max_drawdown_usd, 4.0);
+        assert!((report.win_rate_pct - 200.0 / 3.0).abs() < 1e-9);
+
+        let daily: Vec<(&str, usize, f64, f64)> =
+            report.daily.iter().map(|d| (d.date.as_str(), d.trades, d.pnl_usd, d.cumulative_pnl_usd)).collect();
+        assert_eq!(daily, [("2024-01-01", 1, 10.0, 10.0), ("2024-01-02", 0, 0.0, 10.0), ("2024-01-03", 1, -4.0, 6.0)]);
+        // El día vacío cuenta: Sharpe sobre [10, 0, -4] y no sobre [10, -4]
+        assert_eq!((report.sharpe, report.sortino), sharpe_sortino(&days(&[10.0, 0.0, -4.0])));
+
+        assert_eq!(report.cumulative.last().map(|p| p.cumulative_pnl_usd), Some(7.0));
+        assert_eq!(report.by_hour.len(), 1);
+        assert_eq!(report.by_hour[0].trades, 2);
+        assert!((report.avg_captured_edge_pct.unwrap() - (1.0 - 0.4 + 0.1) / 3.0).abs() < 1e-9);
+        assert_eq!(report.capture_ratio, report.avg_captured_edge_pct);
+    }
+}
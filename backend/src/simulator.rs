@@ -0,0 +1,227 @@
+// src/simulator.rs
+
+use crate::arbitrage::{detector::OpportunityKind, ArbitrageOpportunity};
+use crate::exchanges::{Exchange, MarketType};
+use crate::execution::Side;
+use crate::fees::FeeConfig;
+use crate::journal::{self, Journal, TradeLeg, TradeRecord};
+use crate::metrics::{self, RiskLimit};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const INITIAL_BALANCE_PER_EXCHANGE: f64 = 5000.0;
+pub const DEFAULT_MAX_TRADE_USD: f64 = 2000.0;
+const SLIPPAGE_BPS: f64 = 0.5;
+const MAX_RECENT_TRADES: usize = 10;
+
+#[derive(Serialize, Clone)]
+pub struct SimStats {
+    pub total_usd: f64,
+    pub binance_usd: f64,
+    pub bybit_usd: f64,
+    pub hyperliquid_usd: f64,
+    pub extended_usd: f64,
+    pub trade_count: u32,
+    pub last_action: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)] // Se requiere Clone para el historial en memoria
+pub struct TradeLog {
+    pub timestamp: String,
+    #[serde(default)]
+    pub executed_at: u64, // ms UTC
+    pub symbol: String,
+    pub buy_exchange: String,
+    pub sell_exchange: String,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    pub profit_usd: f64,
+    pub balance_after: f64,
+    pub note: String,
+}
+
+// Lo que hace falta para retomar la simulación tras un reinicio
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SimLedger {
+    pub balances: HashMap<Exchange, f64>,
+    pub trade_count: u32,
+    pub last_action: String,
+    pub recent_trades: Vec<TradeLog>,
+}
+
+// Estado de la simulación: balances por exchange, contador y últimos trades
+pub struct SimEngine {
+    fee_config: FeeConfig,
+    journal: Journal,
+    balances: HashMap<Exchange, f64>,
+    trade_count: u32,
+    last_action: String,
+    recent_trades: Vec<TradeLog>,
+    max_trade_usd: f64,
+}
+
+impl SimEngine {
+    pub fn new(fee_config: FeeConfig, journal: Journal, recent_trades: Vec<TradeLog>) -> Self {
+        let balances = [Exchange::Binance, Exchange::Bybit, Exchange::Hyperliquid, Exchange::Extended]
+            .into_iter()
+            .map(|ex| (ex, INITIAL_BALANCE_PER_EXCHANGE))
+            .collect();
+        Self {
+            fee_config,
+            journal,
+            balances,
+            trade_count: 0,
+            last_action: "Sistema Iniciado".to_string(),
+            recent_trades,
+            max_trade_usd: DEFAULT_MAX_TRADE_USD,
+        }
+    }
+
+    // Tope por trade; se cambia en caliente desde la API de control
+    pub fn set_max_trade_usd(&mut self, max_trade_usd: f64) {
+        self.max_trade_usd = max_trade_usd;
+    }
+
+    fn balance(&self, exchange: Exchange) -> f64 {
+        self.balances.get(&exchange).copied().unwrap_or(0.0)
+    }
+
+    fn total_balance(&self) -> f64 {
+        self.balances.values().sum()
+    }
+
+    // Fricción = slippage fijo + impacto proporcional al tamaño sobre la liquidez visible
+    fn friction(&self, op: &ArbitrageOpportunity) -> (f64, f64) {
+        let trade_capital = f64::min(self.max_trade_usd, op.max_tradeable_usd);
+        let liquidity_impact = (trade_capital / op.max_tradeable_usd) * 0.0003;
+        (trade_capital, SLIPPAGE_BPS / 10000.0 + liquidity_impact)
+    }
+
+    // Simulación de fricción para todas las oportunidades en el feed
+    pub fn apply_friction(&self, opportunities: &mut [ArbitrageOpportunity]) {
+        for op in opportunities.iter_mut().filter(|op| op.kind == OpportunityKind::CrossVenue) {
+            let (_, total_fric_sim) = self.friction(op);
+            op.total_fees_pct += total_fric_sim * 100.0; // Fricción sobre los fees que ya calculó el detector
+        }
+    }
+
+    // Intenta ejecutar (simulado) la oportunidad. Devuelve el trade si fue rentable.
+    pub fn try_trade(&mut self, op: &ArbitrageOpportunity) -> Option<TradeLog> {
+        // El simulador solo modela compra/venta en dos venues
+        if op.kind != OpportunityKind::CrossVenue {
+            return None;
+        }
+        let (trade_capital, total_friction) = self.friction(op);
+        if trade_capital <= 10.0 {
+            return None;
+        }
+        let final_buy_price = op.buy_price * (1.0 + total_friction);
+        let final_sell_price = op.sell_price * (1.0 - total_friction);
+
+        // Taker de la cuenta en cada pata (tier por volumen, override por símbolo)
+        let buy_fee = self.fee_config.get_fees(op.buy_exchange, MarketType::Perp, Some(&op.symbol)).taker / 100.0;
+        let sell_fee = self.fee_config.get_fees(op.sell_exchange, MarketType::Perp, Some(&op.symbol)).taker / 100.0;
+
+        let trade_qty = trade_capital / final_buy_price;
+        let cost_real = (trade_qty * final_buy_price) * (1.0 + buy_fee);
+        let revenue_real = (trade_qty * final_sell_price) * (1.0 - sell_fee);
+        let profit_net_real = revenue_real - cost_real;
+
+        if profit_net_real <= 0.0001 {
+            return None;
+        }
+        // Recién acá es un trade que se habría hecho: el tope cuenta una vez por decisión
+        if self.balance(op.buy_exchange) < trade_capital {
+            metrics::risk_limit(RiskLimit::Balance);
+            return None;
+        }
+
+        // Gestión de Balances
+        *self.balances.entry(op.buy_exchange).or_insert(0.0) -= trade_capital;
+        *self.balances.entry(op.sell_exchange).or_insert(0.0) += trade_capital + profit_net_real;
+
+        self.fee_config.record_volume(op.buy_exchange, trade_qty * final_buy_price);
+        self.fee_config.record_volume(op.sell_exchange, trade_qty * final_sell_price);
+
+        if self.max_trade_usd < op.max_tradeable_usd {
+            metrics::risk_limit(RiskLimit::MaxTradeUsd);
+        }
+        self.trade_count += 1;
+        self.last_action = format!("WIN: {} (+${:.4})", op.symbol, profit_net_real);
+
+        let executed_at = chrono::Utc::now().timestamp_millis() as u64;
+        let trade = TradeLog {
+            timestamp: journal::format_ts(executed_at),
+            executed_at,
+            symbol: op.symbol.clone(),
+            buy_exchange: format!("{:?}", op.buy_exchange),
+            sell_exchange: format!("{:?}", op.sell_exchange),
+            buy_price: final_buy_price,
+            sell_price: final_sell_price,
+            profit_usd: profit_net_real,
+            balance_after: self.total_balance(),
+            note: format!("Tokio Sim (Fric: {:.2}bps)", total_friction * 10000.0),
+        };
+
+        self.journal.record_trade(&TradeRecord {
+            strategy: journal::STRATEGY_SIMULATOR,
+            executed_at,
+            symbol: &op.symbol,
+            buy_exchange: op.buy_exchange,
+            sell_exchange: op.sell_exchange,
+            buy_price: final_buy_price,
+            sell_price: final_sell_price,
+            profit_usd: profit_net_real,
+            notional_usd: trade_qty * final_buy_price,
+            detected_edge_pct: Some(op.net_profit_pct),
+            balance_after: Some(trade.balance_after),
+            note: &trade.note,
+            legs: vec![
+                TradeLeg { exchange: op.buy_exchange, side: Side::Buy, price: final_buy_price, qty: trade_qty, fee_usd: trade_qty * final_buy_price * buy_fee },
+                TradeLeg { exchange: op.sell_exchange, side: Side::Sell, price: final_sell_price, qty: trade_qty, fee_usd: trade_qty * final_sell_price * sell_fee },
+            ],
+        });
+
+        // Actualizar historial para el Frontend
+        self.recent_trades.insert(0, trade.clone());
+        self.recent_trades.truncate(MAX_RECENT_TRADES);
+
+        tracing::info!("💰 TRADE #{}: +${:.4} en {} (Fricción: {:.4}%)",
+            self.trade_count, profit_net_real, op.symbol, total_friction * 100.0);
+
+        Some(trade)
+    }
+
+    pub fn stats(&self) -> SimStats {
+        SimStats {
+            total_usd: self.total_balance(),
+            binance_usd: self.balance(Exchange::Binance),
+            bybit_usd: self.balance(Exchange::Bybit),
+            hyperliquid_usd: self.balance(Exchange::Hyperliquid),
+            extended_usd: self.balance(Exchange::Extended),
+            trade_count: self.trade_count,
+            last_action: self.last_action.clone(),
+        }
+    }
+
+    pub fn recent_trades(&self) -> &[TradeLog] {
+        &self.recent_trades
+    }
+
+    pub fn ledger(&self) -> SimLedger {
+        SimLedger {
+            balances: self.balances.clone(),
+            trade_count: self.trade_count,
+            last_action: self.last_action.clone(),
+            recent_trades: self.recent_trades.clone(),
+        }
+    }
+
+    // Pisa el estado inicial con el guardado; un venue nuevo arranca con el balance inicial
+    pub fn restore(&mut self, ledger: SimLedger) {
+        self.balances.extend(ledger.balances);
+        self.trade_count = ledger.trade_count;
+        self.last_action = ledger.last_action;
+        self.recent_trades = ledger.recent_trades;
+    }
+}
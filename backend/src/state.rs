@@ -0,0 +1,60 @@
+// src/state.rs
+//
+// Foto del estado de la simulación y las estrategias. Se escribe cada tanto y
+// al apagar, y se lee al arrancar para seguir donde se quedó.
+
+use crate::arbitrage::{ConvergenceSnapshot, MakerTakerSnapshot};
+use crate::control::TradingControl;
+use crate::exchanges::Exchange;
+use crate::simulator::SimLedger;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const DEFAULT_STATE_PATH: &str = "state.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedState {
+    pub saved_at: u64,
+    pub sim: SimLedger,
+    pub maker_taker: MakerTakerSnapshot,
+    pub convergence: ConvergenceSnapshot,
+    // Volumen operado por día: define el tier de comisiones
+    #[serde(default)]
+    pub daily_volume: HashMap<Exchange, BTreeMap<String, f64>>,
+    // Pausas, límites vigentes y kill switch
+    #[serde(default)]
+    pub control: TradingControl,
+}
+
+pub fn path() -> String {
+    std::env::var("STATE_PATH").unwrap_or_else(|_| DEFAULT_STATE_PATH.to_string())
+}
+
+// Se escribe a un temporal y se renombra: un corte a mitad no deja el archivo roto
+pub fn save(state: &SavedState) -> Result<()> {
+    let path = path();
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+// None si todavía no hay estado guardado (primer arranque)
+pub fn load() -> Result<Option<SavedState>> {
+    match std::fs::read_to_string(path()) {
+        Ok(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// `--reset`: se aparta el estado anterior en vez de borrarlo
+pub fn discard() -> Result<()> {
+    let path = path();
+    match std::fs::rename(&path, format!("{}.bak", path)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
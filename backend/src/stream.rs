@@ -0,0 +1,635 @@
+// src/stream.rs
+//
+// Protocolo del WebSocket /ws. Un cliente que no manda nada recibe el payload
+// completo del dashboard en cada publicación, como siempre. Con el primer
+// `subscribe` pasa a recibir solo los topics que pidió:
+//
+//   → {"op":"subscribe","topic":"opportunities","filter":{"symbols":["BTC"],"min_net_pct":0.05},"throttle_ms":500}
+//   → {"op":"unsubscribe","topic":"opportunities"}
+//   ← {"type":"snapshot","topic":"opportunities","seq":0,"data":{...}}
+//   ← {"type":"delta","topic":"opportunities","seq":1,"patch":{...}}
+//
+// Topics: opportunities, funding, basis, trades, stats, health y books:<SÍMBOLO>.
+// Las colecciones van como objeto por clave (`BTC:Binance:Bybit`) para que un
+// cambio toque solo esa entrada; el orden lo pone el cliente. Cada delta es un
+// JSON merge patch (RFC 7386) contra lo último enviado en ese topic: una clave
+// en null es una entrada que desapareció. Volver a suscribirse a un topic
+// cambia filtro y throttle y manda una foto nueva.
+//
+// Un cliente lento no frena a nadie ni se corta: su cola guarda solo la última
+// vista de cada topic (el delta sale contra lo que de verdad recibió) y le
+// avisa con {"type":"lagged","skipped":n} cuántas publicaciones se salteó.
+// El servidor manda ping cada 15 s y corta al cliente que no contesta.
+
+use crate::aggregator::PriceAggregator;
+use crate::api::DashboardPayload;
+use crate::exchanges::{Exchange, MarketType};
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Notify};
+use tracing::warn;
+use warp::ws::{Message, WebSocket};
+
+// El motor publica cada 50 ms: un throttle menor no cambia nada
+const MIN_THROTTLE_MS: u64 = 50;
+const DEFAULT_THROTTLE_MS: u64 = 250;
+
+// Respuestas y pings sin enviar por cliente; más que esto es un cliente que no lee
+const REPLY_QUEUE: usize = 64;
+const LAG_NOTICE_INTERVAL: Duration = Duration::from_secs(5);
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+const PONG_TIMEOUT: Duration = Duration::from_secs(45); // Sin nada del cliente en este tiempo, se lo da por muerto
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Topic {
+    Opportunities,
+    Funding,
+    Basis,
+    Trades,
+    Stats,
+    Health,
+    Books(String), // Todos los venues y mercados de un símbolo
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, String> {
+        match raw.split_once(':') {
+            Some(("books", symbol)) if !symbol.trim().is_empty() => Ok(Topic::Books(symbol.trim().to_uppercase())),
+            Some(_) => Err(format!("topic desconocido: {} (los libros van como books:<SÍMBOLO>)", raw)),
+            None => match raw {
+                "opportunities" => Ok(Topic::Opportunities),
+                "funding" => Ok(Topic::Funding),
+                "basis" => Ok(Topic::Basis),
+                "trades" => Ok(Topic::Trades),
+                "stats" => Ok(Topic::Stats),
+                "health" => Ok(Topic::Health),
+                _ => Err(format!("topic desconocido: {}", raw)),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Topic {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, String> {
+        raw.parse()
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topic::Opportunities => write!(f, "opportunities"),
+            Topic::Funding => write!(f, "funding"),
+            Topic::Basis => write!(f, "basis"),
+            Topic::Trades => write!(f, "trades"),
+            Topic::Stats => write!(f, "stats"),
+            Topic::Health => write!(f, "health"),
+            Topic::Books(symbol) => write!(f, "books:{}", symbol),
+        }
+    }
+}
+
+// Se aplica donde tiene sentido: stats y health no tienen símbolo ni profit
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+    #[serde(default)]
+    pub symbols: Vec<String>, // Vacío: todos
+    pub min_net_pct: Option<f64>,
+}
+
+impl Filter {
+    fn symbol(&self, symbol: &str) -> bool {
+        self.symbols.is_empty() || self.symbols.iter().any(|s| s.eq_ignore_ascii_case(symbol))
+    }
+
+    fn net(&self, net_pct: f64) -> bool {
+        self.min_net_pct.is_none_or(|min| net_pct >= min)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+enum ClientMessage {
+    Subscribe {
+        topic: Topic,
+        #[serde(default)]
+        filter: Filter,
+        throttle_ms: Option<u64>,
+    },
+    Unsubscribe {
+        topic: Topic,
+    },
+}
+
+#[derive(Serialize)]
+struct BookView {
+    exchange: Exchange,
+    market_type: MarketType,
+    bid: f64,
+    ask: f64,
+    bid_size: f64,
+    ask_size: f64,
+    exchange_ts: Option<u64>,
+}
+
+struct Subscription {
+    filter: Filter,
+    throttle: Duration,
+    checked_at: Option<Instant>,
+}
+
+impl Subscription {
+    fn new(filter: Filter, throttle_ms: Option<u64>) -> Self {
+        let throttle = Duration::from_millis(throttle_ms.unwrap_or(DEFAULT_THROTTLE_MS).max(MIN_THROTTLE_MS));
+        Self { filter, throttle, checked_at: None }
+    }
+
+    // Vista vigente del topic, si ya pasó el throttle
+    fn poll(&mut self, topic: &Topic, payload: &DashboardPayload, aggregator: &PriceAggregator) -> Option<Value> {
+        let now = Instant::now();
+        if self.checked_at.is_some_and(|at| now.duration_since(at) < self.throttle) {
+            return None;
+        }
+        self.checked_at = Some(now);
+        Some(view(topic, &self.filter, payload, aggregator))
+    }
+}
+
+// Un topic en la cola del cliente: lo último que recibió y la vista más nueva sin enviar
+#[derive(Default)]
+struct TopicSlot {
+    delivered: Option<Value>,
+    pending: Option<Value>,
+    seq: u64,
+}
+
+impl TopicSlot {
+    // El delta se arma al enviar, contra lo que el cliente realmente tiene
+    fn render(&mut self, topic: &Topic) -> Option<Message> {
+        let current = self.pending.take()?;
+        let message = match &self.delivered {
+            None => json!({ "type": "snapshot", "topic": topic.to_string(), "seq": self.seq, "data": current }),
+            Some(delivered) => json!({ "type": "delta", "topic": topic.to_string(), "seq": self.seq, "patch": diff(delivered, &current)? }),
+        };
+        self.seq += 1;
+        self.delivered = Some(current);
+        Some(Message::text(message.to_string()))
+    }
+}
+
+#[derive(Default)]
+struct OutboxState {
+    replies: VecDeque<Message>, // Respuestas, avisos y pings: en orden y sin pisarse
+    dashboard: Option<String>,  // Payload completo pendiente (cliente sin suscripciones)
+    topics: HashMap<Topic, TopicSlot>,
+    conflated: u64, // Publicaciones pisadas sin llegar al cliente desde el último aviso
+    noticed_at: Option<Instant>,
+    closed: bool,
+}
+
+// Cola acotada por cliente: un lugar por topic donde la publicación nueva pisa
+// a la que el cliente todavía no leyó, más una fila corta de respuestas
+#[derive(Default)]
+struct Outbox {
+    state: Mutex<OutboxState>,
+    ready: Notify,
+}
+
+impl Outbox {
+    fn lock(&self) -> std::sync::MutexGuard<'_, OutboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // false si la fila está llena: el cliente pide más de lo que lee
+    fn reply(&self, message: Message) -> bool {
+        let mut state = self.lock();
+        if state.replies.len() >= REPLY_QUEUE {
+            return false;
+        }
+        state.replies.push_back(message);
+        self.ready.notify_one();
+        true
+    }
+
+    fn skipped(&self, count: u64) {
+        self.lock().conflated += count;
+    }
+
+    fn offer_dashboard(&self, json: String) -> u64 {
+        let mut state = self.lock();
+        let conflated = state.dashboard.replace(json).is_some() as u64;
+        state.conflated += conflated;
+        self.ready.notify_one();
+        conflated
+    }
+
+    fn offer(&self, topic: &Topic, view: Value) -> u64 {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let Some(slot) = state.topics.get_mut(topic) else { return 0 };
+        let conflated = slot.pending.replace(view).is_some() as u64;
+        state.conflated += conflated;
+        self.ready.notify_one();
+        conflated
+    }
+
+    // Suscribirse de nuevo arranca de cero: el próximo envío es una foto
+    fn subscribe(&self, topic: &Topic) {
+        let mut state = self.lock();
+        state.dashboard = None;
+        state.topics.insert(topic.clone(), TopicSlot::default());
+    }
+
+    fn unsubscribe(&self, topic: &Topic) {
+        self.lock().topics.remove(topic);
+    }
+
+    // Todo lo pendiente, en orden de envío; None al cerrar
+    fn take(&self, metrics: &ClientMetrics) -> Option<Vec<Message>> {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        if state.closed {
+            return None;
+        }
+        let mut batch: Vec<Message> = state.replies.drain(..).collect();
+        let notice_due = state.noticed_at.is_none_or(|at| at.elapsed() >= LAG_NOTICE_INTERVAL);
+        if state.conflated > 0 && notice_due {
+            batch.push(Message::text(json!({ "type": "lagged", "skipped": state.conflated }).to_string()));
+            metrics.inner.lag_notices.fetch_add(1, Ordering::Relaxed);
+            state.conflated = 0;
+            state.noticed_at = Some(Instant::now());
+        }
+        batch.extend(state.dashboard.take().map(Message::text));
+        batch.extend(state.topics.iter_mut().filter_map(|(topic, slot)| slot.render(topic)));
+        Some(batch)
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.ready.notify_one();
+    }
+}
+
+#[derive(Default)]
+struct ClientCounters {
+    connected: AtomicU64,
+    connections: AtomicU64,
+    conflated: AtomicU64,
+    lag_notices: AtomicU64,
+    keepalive_timeouts: AtomicU64,
+    overflows: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStats {
+    pub connected: u64,
+    pub connections_total: u64,
+    pub conflated_total: u64, // Publicaciones que un cliente lento no llegó a leer
+    pub lag_notices_total: u64,
+    pub keepalive_timeouts_total: u64,
+    pub overflows_total: u64, // Clientes cortados por no leer sus respuestas
+}
+
+// Contadores de clientes del WebSocket, compartidos con la API
+#[derive(Clone, Default)]
+pub struct ClientMetrics {
+    inner: Arc<ClientCounters>,
+}
+
+impl ClientMetrics {
+    pub fn stats(&self) -> ClientStats {
+        let c = &self.inner;
+        ClientStats {
+            connected: c.connected.load(Ordering::Relaxed),
+            connections_total: c.connections.load(Ordering::Relaxed),
+            conflated_total: c.conflated.load(Ordering::Relaxed),
+            lag_notices_total: c.lag_notices.load(Ordering::Relaxed),
+            keepalive_timeouts_total: c.keepalive_timeouts.load(Ordering::Relaxed),
+            overflows_total: c.overflows.load(Ordering::Relaxed),
+        }
+    }
+
+    fn conflated(&self, count: u64) {
+        if count > 0 {
+            self.inner.conflated.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+// Baja del contador de conectados pase lo que pase con la conexión
+struct ConnectedClient(ClientMetrics);
+
+impl ConnectedClient {
+    fn new(metrics: &ClientMetrics) -> Self {
+        metrics.inner.connected.fetch_add(1, Ordering::Relaxed);
+        metrics.inner.connections.fetch_add(1, Ordering::Relaxed);
+        Self(metrics.clone())
+    }
+}
+
+impl Drop for ConnectedClient {
+    fn drop(&mut self) {
+        self.0.inner.connected.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn view(topic: &Topic, filter: &Filter, payload: &DashboardPayload, aggregator: &PriceAggregator) -> Value {
+    match topic {
+        Topic::Opportunities => keyed(
+            payload.opportunities.iter().filter(|o| filter.symbol(&o.symbol) && filter.net(o.net_profit_pct)),
+            |o| format!("{}:{:?}:{:?}", o.symbol, o.buy_exchange, o.sell_exchange),
+        ),
+        Topic::Funding => keyed(
+            payload.funding_opportunities.iter().filter(|o| filter.symbol(&o.symbol) && filter.net(o.net_edge_pct)),
+            |o| format!("{}:{:?}:{:?}", o.symbol, o.long_exchange, o.short_exchange),
+        ),
+        Topic::Basis => keyed(
+            payload.basis_opportunities.iter().filter(|o| filter.symbol(&o.symbol) && filter.net(o.net_basis_pct)),
+            |o| format!("{}:{:?}:{:?}:{:?}", o.symbol, o.direction, o.spot_exchange, o.perp_exchange),
+        ),
+        Topic::Trades => keyed(
+            payload.recent_trades.iter().filter(|t| filter.symbol(&t.symbol)),
+            |t| format!("{}:{}:{}:{}", t.executed_at, t.symbol, t.buy_exchange, t.sell_exchange),
+        ),
+        Topic::Stats => serde_json::to_value(&payload.stats).unwrap_or_default(),
+        Topic::Health => json!({
+            "healthy": payload.health.healthy,
+            "feeds": keyed(payload.health.feeds.iter(), |f| format!("{:?}:{:?}", f.exchange, f.market_type)),
+        }),
+        Topic::Books(symbol) => {
+            let books: Vec<BookView> = [MarketType::Perp, MarketType::Spot]
+                .into_iter()
+                .flat_map(|market_type| {
+                    aggregator.get_books(symbol, market_type).unwrap_or_default().into_iter().map(move |(exchange, book)| BookView {
+                        exchange,
+                        market_type,
+                        bid: book.bid,
+                        ask: book.ask,
+                        bid_size: book.bid_size,
+                        ask_size: book.ask_size,
+                        exchange_ts: book.exchange_ts,
+                    })
+                })
+                .collect();
+            keyed(books.iter(), |b| format!("{:?}:{:?}", b.exchange, b.market_type))
+        }
+    }
+}
+
+fn keyed<'a, T: Serialize + 'a>(items: impl Iterator<Item = &'a T>, key: impl Fn(&T) -> String) -> Value {
+    Value::Object(items.map(|item| (key(item), serde_json::to_value(item).unwrap_or_default())).collect())
+}
+
+// Merge patch que lleva `old` a `new`; None si no hay cambios
+fn diff(old: &Value, new: &Value) -> Option<Value> {
+    if old == new {
+        return None;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, value) in new {
+                match old.get(key) {
+                    Some(previous) => {
+                        if let Some(change) = diff(previous, value) {
+                            patch.insert(key.clone(), change);
+                        }
+                    }
+                    None => {
+                        patch.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Some(Value::Object(patch))
+        }
+        _ => Some(new.clone()),
+    }
+}
+
+// Escribe la cola del cliente a su ritmo; si el socket no avanza, la cola conflaciona
+async fn write_loop(mut sender: SplitSink<WebSocket, Message>, outbox: Arc<Outbox>, metrics: ClientMetrics) {
+    loop {
+        outbox.ready.notified().await;
+        let Some(batch) = outbox.take(&metrics) else {
+            let _ = sender.send(Message::close()).await;
+            return;
+        };
+        for message in batch {
+            if sender.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub async fn handle_socket(
+    ws: WebSocket,
+    mut rx: broadcast::Receiver<Arc<DashboardPayload>>,
+    latest: watch::Receiver<Option<Arc<DashboardPayload>>>,
+    aggregator: PriceAggregator,
+    metrics: ClientMetrics,
+) {
+    let _client = ConnectedClient::new(&metrics);
+    let (sender, mut incoming) = ws.split();
+    let outbox = Arc::new(Outbox::default());
+    let mut writer = tokio::spawn(write_loop(sender, outbox.clone(), metrics.clone()));
+    let mut subscriptions: HashMap<Topic, Subscription> = HashMap::new();
+    let mut full_payload = true; // Hasta el primer subscribe: el dashboard de siempre
+    let mut last_seen = Instant::now();
+    let mut keepalive = tokio::time::interval(PING_INTERVAL);
+    keepalive.tick().await;
+
+    loop {
+        tokio::select! {
+            message = incoming.next() => {
+                let Some(Ok(message)) = message else { break };
+                last_seen = Instant::now(); // Cualquier cosa del cliente cuenta como pong
+                if message.is_close() {
+                    break;
+                }
+                let Ok(text) = message.to_str() else { continue };
+                let reply = match serde_json::from_str::<ClientMessage>(text) {
+                    Ok(ClientMessage::Subscribe { topic, filter, throttle_ms }) => {
+                        full_payload = false;
+                        outbox.subscribe(&topic);
+                        let subscription = subscriptions.entry(topic.clone()).insert_entry(Subscription::new(filter, throttle_ms)).into_mut();
+                        // Foto inmediata con lo último publicado; si todavía no hay nada, sale con la primera publicación
+                        let current = latest.borrow().clone();
+                        if let Some(view) = current.and_then(|payload| subscription.poll(&topic, &payload, &aggregator)) {
+                            outbox.offer(&topic, view);
+                        }
+                        None
+                    }
+                    Ok(ClientMessage::Unsubscribe { topic }) => {
+                        subscriptions.remove(&topic);
+                        outbox.unsubscribe(&topic);
+                        Some(json!({ "type": "unsubscribed", "topic": topic.to_string() }))
+                    }
+                    Err(e) => Some(json!({ "type": "error", "message": e.to_string() })),
+                };
+                if let Some(reply) = reply {
+                    if !outbox.reply(Message::text(reply.to_string())) {
+                        metrics.inner.overflows.fetch_add(1, Ordering::Relaxed);
+                        warn!("⚠️ Cliente WS cortado: pide más de lo que lee");
+                        break;
+                    }
+                }
+            }
+            payload = rx.recv() => {
+                let payload = match payload {
+                    Ok(payload) => payload,
+                    // Las publicaciones que este cliente no alcanzó a procesar cuentan como pisadas
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        outbox.skipped(skipped);
+                        metrics.conflated(skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if full_payload {
+                    if let Ok(json) = serde_json::to_string(&*payload) {
+                        metrics.conflated(outbox.offer_dashboard(json));
+                    }
+                    continue;
+                }
+                for (topic, subscription) in subscriptions.iter_mut() {
+                    if let Some(view) = subscription.poll(topic, &payload, &aggregator) {
+                        metrics.conflated(outbox.offer(topic, view));
+                    }
+                }
+            }
+            _ = keepalive.tick() => {
+                if last_seen.elapsed() > PONG_TIMEOUT {
+                    metrics.inner.keepalive_timeouts.fetch_add(1, Ordering::Relaxed);
+                    warn!("⚠️ Cliente WS sin responder hace {}s: se corta", last_seen.elapsed().as_secs());
+                    break;
+                }
+                if !outbox.reply(Message::ping(Vec::new())) {
+                    break;
+                }
+            }
+            _ = &mut writer => break, // El socket se cerró o dejó de aceptar escrituras
+        }
+    }
+
+    outbox.close();
+    if tokio::time::timeout(CLOSE_TIMEOUT, &mut writer).await.is_err() {
+        writer.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Aplicación de un merge patch según RFC 7386, como lo hace el cliente
+    fn merge(target: &mut Value, patch: &Value) {
+        let Value::Object(patch) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        let target = target.as_object_mut().unwrap();
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+
+    #[test]
+    fn diff_without_changes_is_none() {
+        let value = json!({ "a": 1, "b": { "c": [1, 2] } });
+        assert_eq!(diff(&value, &value.clone()), None);
+    }
+
+    #[test]
+    fn diff_removed_key_is_null() {
+        let patch = diff(&json!({ "a": 1, "b": 2 }), &json!({ "a": 1 }));
+        assert_eq!(patch, Some(json!({ "b": null })));
+    }
+
+    #[test]
+    fn diff_nested_change_only_carries_the_change() {
+        let old = json!({ "BTC:Binance:Bybit": { "net": 0.1, "buy": 100.0 }, "ETH:Binance:Bybit": { "net": 0.2 } });
+        let new = json!({ "BTC:Binance:Bybit": { "net": 0.3, "buy": 100.0 }, "ETH:Binance:Bybit": { "net": 0.2 } });
+        assert_eq!(diff(&old, &new), Some(json!({ "BTC:Binance:Bybit": { "net": 0.3 } })));
+        // Los arrays y escalares se reemplazan enteros
+        assert_eq!(diff(&json!({ "levels": [1, 2] }), &json!({ "levels": [1, 3] })), Some(json!({ "levels": [1, 3] })));
+    }
+
+    #[test]
+    fn diff_round_trips_through_merge_patch() {
+        let cases = [
+            (json!({ "a": 1, "b": { "c": 2, "d": 3 } }), json!({ "b": { "c": 4 }, "e": [1, 2] })),
+            (json!({ "a": { "b": { "c": 1 } } }), json!({ "a": { "b": { "c": 1, "d": "x" } } })),
+            (json!({ "a": 1 }), json!({})),
+            (json!({ "a": [1] }), json!({ "a": { "b": 1 } })),
+            (json!(1), json!({ "a": 1 })),
+        ];
+        for (old, new) in cases {
+            let mut patched = old.clone();
+            merge(&mut patched, &diff(&old, &new).unwrap());
+            assert_eq!(patched, new, "{} -> {}", old, new);
+        }
+    }
+
+    #[test]
+    fn topic_parses_books_symbol() {
+        assert_eq!("books:btc-usdt".parse::<Topic>(), Ok(Topic::Books("BTC-USDT".to_string())));
+        assert!(" opportunities".parse::<Topic>().is_err());
+        assert!("books:".parse::<Topic>().is_err());
+        assert!("books: ".parse::<Topic>().is_err());
+        assert!("trades:BTC".parse::<Topic>().is_err());
+        assert_eq!("funding".parse::<Topic>(), Ok(Topic::Funding));
+        // Display vuelve a dar lo que se parsea
+        for topic in [Topic::Opportunities, Topic::Health, Topic::Books("ETH-USDT".to_string())] {
+            assert_eq!(topic.to_string().parse::<Topic>(), Ok(topic));
+        }
+    }
+
+    #[test]
+    fn subscribe_message_with_filter() {
+        let raw = r#"{"op":"subscribe","topic":"books:sol-usdt","filter":{"symbols":["SOL-USDT"],"min_net_pct":0.05}}"#;
+        let Ok(ClientMessage::Subscribe { topic, filter, throttle_ms }) = serde_json::from_str(raw) else { panic!("no parsea: {}", raw) };
+        assert_eq!(topic, Topic::Books("SOL-USDT".to_string()));
+        assert_eq!(throttle_ms, None);
+        assert_eq!(filter.min_net_pct, Some(0.05));
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"op":"subscribe","topic":"nope"}"#).is_err());
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"op":"subscribe","topic":"stats","filter":{"symbol":"X"}}"#).is_err());
+    }
+
+    #[test]
+    fn filter_matches_symbols_and_net() {
+        let all = Filter::default();
+        assert!(all.symbol("BTC-USDT") && all.net(-1.0));
+
+        let filter = Filter { symbols: vec!["btc-usdt".to_string()], min_net_pct: Some(0.1) };
+        assert!(filter.symbol("BTC-USDT"));
+        assert!(!filter.symbol("ETH-USDT"));
+        assert!(filter.net(0.1));
+        assert!(!filter.net(0.05));
+    }
+}
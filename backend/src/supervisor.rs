@@ -0,0 +1,143 @@
+// src/supervisor.rs
+//
+// Dueño de todas las tareas de fondo (conectores, servidor, feeds auxiliares).
+// Si una tarea termina o entra en pánico se relanza con backoff; al apagar se
+// avisa a todas y se espera un tiempo acotado antes de abortarlas.
+
+use futures_util::future::join_all;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Una tarea que corrió al menos esto se considera sana: el backoff vuelve al mínimo
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+// Aviso de apagado para las tareas que necesitan cerrar ordenadamente
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    pub async fn wait(&mut self) {
+        // Err = el supervisor ya no existe: también es apagado
+        let _ = self.rx.wait_for(|down| *down).await;
+    }
+}
+
+pub struct Supervisor {
+    shutdown_tx: watch::Sender<bool>,
+    tasks: Vec<(String, JoinHandle<()>)>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+        Self { shutdown_tx, tasks: Vec::new() }
+    }
+
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal { rx: self.shutdown_tx.subscribe() }
+    }
+
+    // Tarea sin estado que perder: al apagar se aborta
+    pub fn spawn<F, Fut>(&mut self, name: impl Into<String>, make_task: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_inner(name.into(), move |_| make_task(), false);
+    }
+
+    // Tarea que escucha la señal y cierra sola (p. ej. el servidor drenando conexiones)
+    pub fn spawn_graceful<F, Fut>(&mut self, name: impl Into<String>, make_task: F)
+    where
+        F: Fn(ShutdownSignal) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_inner(name.into(), make_task, true);
+    }
+
+    fn spawn_inner<F, Fut>(&mut self, name: String, make_task: F, graceful: bool)
+    where
+        F: Fn(ShutdownSignal) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut signal = self.signal();
+        let task_name = name.clone();
+        let handle = tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let started = tokio::time::Instant::now();
+                let mut task = tokio::spawn(make_task(signal.clone()));
+                tokio::select! {
+                    result = &mut task => match result {
+                        Ok(()) => tracing::warn!("🔁 Tarea {} terminó, relanzando en {:?}", task_name, backoff),
+                        Err(e) if e.is_panic() => tracing::error!("💥 Tarea {} entró en pánico, relanzando en {:?}", task_name, backoff),
+                        Err(_) => return,
+                    },
+                    _ = signal.wait() => {
+                        if graceful {
+                            // La tarea recibió la misma señal; el límite lo pone `shutdown`
+                            let _ = task.await;
+                        } else {
+                            task.abort();
+                        }
+                        return;
+                    }
+                }
+
+                if started.elapsed() >= HEALTHY_RUN {
+                    backoff = INITIAL_BACKOFF;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = signal.wait() => return,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+        self.tasks.push((name, handle));
+    }
+
+    // Avisa a todas las tareas y espera hasta `grace`; lo que quede se aborta
+    pub async fn shutdown(self, grace: Duration) {
+        let _ = self.shutdown_tx.send(true);
+        let (names, handles): (Vec<String>, Vec<JoinHandle<()>>) = self.tasks.into_iter().unzip();
+        let aborts: Vec<_> = handles.iter().map(|h| h.abort_handle()).collect();
+        if tokio::time::timeout(grace, join_all(handles)).await.is_err() {
+            tracing::warn!("⏱️ Tareas sin cerrar tras {:?}, abortando", grace);
+            for (name, abort) in names.iter().zip(aborts) {
+                if !abort.is_finished() {
+                    tracing::warn!("⏱️ Abortando {}", name);
+                    abort.abort();
+                }
+            }
+        }
+    }
+}
+
+// SIGINT (Ctrl-C) o SIGTERM
+pub async fn wait_for_os_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => { sigterm.recv().await; }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -12,6 +12,14 @@ interface Trade {
   leverage: number;
 }
 
+interface FxAssumption {
+  exchange: string;
+  from: string;
+  to: string;
+  rate: number;
+  live: boolean;
+}
+
 interface ArbitrageOpportunity {
   symbol: string;
   buy_exchange: string;
@@ -25,6 +33,23 @@ interface ArbitrageOpportunity {
   liquidity_bottleneck: string;
   timestamp: number;
   data_age_ms: number;
+  fx?: FxAssumption[];
+}
+
+interface FundingOpportunity {
+  symbol: string;
+  long_exchange: string;
+  short_exchange: string;
+  long_rate_hourly_pct: number;
+  short_rate_hourly_pct: number;
+  holding_hours: number;
+  funding_diff_pct: number;
+  entry_exit_cost_pct: number;
+  net_edge_pct: number;
+  apr_pct: number;
+  next_funding_long: number;
+  next_funding_short: number;
+  timestamp: number;
 }
 
 interface SimStats {
@@ -39,6 +64,7 @@ interface SimStats {
 
 interface DashboardPayload {
   opportunities: ArbitrageOpportunity[];
+  funding_opportunities: FundingOpportunity[];
   stats: SimStats;
   last_trades: Trade[];
 }
@@ -84,11 +110,62 @@ const TradeHistory = ({ trades }: { trades: Trade[] }) => (
   </div>
 );
 
+const FundingPanel = ({ funding }: { funding: FundingOpportunity[] }) => (
+  <div className="bg-[#0f0f11] border border-white/5 rounded-[2rem] p-8 shadow-2xl relative overflow-hidden max-h-[420px] flex flex-col">
+    <div className="flex justify-between items-center mb-6 border-b border-white/5 pb-4">
+      <h3 className="text-[10px] font-black uppercase tracking-[0.3em] text-gray-400 flex items-center gap-2">
+        <TrendingUp size={14} className="text-accent" /> Funding Carry
+      </h3>
+      <span className="text-[9px] text-gray-600 font-black uppercase">{funding[0]?.holding_hours ?? 0}h horizon</span>
+    </div>
+
+    <div className="space-y-3 overflow-y-auto pr-2 custom-scrollbar flex-1">
+      {funding.length === 0 ? (
+        <p className="text-[10px] text-gray-600 italic text-center mt-6 uppercase tracking-widest">Sin diferenciales de funding...</p>
+      ) : (
+        funding.map((f, i) => (
+          <div key={i} className="flex flex-col p-4 bg-white/[0.02] border border-white/5 rounded-2xl">
+            <div className="flex justify-between items-start mb-2">
+              <span className="text-sm font-black italic tracking-tighter text-white">{f.symbol}</span>
+              <span className="text-[11px] font-black text-green-400">+{f.net_edge_pct.toFixed(4)}%</span>
+            </div>
+            <div className="flex justify-between items-center text-[9px] font-black text-gray-500">
+              <div className="flex items-center gap-1.5">
+                <span className="text-blue-400">LONG {f.long_exchange}</span>
+                <ArrowRight size={10} />
+                <span className="text-purple-400">SHORT {f.short_exchange}</span>
+              </div>
+              <span className="uppercase">APR {f.apr_pct.toFixed(1)}%</span>
+            </div>
+            <div className="text-[8px] text-gray-600 mt-1 uppercase font-bold">
+              {f.long_rate_hourly_pct.toFixed(5)}%/h vs {f.short_rate_hourly_pct.toFixed(5)}%/h · Costs {f.entry_exit_cost_pct.toFixed(3)}%
+            </div>
+          </div>
+        ))
+      )}
+    </div>
+  </div>
+);
+
 const INITIAL_CAPITAL = 44.97;
 
+// Mismo host que sirve el dashboard (wss si la página vino por https); VITE_WS_URL lo pisa en desarrollo
+const WS_URL: string = import.meta.env.VITE_WS_URL || `${location.protocol === 'https:' ? 'wss' : 'ws'}://${location.host}/ws`;
+// El probe /health no pide credenciales: sirve para saber si el socket falló por el token
+const HEALTH_URL = WS_URL.replace(/^ws/, 'http').replace(/\/ws$/, '/health');
+// El token se pide al operador y vive solo en la pestaña, nunca en el bundle
+const TOKEN_KEY = 'dashboard_token';
+
+const askToken = () => {
+  const token = window.prompt('Token del dashboard');
+  if (token) sessionStorage.setItem(TOKEN_KEY, token);
+  else sessionStorage.removeItem(TOKEN_KEY);
+};
+
 function App() {
   const [opportunities, setOpportunities] = useState<ArbitrageOpportunity[]>([]);
   const [recentTrades, setRecentTrades] = useState<Trade[]>([]);
+  const [funding, setFunding] = useState<FundingOpportunity[]>([]);
   const [stats, setStats] = useState<SimStats>({ 
     total_usd: INITIAL_CAPITAL, binance_usd: 20, bybit_usd: 0.0, 
     hyperliquid_usd: 0.0, extended_usd: 24, 
@@ -101,15 +178,24 @@ function App() {
 
   useEffect(() => {
     const connect = () => {
-      const ws = new WebSocket('ws://13.158.22.50:3030/ws');
-      ws.onopen = () => setConnected(true);
-      ws.onclose = () => { setConnected(false); setTimeout(connect, 3000); };
+      // Con usuarios en el backend el navegador se identifica con ?token= (no puede mandar headers)
+      const token = sessionStorage.getItem(TOKEN_KEY);
+      const ws = new WebSocket(`${WS_URL}${token ? `?token=${encodeURIComponent(token)}` : ''}`);
+      let opened = false;
+      ws.onopen = () => { opened = true; setConnected(true); };
+      ws.onclose = async () => {
+        setConnected(false);
+        // Si el backend responde pero el socket nunca abrió, el token falta o es inválido
+        if (!opened && await fetch(HEALTH_URL).then(() => true, () => false)) askToken();
+        setTimeout(connect, 3000);
+      };
       ws.onmessage = (event) => {
         try {
           const data: DashboardPayload = JSON.parse(event.data);
           if (data.stats) {
             setStats(data.stats);
             setOpportunities(data.opportunities || []);
+            setFunding(data.funding_opportunities || []);
             setRecentTrades(data.last_trades || []);
             setHistory(prev => {
               const now = new Date().toLocaleTimeString([], { hour: '2-digit', minute: '2-digit', second: '2-digit' });
@@ -289,6 +375,11 @@ function App() {
                         <span className="text-purple-400">{op.sell_exchange}</span>
                       </div>
                       <span className="text-[9px] text-blue-500 font-bold uppercase">{getOrderAge(op.timestamp)}</span>
+                      {op.fx?.map(fx => (
+                        <span key={fx.exchange} className={clsx("text-[9px] font-bold uppercase", fx.live ? "text-gray-500" : "text-yellow-500")}>
+                          {fx.exchange} {fx.from}→{fx.to} {fx.live ? fx.rate.toFixed(5) : '1:1 (sin FX)'}
+                        </span>
+                      ))}
                     </div>
                   </div>
                   <div className="text-right">
@@ -312,6 +403,7 @@ function App() {
         <div className="space-y-10">          
           {/* 3. Colocamos el nuevo componente aquí */}
           <TradeHistory trades={recentTrades} />
+          <FundingPanel funding={funding} />
           <div className="bg-[#0f0f11] border border-white/5 rounded-[2rem] p-10 h-96 shadow-2xl">
              <h3 className="text-[10px] font-black text-gray-600 uppercase tracking-[0.3em] mb-10 italic">Portfolio Performance</h3>
              <div className="h-[220px]">